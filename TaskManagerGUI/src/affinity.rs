@@ -0,0 +1,150 @@
+use std::path::Path;
+use nix::sched::{self, CpuSet};
+use nix::unistd::Pid;
+use crate::priority;
+use crate::users::Permission;
+
+// CPU AFFINITY
+//
+// Masks go through sched_getaffinity(2) / sched_setaffinity(2). Like nice values they belong to a thread:
+// a process' mask is the one of its main thread, and pinning the whole process means changing every thread
+// in /proc/<pid>/task (threads started later copy the mask of the thread that creates them).
+// CPU lists are written like taskset and /proc/<pid>/status do, e.g. "0-3,8".
+
+// CPUs the thread may run on, lowest first
+pub fn get(pid: u32) -> Result<Vec<usize>, String> {
+    let set = sched::sched_getaffinity(Pid::from_raw(pid as i32))
+        .map_err(|e| priority::explain(pid, std::io::Error::from_raw_os_error(e as i32), "read the CPU affinity of"))?;
+    Ok((0..CpuSet::count()).filter(|cpu| set.is_set(*cpu).unwrap_or(false)).collect())
+}
+
+// Pin a process to `cpus`, either just its main thread or every thread. Returns how many threads were changed.
+pub fn set(proc_root: &Path, pid: u32, cpus: &[usize], all_threads: bool) -> Result<usize, String> {
+    if cpus.is_empty() {
+        return Err("the CPU list is empty, a process needs at least one CPU".to_string());
+    }
+    let mut set = CpuSet::new();
+    for cpu in cpus {
+        set.set(*cpu).map_err(|_| format!("CPU {} is out of range", cpu))?;
+    }
+    crate::users::check_owner(proc_root, pid, Permission::Schedule, "change the CPU affinity of")?;
+    let tids = if all_threads { priority::thread_ids(proc_root, pid) } else { vec![pid] };
+    for tid in &tids {
+        if let Err(e) = sched::sched_setaffinity(Pid::from_raw(*tid as i32), &set) {
+            // EINVAL: none of the CPUs is online or the process is limited to other CPUs (cpuset cgroup)
+            if e == nix::errno::Errno::EINVAL {
+                return Err(format!("CPUs {} are not available to PID {}", format_cpu_list(cpus), pid));
+            }
+            return Err(priority::explain(pid, std::io::Error::from_raw_os_error(e as i32), "set the CPU affinity of"));
+        }
+    }
+    Ok(tids.len())
+}
+
+// Mask of every thread of a process, to see when threads were pinned separately
+pub fn thread_masks(proc_root: &Path, pid: u32) -> Vec<(u32, Result<Vec<usize>, String>)> {
+    priority::thread_ids(proc_root, pid).into_iter().map(|tid| (tid, get(tid))).collect()
+}
+
+// "0-3,8" -> [0, 1, 2, 3, 8], only CPUs below `cpus` are accepted
+pub fn parse_cpu_list(text: &str, cpus: usize) -> Result<Vec<usize>, String> {
+    let mut list = Vec::new();
+    for part in text.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (part, part),
+        };
+        let first: usize = first.parse().map_err(|_| format!("'{}' is not a CPU number or range", part))?;
+        let last: usize = last.parse().map_err(|_| format!("'{}' is not a CPU number or range", part))?;
+        if first > last {
+            return Err(format!("'{}' is a backwards range", part));
+        }
+        if last >= cpus {
+            return Err(format!("CPU {} does not exist, this machine has CPUs 0-{}", last, cpus.saturating_sub(1)));
+        }
+        list.extend(first..=last);
+    }
+    list.sort();
+    list.dedup();
+    if list.is_empty() {
+        return Err("the CPU list is empty, a process needs at least one CPU".to_string());
+    }
+    Ok(list)
+}
+
+// [0, 1, 2, 3, 8] -> "0-3,8"
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < cpus.len() {
+        let start = cpus[i];
+        while i + 1 < cpus.len() && cpus[i + 1] == cpus[i] + 1 {
+            i += 1;
+        }
+        parts.push(if cpus[i] == start { start.to_string() } else { format!("{}-{}", start, cpus[i]) });
+        i += 1;
+    }
+    parts.join(",")
+}
+
+// Whether a mask covers every one of the machine's `cpus` CPUs, i.e. the process is not pinned
+pub fn is_unpinned(mask: &[usize], cpus: usize) -> bool {
+    (0..cpus).all(|cpu| mask.contains(&cpu))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lists_and_ranges() {
+        assert_eq!(parse_cpu_list("0-3,8", 16), Ok(vec![0, 1, 2, 3, 8]));
+        assert_eq!(parse_cpu_list(" 2 , 0 - 1 ,", 4), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_cpu_list("5", 8), Ok(vec![5]));
+        assert_eq!(parse_cpu_list("3-3", 8), Ok(vec![3]));
+    }
+
+    #[test]
+    fn duplicates_and_overlaps_are_merged() {
+        assert_eq!(parse_cpu_list("1,1,0-2,2", 4), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_cpu_list("4-7,0-5", 8), Ok((0..8).collect()));
+    }
+
+    #[test]
+    fn rejects_bad_lists() {
+        assert_eq!(parse_cpu_list("3-1", 8), Err("'3-1' is a backwards range".to_string()));
+        assert_eq!(
+            parse_cpu_list("0-3,8", 8),
+            Err("CPU 8 does not exist, this machine has CPUs 0-7".to_string())
+        );
+        assert!(parse_cpu_list("4", 4).is_err()); // CPUs count from 0
+        assert!(parse_cpu_list("", 4).is_err());
+        assert!(parse_cpu_list(" , ", 4).is_err());
+        assert!(parse_cpu_list("a-3", 4).is_err());
+        assert!(parse_cpu_list("-1", 4).is_err());
+        assert!(parse_cpu_list("1-", 4).is_err());
+    }
+
+    #[test]
+    fn formats_ranges() {
+        assert_eq!(format_cpu_list(&[0, 1, 2, 3, 8]), "0-3,8");
+        assert_eq!(format_cpu_list(&[0, 2, 4]), "0,2,4");
+        assert_eq!(format_cpu_list(&[6, 7]), "6-7");
+        assert_eq!(format_cpu_list(&[]), "");
+    }
+
+    #[test]
+    fn round_trips() {
+        for text in ["0-3,8", "0", "1,3,5-7", "0-63"] {
+            assert_eq!(format_cpu_list(&parse_cpu_list(text, 64).unwrap()), text);
+        }
+        // written the long way, read back in the short form
+        assert_eq!(format_cpu_list(&parse_cpu_list("8,3,2,1,0,2", 16).unwrap()), "0-3,8");
+    }
+
+    #[test]
+    fn unpinned_means_every_cpu() {
+        assert!(is_unpinned(&[0, 1, 2, 3], 4));
+        assert!(!is_unpinned(&[0, 1, 2], 4));
+    }
+}
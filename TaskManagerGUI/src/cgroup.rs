@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+use crate::units::{self, MemoryUnit};
+
+// CONTROL GROUPS (cgroup v2)
+//
+// A process' group comes from the "0::/path" line of /proc/<pid>/cgroup. Limits and usage are read from the
+// group's directory under the unified hierarchy, as the kernel reports them (memory.max, cpu.max, ...).
+// On hybrid hosts (v1 controllers plus v2 at /sys/fs/cgroup/unified) the v2 groups exist but have no
+// controllers, so their limits show up as unavailable.
+
+// Group path like "/system.slice/nginx.service", falling back to the v1 systemd hierarchy on v1-only hosts
+pub fn read_cgroup(proc_root: &Path, pid: u32) -> Option<String> {
+    let text = std::fs::read_to_string(proc_root.join(pid.to_string()).join("cgroup")).ok()?;
+    let mut systemd = None;
+    for line in text.lines() {
+        // hierarchy-id:controllers:path, the path may contain ':'
+        let mut fields = line.splitn(3, ':');
+        let (id, controllers, path) = (fields.next()?, fields.next()?, fields.next()?);
+        if id == "0" && controllers.is_empty() {
+            return Some(path.to_string());
+        }
+        if controllers == "name=systemd" {
+            systemd = Some(path.to_string());
+        }
+    }
+    systemd
+}
+
+// Where the v2 hierarchy is mounted: /sys/fs/cgroup on unified hosts, /sys/fs/cgroup/unified on hybrid ones
+pub fn unified_root(sys_root: &Path) -> Option<PathBuf> {
+    let base = sys_root.join("fs").join("cgroup");
+    [base.clone(), base.join("unified")].into_iter().find(|dir| dir.join("cgroup.procs").exists())
+}
+
+// Limits and usage of one group, None where the controller is not enabled for it
+#[derive(Clone, Debug, Default)]
+pub struct CgroupLimits {
+    pub memory_current: Option<u64>, // bytes
+    pub memory_max: Option<Limit<u64>>,
+    pub cpu_max: Option<Limit<(u64, u64)>>, // (quota, period) in microseconds
+    pub cpu_usage_usec: Option<u64>, // total CPU time used, from cpu.stat
+    pub pids_current: Option<u64>,
+    pub pids_max: Option<Limit<u64>>,
+}
+
+// A limit file says either "max" or a number
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Limit<T> {
+    Unlimited,
+    Value(T),
+}
+
+impl<T: Copy> Limit<T> {
+    fn show(self, format: impl Fn(T) -> String) -> String {
+        match self {
+            Limit::Unlimited => "max".to_string(),
+            Limit::Value(value) => format(value),
+        }
+    }
+}
+
+impl CgroupLimits {
+    // cpu.max as a number of CPUs, e.g. "150000 100000" -> 1.5
+    pub fn cpu_limit(&self) -> Option<f64> {
+        match self.cpu_max? {
+            Limit::Value((quota, period)) if period > 0 => Some(quota as f64 / period as f64),
+            _ => None,
+        }
+    }
+
+    // "memory 120.0 MiB / 512.0 MiB, cpu max 1.50 CPUs, pids 12 / max", leaving out controllers that are off
+    pub fn describe(&self, unit: MemoryUnit) -> String {
+        let mut parts = Vec::new();
+        if let Some(current) = self.memory_current {
+            let max = self.memory_max.map_or("?".to_string(), |max| max.show(|bytes| units::format_bytes(bytes, unit)));
+            parts.push(format!("memory {} / {}", units::format_bytes(current, unit), max));
+        }
+        if let Some(cpu_max) = self.cpu_max {
+            parts.push(format!("cpu max {}", cpu_max.show(|_| format!("{:.2} CPUs", self.cpu_limit().unwrap_or(0.0)))));
+        }
+        if let Some(current) = self.pids_current {
+            parts.push(format!("pids {} / {}", current, self.pids_max.map_or("?".to_string(), |max| max.show(|n| n.to_string()))));
+        }
+        if parts.is_empty() {
+            return "no controllers enabled".to_string();
+        }
+        parts.join(", ")
+    }
+}
+
+pub fn read_limits(root: &Path, cgroup: &str) -> Result<CgroupLimits, String> {
+    let dir = root.join(cgroup.trim_start_matches('/'));
+    if !dir.is_dir() {
+        return Err(format!("{} does not exist", dir.display()));
+    }
+    let read = |file: &str| std::fs::read_to_string(dir.join(file)).ok().map(|text| text.trim().to_string());
+    let number = |file: &str| read(file).and_then(|text| text.parse::<u64>().ok());
+    let limit = |file: &str| {
+        read(file).and_then(|text| match text.as_str() {
+            "max" => Some(Limit::Unlimited),
+            value => value.parse().ok().map(Limit::Value),
+        })
+    };
+    Ok(CgroupLimits {
+        memory_current: number("memory.current"),
+        memory_max: limit("memory.max"),
+        // "max 100000" or "200000 100000"
+        cpu_max: read("cpu.max").and_then(|text| {
+            let (quota, period) = text.split_once(' ')?;
+            let period = period.parse().ok()?;
+            match quota {
+                "max" => Some(Limit::Unlimited),
+                quota => Some(Limit::Value((quota.parse().ok()?, period))),
+            }
+        }),
+        cpu_usage_usec: read("cpu.stat").and_then(|text| {
+            text.lines().find_map(|line| line.strip_prefix("usage_usec ").and_then(|v| v.trim().parse().ok()))
+        }),
+        pids_current: number("pids.current"),
+        pids_max: limit("pids.max"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    // A fake /proc and /sys under the temp directory, removed again when the test ends
+    struct FakeRoot {
+        root: PathBuf,
+    }
+
+    impl FakeRoot {
+        fn new(test: &str) -> FakeRoot {
+            let root = std::env::temp_dir().join(format!("taskmanager-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            FakeRoot { root }
+        }
+
+        fn file(&self, path: &str, text: &str) -> &Self {
+            let path = self.root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+            self
+        }
+    }
+
+    impl Drop for FakeRoot {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn group_from_proc_cgroup() {
+        let fake = FakeRoot::new("cgroup-proc");
+        fake.file("100/cgroup", "0::/system.slice/nginx.service\n")
+            // hybrid host: v1 controller lines around the v2 one
+            .file("200/cgroup", "12:cpu,cpuacct:/user.slice\n1:name=systemd:/user.slice/user-1000.slice/session-2.scope\n0::/user.slice/user-1000.slice/session-2.scope\n")
+            // v1 only: the systemd hierarchy is the best there is
+            .file("300/cgroup", "4:memory:/docker/abc\n1:name=systemd:/docker/abc\n")
+            .file("400/cgroup", "4:memory:/docker/abc\n")
+            // paths may contain ':'
+            .file("500/cgroup", "0::/machine.slice/libpod-abc:def.scope\n");
+        assert_eq!(read_cgroup(&fake.root, 100).as_deref(), Some("/system.slice/nginx.service"));
+        assert_eq!(read_cgroup(&fake.root, 200).as_deref(), Some("/user.slice/user-1000.slice/session-2.scope"));
+        assert_eq!(read_cgroup(&fake.root, 300).as_deref(), Some("/docker/abc"));
+        assert_eq!(read_cgroup(&fake.root, 400), None);
+        assert_eq!(read_cgroup(&fake.root, 500).as_deref(), Some("/machine.slice/libpod-abc:def.scope"));
+        assert_eq!(read_cgroup(&fake.root, 600), None);
+    }
+
+    #[test]
+    fn unified_or_hybrid_mount() {
+        let fake = FakeRoot::new("cgroup-mount");
+        assert_eq!(unified_root(&fake.root), None);
+        fake.file("fs/cgroup/unified/cgroup.procs", "1\n");
+        assert_eq!(unified_root(&fake.root), Some(fake.root.join("fs/cgroup/unified")));
+        fake.file("fs/cgroup/cgroup.procs", "1\n");
+        assert_eq!(unified_root(&fake.root), Some(fake.root.join("fs/cgroup")));
+    }
+
+    #[test]
+    fn limits_and_max() {
+        let fake = FakeRoot::new("cgroup-limits");
+        fake.file("system.slice/nginx.service/memory.current", "125829120\n")
+            .file("system.slice/nginx.service/memory.max", "max\n")
+            .file("system.slice/nginx.service/cpu.max", "max 100000\n")
+            .file("system.slice/nginx.service/cpu.stat", "usage_usec 8123456\nuser_usec 6000000\nsystem_usec 2123456\n")
+            .file("system.slice/nginx.service/pids.current", "12\n")
+            .file("system.slice/nginx.service/pids.max", "4915\n");
+        let limits = read_limits(&fake.root, "/system.slice/nginx.service").unwrap();
+        assert_eq!(limits.memory_current, Some(120 * MIB));
+        assert_eq!(limits.memory_max, Some(Limit::Unlimited));
+        assert_eq!(limits.cpu_max, Some(Limit::Unlimited));
+        assert_eq!(limits.cpu_limit(), None);
+        assert_eq!(limits.cpu_usage_usec, Some(8123456));
+        assert_eq!((limits.pids_current, limits.pids_max), (Some(12), Some(Limit::Value(4915))));
+        assert_eq!(limits.describe(MemoryUnit::MiB), "memory 120.0 MiB / max, cpu max max, pids 12 / 4915");
+
+        fake.file("system.slice/nginx.service/memory.max", "536870912\n")
+            .file("system.slice/nginx.service/cpu.max", "50000 100000\n");
+        let limits = read_limits(&fake.root, "/system.slice/nginx.service").unwrap();
+        assert_eq!(limits.memory_max, Some(Limit::Value(512 * MIB)));
+        assert_eq!(limits.cpu_max, Some(Limit::Value((50000, 100000))));
+        assert_eq!(limits.cpu_limit(), Some(0.5));
+        assert!(limits.describe(MemoryUnit::MiB).starts_with("memory 120.0 MiB / 512.0 MiB, cpu max 0.50 CPUs"));
+    }
+
+    #[test]
+    fn controllers_off_or_group_gone() {
+        let fake = FakeRoot::new("cgroup-empty");
+        // the group exists but no controller is enabled for it (v2 groups on a hybrid host)
+        fake.file("user.slice/cgroup.procs", "1234\n").file("user.slice/cpu.max", "garbage\n");
+        let limits = read_limits(&fake.root, "/user.slice").unwrap();
+        assert_eq!(limits.memory_max, None);
+        assert_eq!(limits.cpu_max, None);
+        assert_eq!(limits.describe(MemoryUnit::Auto), "no controllers enabled");
+        assert!(read_limits(&fake.root, "/system.slice/gone.service").unwrap_err().contains("does not exist"));
+    }
+}
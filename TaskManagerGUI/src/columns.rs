@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::snapshot::{self, Aggregated, GroupBy};
+use crate::units::{self, CpuMode, MemoryUnit};
+
+// PROCESS TABLE COLUMNS
+//
+// Every field of an aggregated row as a column, so the GUI table and the CLI text output are built from the
+// same list. Which columns are shown and in what order is `display.columns` in the config, GUI widths are in
+// `display.column_widths`; columns without a width are fitted to their content.
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    Pid,
+    Ppid,
+    Name, // the group label: process name, cgroup, container, ...
+    #[serde(alias = "rss")]
+    Memory,
+    Cpu,
+    Io, // read and write rate together
+    Read,
+    Write,
+    ReadTotal, // bytes since the process started
+    WriteTotal,
+    Threads,
+    Processes, // how many processes the row adds up
+    Nice,
+    Priority,
+    Affinity,
+    Status,
+    User,
+    Uid, // real / effective UID
+    Container,
+    Unit,
+    Cgroup, // the limits instead when grouping by cgroup
+    Command,
+    Exe,
+}
+
+pub const ALL_COLUMNS: [Column; 23] = [
+    Column::Pid,
+    Column::Ppid,
+    Column::Name,
+    Column::Memory,
+    Column::Cpu,
+    Column::Io,
+    Column::Read,
+    Column::Write,
+    Column::ReadTotal,
+    Column::WriteTotal,
+    Column::Threads,
+    Column::Processes,
+    Column::Nice,
+    Column::Priority,
+    Column::Affinity,
+    Column::Status,
+    Column::User,
+    Column::Uid,
+    Column::Container,
+    Column::Unit,
+    Column::Cgroup,
+    Column::Command,
+    Column::Exe,
+];
+
+// What the table showed before columns could be chosen
+pub fn default_columns() -> Vec<Column> {
+    vec![
+        Column::Pid,
+        Column::Name,
+        Column::Memory,
+        Column::Cpu,
+        Column::Io,
+        Column::Nice,
+        Column::Priority,
+        Column::Affinity,
+        Column::Status,
+        Column::User,
+        Column::Uid,
+        Column::Container,
+        Column::Unit,
+        Column::Cgroup,
+        Column::Command,
+    ]
+}
+
+// Everything a cell needs besides its row
+pub struct CellContext<'a> {
+    pub group_by: GroupBy,
+    pub memory_unit: MemoryUnit,
+    pub cpu_mode: CpuMode,
+    pub cpus: usize,
+    pub cgroup_limits: &'a HashMap<String, String>, // cgroup path -> limits text, filled when grouping by cgroup
+}
+
+impl Column {
+    pub fn parse(text: &str) -> Option<Column> {
+        let column = match text.trim().to_lowercase().as_str() {
+            "pid" => Column::Pid,
+            "ppid" | "parent" => Column::Ppid,
+            "name" => Column::Name,
+            "memory" | "mem" | "rss" => Column::Memory,
+            "cpu" => Column::Cpu,
+            "io" | "disk" => Column::Io,
+            "read" => Column::Read,
+            "write" => Column::Write,
+            "read-total" => Column::ReadTotal,
+            "write-total" => Column::WriteTotal,
+            "threads" => Column::Threads,
+            "processes" | "count" => Column::Processes,
+            "nice" | "ni" => Column::Nice,
+            "priority" | "pri" => Column::Priority,
+            "affinity" => Column::Affinity,
+            "status" | "state" => Column::Status,
+            "user" => Column::User,
+            "uid" => Column::Uid,
+            "container" => Column::Container,
+            "unit" => Column::Unit,
+            "cgroup" => Column::Cgroup,
+            "command" | "cmd" | "args" => Column::Command,
+            "exe" | "executable" => Column::Exe,
+            _ => return None,
+        };
+        Some(column)
+    }
+
+    // Name in the config file and for --columns
+    pub fn key(self) -> &'static str {
+        match self {
+            Column::Pid => "pid",
+            Column::Ppid => "ppid",
+            Column::Name => "name",
+            Column::Memory => "memory",
+            Column::Cpu => "cpu",
+            Column::Io => "io",
+            Column::Read => "read",
+            Column::Write => "write",
+            Column::ReadTotal => "read-total",
+            Column::WriteTotal => "write-total",
+            Column::Threads => "threads",
+            Column::Processes => "processes",
+            Column::Nice => "nice",
+            Column::Priority => "priority",
+            Column::Affinity => "affinity",
+            Column::Status => "status",
+            Column::User => "user",
+            Column::Uid => "uid",
+            Column::Container => "container",
+            Column::Unit => "unit",
+            Column::Cgroup => "cgroup",
+            Column::Command => "command",
+            Column::Exe => "exe",
+        }
+    }
+
+    pub fn header(self, cx: &CellContext) -> String {
+        let header = match self {
+            Column::Pid => "PID",
+            Column::Ppid => "PPID",
+            Column::Name => cx.group_by.label(),
+            Column::Memory => {
+                return match cx.memory_unit {
+                    MemoryUnit::Auto => "Memory".to_string(),
+                    unit => format!("Memory ({})", unit.label()),
+                }
+            }
+            Column::Cpu => return cx.cpu_mode.header(),
+            Column::Io => "Disk I/O (read / write)",
+            Column::Read => "Read/s",
+            Column::Write => "Write/s",
+            Column::ReadTotal => "Read total",
+            Column::WriteTotal => "Written total",
+            Column::Threads => "Threads",
+            Column::Processes => "Processes",
+            Column::Nice => "Nice",
+            Column::Priority => "Pri",
+            Column::Affinity => "Affinity",
+            Column::Status => "Status",
+            Column::User => "User",
+            Column::Uid => "UID/EUID",
+            Column::Container => "Container",
+            Column::Unit => "Unit",
+            // each process' cgroup, or the group's limits when the rows are cgroups
+            Column::Cgroup if cx.group_by == GroupBy::Cgroup => "Limits",
+            Column::Cgroup => "Cgroup",
+            Column::Command => "Command",
+            Column::Exe => "Executable",
+        };
+        header.to_string()
+    }
+
+    pub fn text(self, row: &Aggregated, cx: &CellContext) -> String {
+        let rate = |bytes_per_sec: f64| format!("{}/s", units::format_bytes(bytes_per_sec as u64, cx.memory_unit));
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        match self {
+            Column::Pid => or_dash(row.pid.map(|pid| pid.to_string())),
+            Column::Ppid => or_dash(row.parent.map(|pid| pid.to_string())),
+            Column::Name => row.label(cx.group_by),
+            Column::Memory => units::format_bytes(row.memory, cx.memory_unit),
+            Column::Cpu => format!("{:.2}%", cx.cpu_mode.scale(row.cpu, cx.cpus)),
+            Column::Io => format!("{} / {}", rate(row.read_rate), rate(row.write_rate)),
+            Column::Read => rate(row.read_rate),
+            Column::Write => rate(row.write_rate),
+            Column::ReadTotal => units::format_bytes(row.read_bytes, cx.memory_unit),
+            Column::WriteTotal => units::format_bytes(row.written_bytes, cx.memory_unit),
+            Column::Threads => row.threads.to_string(),
+            Column::Processes => row.processes.to_string(),
+            Column::Nice => or_dash(row.nice.map(|nice| nice.to_string())),
+            Column::Priority => or_dash(row.priority.map(|priority| priority.to_string())),
+            // unpinned processes may run anywhere
+            Column::Affinity => row.affinity.clone().unwrap_or_else(|| "all".to_string()),
+            Column::Status => row.status.clone().unwrap_or_else(|| "Unknown".to_string()),
+            Column::User => row.user.clone(),
+            Column::Uid => row.uids.clone(),
+            Column::Container => or_dash(
+                row.cgroup.as_deref().and_then(crate::container::from_cgroup).map(|c| format!("{} {}", c.runtime, c.short_id())),
+            ),
+            Column::Unit => or_dash(row.cgroup.as_deref().and_then(crate::systemd::unit_from_cgroup).map(|unit| unit.label())),
+            Column::Cgroup if cx.group_by == GroupBy::Cgroup => or_dash(cx.cgroup_limits.get(&row.name).cloned()),
+            Column::Cgroup => or_dash(row.cgroup.clone()),
+            // kernel threads have no command line, shown in brackets like ps does; arguments can contain newlines
+            Column::Command => row.cmd.as_ref().map_or(format!("[{}]", row.name), |cmd| cmd.replace(|c: char| c.is_control(), " ")),
+            Column::Exe => or_dash(row.exe.clone()),
+        }
+    }
+
+    // Comes with the CPU and memory refresh (command lines are read once per process), the others need
+    // another /proc file or syscall per process on every refresh
+    pub fn is_cheap(self) -> bool {
+        matches!(
+            self,
+            Column::Pid
+                | Column::Ppid
+                | Column::Name
+                | Column::Memory
+                | Column::Cpu
+                | Column::Threads
+                | Column::Processes
+                | Column::Status
+                | Column::Command
+                | Column::Exe
+        )
+    }
+
+    // Widest a fitted column gets (in characters), longer text is cut in the middle
+    pub fn max_chars(self) -> usize {
+        match self {
+            Column::Command => 60,
+            Column::Cgroup | Column::Exe => 50,
+            Column::Name | Column::Unit => 40,
+            _ => 30,
+        }
+    }
+}
+
+// "pid,name,cpu,rss,user" -> columns, in that order
+pub fn parse_list(text: &str) -> Result<Vec<Column>, String> {
+    let mut columns = Vec::new();
+    for key in text.split(',').filter(|key| !key.trim().is_empty()) {
+        let column = Column::parse(key).ok_or(format!(
+            "unknown column '{}', expected some of {}",
+            key.trim(),
+            ALL_COLUMNS.iter().map(|c| c.key()).collect::<Vec<_>>().join(", ")
+        ))?;
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+    if columns.is_empty() {
+        return Err("no columns given".to_string());
+    }
+    Ok(columns)
+}
+
+// The CLI table: every column as wide as its longest cell (up to max_chars), the last one not padded
+pub fn format_table(columns: &[Column], rows: &[Aggregated], cx: &CellContext) -> Vec<String> {
+    let headers: Vec<String> = columns.iter().map(|column| column.header(cx)).collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|column| snapshot::truncate_middle(&column.text(row, cx), column.max_chars())).collect())
+        .collect();
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| cells.iter().map(|row| row[i].chars().count()).chain([headers[i].chars().count()]).max().unwrap_or(0))
+        .collect();
+    let line = |texts: &[String]| {
+        let mut line = String::new();
+        for (i, text) in texts.iter().enumerate() {
+            if i + 1 == texts.len() {
+                line.push_str(text);
+            } else {
+                line.push_str(&format!("{:<width$}  ", text, width = widths[i]));
+            }
+        }
+        line
+    };
+    std::iter::once(line(&headers)).chain(cells.iter().map(|row| line(row))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{ProcessFilter, ProcessRecord, Snapshot};
+
+    fn record(pid: u32, name: &str, cmd: &str) -> ProcessRecord {
+        serde_json::from_value(serde_json::json!({"pid": pid, "name": name, "mem": 1024, "cpu": 1.0, "st": "Sleep", "cmd": cmd})).unwrap()
+    }
+
+    // one row per process, by PID
+    fn rows(processes: Vec<ProcessRecord>) -> Vec<Aggregated> {
+        let snapshot = Snapshot { cpus: 4, processes, ..Snapshot::default() };
+        let mut rows = snapshot.aggregate(&ProcessFilter::default(), GroupBy::Name);
+        rows.sort_by_key(|row| row.pid);
+        rows
+    }
+
+    fn context(cgroup_limits: &HashMap<String, String>) -> CellContext<'_> {
+        CellContext { group_by: GroupBy::Name, memory_unit: MemoryUnit::MiB, cpu_mode: CpuMode::Irix, cpus: 4, cgroup_limits }
+    }
+
+    #[test]
+    fn aliases_and_keys() {
+        for (text, column) in [
+            ("rss", Column::Memory),
+            ("MEM", Column::Memory),
+            (" parent ", Column::Ppid),
+            ("disk", Column::Io),
+            ("count", Column::Processes),
+            ("ni", Column::Nice),
+            ("pri", Column::Priority),
+            ("state", Column::Status),
+            ("args", Column::Command),
+            ("cmd", Column::Command),
+            ("executable", Column::Exe),
+        ] {
+            assert_eq!(Column::parse(text), Some(column), "{}", text);
+        }
+        // every config key parses back to its column, also through serde
+        for column in ALL_COLUMNS {
+            assert_eq!(Column::parse(column.key()), Some(column));
+            assert_eq!(serde_json::from_str::<Column>(&format!("\"{}\"", column.key())).unwrap(), column);
+        }
+        assert_eq!(serde_json::from_str::<Column>("\"rss\"").unwrap(), Column::Memory);
+        assert_eq!(Column::parse("memory-usage"), None);
+    }
+
+    #[test]
+    fn list_keeps_the_order_and_drops_duplicates() {
+        assert_eq!(parse_list("pid,name,cpu,rss,user").unwrap(), [Column::Pid, Column::Name, Column::Cpu, Column::Memory, Column::User]);
+        assert_eq!(parse_list("cpu, pid ,CPU,mem,memory,,").unwrap(), [Column::Cpu, Column::Pid, Column::Memory]);
+    }
+
+    #[test]
+    fn unknown_or_no_columns() {
+        let error = parse_list("pid,nmae,cpu").unwrap_err();
+        assert!(error.starts_with("unknown column 'nmae', expected some of pid, ppid, name,"), "{}", error);
+        assert_eq!(parse_list("").unwrap_err(), "no columns given");
+        assert_eq!(parse_list(" , ,").unwrap_err(), "no columns given");
+    }
+
+    #[test]
+    fn table_is_padded_to_the_widest_cell() {
+        let limits = HashMap::new();
+        let rows = rows(vec![record(7, "sh", "sh -c true"), record(1234, "python3", "python3 app.py")]);
+        let lines = format_table(&[Column::Pid, Column::Name, Column::Command], &rows, &context(&limits));
+        // two spaces between columns, the last column is not padded
+        assert_eq!(lines, [
+            "PID   Name     Command",
+            "7     sh       sh -c true",
+            "1234  python3  python3 app.py",
+        ]);
+    }
+
+    #[test]
+    fn long_cells_are_cut_to_max_chars() {
+        let limits = HashMap::new();
+        let name = "a-process-name-that-is-much-longer-than-forty-characters";
+        let cmd = format!("/usr/bin/java -Xmx4g -jar /opt/app/{}.jar --config /etc/app/config.yaml", "x".repeat(40));
+        let rows = rows(vec![record(1, name, &cmd), record(2, "sh", "sh")]);
+        let lines = format_table(&[Column::Name, Column::Command, Column::Pid], &rows, &context(&limits));
+
+        assert_eq!(Column::Name.max_chars(), 40);
+        assert_eq!(Column::Command.max_chars(), 60);
+        let first: Vec<&str> = lines[1].split("  ").collect();
+        assert_eq!(first[0].chars().count(), 40);
+        assert!(first[0].starts_with("a-process-") && first[0].contains('…') && first[0].ends_with("characters"));
+        assert_eq!(first[1].chars().count(), 60);
+        assert!(first[1].starts_with("/usr/bin/java") && first[1].ends_with("config.yaml"));
+        // the short row is padded to the cut width, so the PIDs line up
+        let pid_at = |line: &str| line.chars().count() - 1;
+        assert_eq!(pid_at(&lines[1]), pid_at(&lines[2]));
+        assert_eq!(pid_at(&lines[2]), 40 + 2 + 60 + 2);
+    }
+
+    #[test]
+    fn headers_follow_the_context() {
+        let limits = HashMap::new();
+        let mut cx = context(&limits);
+        assert_eq!(Column::Memory.header(&cx), "Memory (MiB)");
+        assert_eq!(Column::Cgroup.header(&cx), "Cgroup");
+        cx.group_by = GroupBy::Cgroup;
+        cx.memory_unit = MemoryUnit::Auto;
+        assert_eq!(Column::Name.header(&cx), "Cgroup");
+        assert_eq!(Column::Cgroup.header(&cx), "Limits");
+        assert_eq!(Column::Memory.header(&cx), "Memory");
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::SortCriteria;
+use crate::columns::{self, Column};
+use crate::snapshot::GroupBy;
+use crate::units::{CpuMode, MemoryUnit};
+
+// PERSISTENT CONFIGURATION ($XDG_CONFIG_HOME/taskmanager/config.toml)
+//
+// Values are layered: built-in defaults < config file < TASKMANAGER_* environment variables < command line flags
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub alerts: AlertConfig,
+    pub refresh: RefreshConfig,
+    pub colors: ColorConfig,
+    pub display: DisplayConfig,
+    pub history: HistoryConfig,
+    pub leaks: LeakConfig,
+    pub actions: ActionConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertConfig {
+    pub cpu_threshold: f32,       // percent
+    pub memory_threshold_mb: u64,
+    pub io_threshold_mb_per_sec: f64, // disk read + write, 0 turns the I/O alert off
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RefreshConfig {
+    pub gui_ms: u64,
+    pub cli_ms: u64,
+    pub watch_ms: u64,
+    pub low_impact: bool, // slower refreshes, only the cheap columns, rare sampling while the window is in the background
+}
+
+// --low-impact refreshes at most this often
+pub const LOW_IMPACT_MIN_MS: u64 = 2000;
+// and this often while the process window is minimized or unfocused, still enough for alerts and notifications
+pub const LOW_IMPACT_BACKGROUND_MS: u64 = 10_000;
+
+// Four increasing percentages splitting values into gray / green / yellow / orange / red
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    pub cpu_breakpoints: [f32; 4],
+    pub memory_breakpoints: [f32; 4],
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub sort: SortCriteria,
+    pub ascending: bool,
+    pub memory_unit: MemoryUnit, // "auto" scales each value, "bytes" shows raw numbers
+    pub cpu_mode: CpuMode, // also decides what alerts.cpu_threshold is compared against
+    pub group_by: GroupBy, // "name", "cgroup", "container", "unit", "user" or "exe"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>, // only show this container (ID prefix, or "host"), usually from --container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>, // only show this systemd unit, usually from --unit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>, // only show processes of this user (name or UID), usually from --user
+    pub columns: Vec<Column>, // shown in this order, e.g. ["pid", "name", "cpu", "rss", "user"]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub column_widths: BTreeMap<String, f32>, // GUI widths in points by column key, the others fit their content
+}
+
+// Optional recording of snapshots for `replay`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    pub path: PathBuf,
+    pub interval_ms: u64,
+    pub max_file_mb: u64, // rotate once the file is this big
+    pub keep_files: usize, // rotated files kept next to the current one
+}
+
+// Leak detector: memory must only grow over the window and faster than the slope
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeakConfig {
+    pub window_secs: u64,
+    pub min_samples: usize,
+    pub min_slope_kb_per_sec: f64,
+}
+
+// Actions the GUI offers besides kill, for machines where they should not be one click away
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActionConfig {
+    pub restart_units: bool, // "Restart unit" in the table's right click menu, always asks first
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        AlertConfig {
+            cpu_threshold: 90.0,       // 90% CPU
+            memory_threshold_mb: 2048, // 2 GB memory
+            io_threshold_mb_per_sec: 0.0, // off
+        }
+    }
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        RefreshConfig {
+            gui_ms: 400,
+            cli_ms: 100,
+            watch_ms: 1000,
+            low_impact: false,
+        }
+    }
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig {
+            cpu_breakpoints: [5.0, 30.0, 60.0, 80.0],
+            memory_breakpoints: [5.0, 20.0, 50.0, 75.0],
+        }
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            sort: SortCriteria::Memory,
+            ascending: false,
+            memory_unit: MemoryUnit::Auto,
+            cpu_mode: CpuMode::Irix,
+            group_by: GroupBy::Name,
+            container: None,
+            unit: None,
+            user: None,
+            columns: columns::default_columns(),
+            column_widths: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            enabled: false,
+            path: default_history_path(),
+            interval_ms: 5000,
+            max_file_mb: 20,
+            keep_files: 3,
+        }
+    }
+}
+
+impl Default for LeakConfig {
+    fn default() -> Self {
+        LeakConfig {
+            window_secs: 300, // 5 minutes
+            min_samples: 10,
+            min_slope_kb_per_sec: 16.0,
+        }
+    }
+}
+
+impl Default for ActionConfig {
+    fn default() -> Self {
+        ActionConfig { restart_units: true }
+    }
+}
+
+impl Config {
+    pub fn memory_threshold_bytes(&self) -> u64 {
+        self.alerts.memory_threshold_mb * 1024 * 1024
+    }
+
+    // 0 when the I/O alert is off
+    pub fn io_threshold_bytes_per_sec(&self) -> f64 {
+        self.alerts.io_threshold_mb_per_sec * 1024.0 * 1024.0
+    }
+
+    // The refresh intervals, raised in low impact mode
+    pub fn gui_interval(&self) -> Duration {
+        Duration::from_millis(self.raised(self.refresh.gui_ms))
+    }
+
+    pub fn cli_interval(&self) -> Duration {
+        Duration::from_millis(self.raised(self.refresh.cli_ms))
+    }
+
+    fn raised(&self, ms: u64) -> u64 {
+        if self.refresh.low_impact {
+            ms.max(LOW_IMPACT_MIN_MS)
+        } else {
+            ms
+        }
+    }
+
+    // The process table columns; low impact mode leaves out the ones costing an extra file per process
+    pub fn table_columns(&self) -> Vec<Column> {
+        if !self.refresh.low_impact {
+            return self.display.columns.clone();
+        }
+        let cheap: Vec<Column> = self.display.columns.iter().copied().filter(|column| column.is_cheap()).collect();
+        if cheap.is_empty() {
+            vec![Column::Pid, Column::Name]
+        } else {
+            cheap
+        }
+    }
+
+    // Check that the values make sense, the message names the offending key
+    pub fn validate(&self) -> Result<(), String> {
+        if self.display.columns.is_empty() {
+            return Err("[display] columns must list at least one column".to_string());
+        }
+        for (key, width) in &self.display.column_widths {
+            if Column::parse(key).is_none() {
+                return Err(format!("[display] column_widths has an unknown column '{}'", key));
+            }
+            if width.is_nan() || *width < 20.0 {
+                return Err(format!("[display] column_widths.{} must be at least 20, got {}", key, width));
+            }
+        }
+        if self.alerts.cpu_threshold.is_nan() || self.alerts.cpu_threshold <= 0.0 {
+            return Err(format!("[alerts] cpu_threshold must be above 0, got {}", self.alerts.cpu_threshold));
+        }
+        if self.display.cpu_mode == CpuMode::Solaris && self.alerts.cpu_threshold > 100.0 {
+            return Err(format!(
+                "[alerts] cpu_threshold can not go above 100 with cpu_mode = \"solaris\", got {}",
+                self.alerts.cpu_threshold
+            ));
+        }
+        if self.alerts.memory_threshold_mb == 0 {
+            return Err("[alerts] memory_threshold_mb must be above 0".to_string());
+        }
+        if self.alerts.io_threshold_mb_per_sec.is_nan() || self.alerts.io_threshold_mb_per_sec < 0.0 {
+            return Err(format!(
+                "[alerts] io_threshold_mb_per_sec must be 0 (off) or above, got {}",
+                self.alerts.io_threshold_mb_per_sec
+            ));
+        }
+        for (key, ms) in [
+            ("gui_ms", self.refresh.gui_ms),
+            ("cli_ms", self.refresh.cli_ms),
+            ("watch_ms", self.refresh.watch_ms),
+        ] {
+            if ms < 50 {
+                return Err(format!("[refresh] {} must be at least 50 ms, got {}", key, ms));
+            }
+        }
+        if self.history.interval_ms < 50 {
+            return Err(format!("[history] interval_ms must be at least 50 ms, got {}", self.history.interval_ms));
+        }
+        if self.history.max_file_mb == 0 {
+            return Err("[history] max_file_mb must be above 0".to_string());
+        }
+        if self.history.enabled && self.history.path.as_os_str().is_empty() {
+            return Err("[history] path must be set when recording is enabled".to_string());
+        }
+        if self.leaks.window_secs == 0 {
+            return Err("[leaks] window_secs must be above 0".to_string());
+        }
+        if self.leaks.min_samples < 2 {
+            return Err(format!("[leaks] min_samples must be at least 2, got {}", self.leaks.min_samples));
+        }
+        if self.leaks.min_slope_kb_per_sec.is_nan() || self.leaks.min_slope_kb_per_sec <= 0.0 {
+            return Err(format!("[leaks] min_slope_kb_per_sec must be above 0, got {}", self.leaks.min_slope_kb_per_sec));
+        }
+        for (key, breakpoints) in [
+            ("cpu_breakpoints", self.colors.cpu_breakpoints),
+            ("memory_breakpoints", self.colors.memory_breakpoints),
+        ] {
+            if breakpoints.iter().any(|b| !(0.0..=100.0).contains(b)) {
+                return Err(format!("[colors] {} must be percentages between 0 and 100, got {:?}", key, breakpoints));
+            }
+            if breakpoints.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(format!("[colors] {} must be strictly increasing, got {:?}", key, breakpoints));
+            }
+        }
+        Ok(())
+    }
+
+    // Read a config file, a missing file just means defaults
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        };
+        let config: Config = toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        config.validate().map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        self.validate()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| format!("cannot serialize config: {}", e))?;
+        std::fs::write(path, text).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+
+    // The config to save after the settings window turned `before` into `after`: only the values that were
+    // edited are copied onto this one (the file as loaded), so one-off flags and TASKMANAGER_* variables that
+    // are in `before` do not end up in the file
+    pub fn with_edits(&self, before: &Config, after: &Config) -> Result<Config, String> {
+        fn to_table(config: &Config) -> Result<toml::Table, String> {
+            toml::Table::try_from(config).map_err(|e| format!("cannot serialize config: {}", e))
+        }
+        fn merge(file: &mut toml::Table, before: &toml::Table, after: &toml::Table) {
+            let keys: Vec<String> = before.keys().chain(after.keys()).cloned().collect();
+            for key in keys {
+                match (before.get(&key), after.get(&key)) {
+                    (Some(toml::Value::Table(old)), Some(toml::Value::Table(new))) => {
+                        if let Some(toml::Value::Table(section)) = file.get_mut(&key) {
+                            merge(section, old, new);
+                        } else if old != new {
+                            file.insert(key, toml::Value::Table(new.clone()));
+                        }
+                    }
+                    (old, Some(new)) if old != Some(new) => {
+                        file.insert(key, new.clone());
+                    }
+                    // an optional value that was cleared
+                    (Some(_), None) => {
+                        file.remove(&key);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut file = to_table(self)?;
+        merge(&mut file, &to_table(before)?, &to_table(after)?);
+        file.try_into().map_err(|e| format!("cannot apply settings: {}", e))
+    }
+
+    // Apply one override, `key` is the flag name without dashes (same as the env variable suffix)
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value.parse::<T>().map_err(|_| format!("invalid value '{}' for {}", value, key))
+        }
+        match key {
+            "cpu-threshold" => self.alerts.cpu_threshold = parse(key, value)?,
+            "memory-threshold-mb" => self.alerts.memory_threshold_mb = parse(key, value)?,
+            "io-threshold-mb-per-sec" => self.alerts.io_threshold_mb_per_sec = parse(key, value)?,
+            "gui-refresh-ms" => self.refresh.gui_ms = parse(key, value)?,
+            "cli-refresh-ms" => self.refresh.cli_ms = parse(key, value)?,
+            "low-impact" => {
+                self.refresh.low_impact = parse_switch(value)
+                    .ok_or(format!("invalid value '{}' for low-impact, expected true/false, yes/no, on/off or 1/0", value))?
+            }
+            "record" => {
+                self.history.enabled = true;
+                self.history.path = PathBuf::from(value);
+            }
+            "memory-unit" => {
+                self.display.memory_unit = MemoryUnit::parse(value)
+                    .ok_or(format!("invalid value '{}' for memory-unit, expected auto, bytes, kib, mib or gib", value))?
+            }
+            "cpu-mode" => {
+                self.display.cpu_mode = CpuMode::parse(value)
+                    .ok_or(format!("invalid value '{}' for cpu-mode, expected irix or solaris", value))?
+            }
+            "group-by" => {
+                self.display.group_by = GroupBy::parse(value)
+                    .ok_or(format!("invalid value '{}' for group-by, expected name, cgroup, container, unit, user or exe", value))?
+            }
+            "container" => self.display.container = Some(value.to_lowercase()).filter(|prefix| !prefix.is_empty()),
+            "unit" => self.display.unit = Some(value.to_string()).filter(|unit| !unit.is_empty()),
+            "user" => self.display.user = Some(value.to_string()).filter(|user| !user.is_empty()),
+            "columns" => self.display.columns = columns::parse_list(value)?,
+            "sort" => {
+                self.display.sort = match value.to_lowercase().as_str() {
+                    "memory" => SortCriteria::Memory,
+                    "cpu" => SortCriteria::CPU,
+                    "io" => SortCriteria::IO,
+                    _ => return Err(format!("invalid value '{}' for sort, expected 'memory', 'cpu' or 'io'", value)),
+                }
+            }
+            _ => return Err(format!("unknown option --{}", key)),
+        }
+        Ok(())
+    }
+}
+
+const OVERRIDE_KEYS: [&str; 15] = [
+    "cpu-threshold",
+    "memory-threshold-mb",
+    "io-threshold-mb-per-sec",
+    "gui-refresh-ms",
+    "cli-refresh-ms",
+    "low-impact",
+    "sort",
+    "memory-unit",
+    "cpu-mode",
+    "group-by",
+    "container",
+    "unit",
+    "user",
+    "columns",
+    "record",
+];
+
+// $XDG_CONFIG_HOME/taskmanager/config.toml, falling back to ~/.config
+pub fn default_config_path() -> PathBuf {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".config"),
+    };
+    base.join("taskmanager").join("config.toml")
+}
+
+// $XDG_STATE_HOME/taskmanager/history.ndjson, falling back to ~/.local/state
+pub fn default_history_path() -> PathBuf {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".local").join("state"),
+    };
+    base.join("taskmanager").join("history.ndjson")
+}
+
+// On/off values the way people write them in the environment, e.g. TASKMANAGER_LOW_IMPACT=1
+fn parse_switch(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "1" | "yes" | "on" | "true" => Some(true),
+        "0" | "no" | "off" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+// Load the config for this run. Recognised flags (`--config <path>`, `--cpu-threshold <n>`, ...) are removed
+// from `args` so the caller only sees its own arguments. Returns the config for this run, the config file
+// without any overrides (what settings are saved on top of) and the path of the file.
+pub fn load_with_overrides(args: &mut Vec<String>) -> Result<(Config, Config, PathBuf), String> {
+    // pull our flags out of the argument list first
+    let mut flags: Vec<(String, String)> = Vec::new();
+    let mut rest = Vec::new();
+    let mut iter = std::mem::take(args).into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.strip_prefix("--") {
+            // a switch, the only flag without a value
+            Some("low-impact") => flags.push(("low-impact".to_string(), "true".to_string())),
+            Some(key) if key == "config" || OVERRIDE_KEYS.contains(&key) => {
+                let value = iter.next().ok_or(format!("missing value for --{}", key))?;
+                flags.push((key.to_string(), value));
+            }
+            _ => rest.push(arg),
+        }
+    }
+    *args = rest;
+
+    let path = flags
+        .iter()
+        .rev()
+        .find(|(key, _)| key == "config")
+        .map(|(_, value)| PathBuf::from(value))
+        .or_else(|| std::env::var_os("TASKMANAGER_CONFIG").map(PathBuf::from))
+        .unwrap_or_else(default_config_path);
+
+    let file = Config::load(&path)?;
+    let mut config = file.clone();
+
+    // environment, e.g. TASKMANAGER_CPU_THRESHOLD=75
+    for key in OVERRIDE_KEYS {
+        let var = format!("TASKMANAGER_{}", key.to_uppercase().replace('-', "_"));
+        if let Ok(value) = std::env::var(&var) {
+            config.set(key, &value).map_err(|e| format!("{}: {}", var, e))?;
+        }
+    }
+    // then command line flags
+    for (key, value) in flags.iter().filter(|(key, _)| key != "config") {
+        config.set(key, value)?;
+    }
+    config.validate()?;
+
+    Ok((config, file, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_saved_without_the_overrides_of_the_run() {
+        let mut file = Config::default();
+        file.alerts.cpu_threshold = 60.0;
+        file.display.column_widths.insert("name".to_string(), 120.0);
+        // what --cpu-threshold 95 --columns pid,name --container abc made of it
+        let mut running = file.clone();
+        running.set("cpu-threshold", "95").unwrap();
+        running.set("columns", "pid,name").unwrap();
+        running.set("container", "abc").unwrap();
+
+        // the settings window changed the memory threshold and cleared the container filter
+        let mut edited = running.clone();
+        edited.alerts.memory_threshold_mb = 4096;
+        edited.display.container = None;
+        let saved = file.with_edits(&running, &edited).unwrap();
+
+        assert_eq!(saved.alerts.memory_threshold_mb, 4096);
+        assert_eq!(saved.alerts.cpu_threshold, 60.0);
+        assert_eq!(saved.display.columns, file.display.columns);
+        assert_eq!(saved.display.container, None);
+        assert_eq!(saved.display.column_widths, file.display.column_widths);
+
+        // an override edited in the window is saved
+        edited.alerts.cpu_threshold = 80.0;
+        assert_eq!(file.with_edits(&running, &edited).unwrap().alerts.cpu_threshold, 80.0);
+        // nothing edited, nothing changes
+        assert_eq!(file.with_edits(&running, &running).unwrap(), file);
+    }
+
+    #[test]
+    fn low_impact_accepts_the_usual_switch_values() {
+        let mut config = Config::default();
+        for value in ["1", "yes", "on", "true", "TRUE", "Yes", " on "] {
+            config.refresh.low_impact = false;
+            config.set("low-impact", value).unwrap();
+            assert!(config.refresh.low_impact, "{}", value);
+        }
+        for value in ["0", "no", "off", "false", "False", "OFF"] {
+            config.refresh.low_impact = true;
+            config.set("low-impact", value).unwrap();
+            assert!(!config.refresh.low_impact, "{}", value);
+        }
+        for value in ["", "2", "enabled", "y"] {
+            let error = config.set("low-impact", value).unwrap_err();
+            assert!(error.contains("for low-impact"), "{}", error);
+        }
+    }
+}
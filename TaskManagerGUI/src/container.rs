@@ -0,0 +1,272 @@
+use std::path::Path;
+
+// CONTAINERS AND NAMESPACES
+//
+// Container runtimes put every container in its own cgroup named after the container ID, e.g.
+//   /system.slice/docker-<id>.scope          (Docker, systemd cgroup driver)
+//   /docker/<id>                             (Docker, cgroupfs driver)
+//   /machine.slice/libpod-<id>.scope         (Podman)
+//   /kubepods.slice/.../cri-containerd-<id>.scope, crio-<id>.scope, /kubepods/burstable/pod<uid>/<id>
+// so the ID is read back from the cgroup path. Namespaces come from the /proc/<pid>/ns/* links.
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ContainerRef {
+    pub runtime: &'static str, // "docker", "podman", ...
+    pub id: String,            // full 64 character ID
+}
+
+impl ContainerRef {
+    // 12 characters, like `docker ps`
+    pub fn short_id(&self) -> &str {
+        &self.id[..12]
+    }
+}
+
+// Innermost container in a cgroup path, None for processes on the host
+pub fn from_cgroup(path: &str) -> Option<ContainerRef> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    for (i, component) in components.iter().enumerate().rev() {
+        let name = component.strip_suffix(".scope").unwrap_or(component);
+        // conmon is Podman's monitor process, it runs next to the container, not in it
+        if name.starts_with("libpod-conmon-") {
+            continue;
+        }
+        let (runtime, id) = match name {
+            _ if name.starts_with("docker-") => ("docker", &name["docker-".len()..]),
+            _ if name.starts_with("libpod-") => ("podman", &name["libpod-".len()..]),
+            _ if name.starts_with("cri-containerd-") => ("containerd", &name["cri-containerd-".len()..]),
+            _ if name.starts_with("crio-") => ("cri-o", &name["crio-".len()..]),
+            // a bare ID, the runtime is whatever directory it sits in
+            _ => match i.checked_sub(1).map(|parent| components[parent]) {
+                Some("docker") => ("docker", name),
+                Some(parent) if parent.starts_with("pod") || parent.starts_with("kubepods") => ("kubernetes", name),
+                _ => ("container", name),
+            },
+        };
+        if id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Some(ContainerRef { runtime, id: id.to_string() });
+        }
+    }
+    None
+}
+
+// Namespace kinds in /proc/<pid>/ns, in the order they are listed
+pub const NAMESPACE_KINDS: [&str; 8] = ["cgroup", "ipc", "mnt", "net", "pid", "time", "user", "uts"];
+
+// (kind, inode) of every namespace the process is in. Two processes share a namespace when the inodes match.
+pub fn namespaces(proc_root: &Path, pid: u32) -> Result<Vec<(&'static str, u64)>, String> {
+    let dir = proc_root.join(pid.to_string()).join("ns");
+    let mut found = Vec::new();
+    for kind in NAMESPACE_KINDS {
+        // "net:[4026531840]"
+        let target = match std::fs::read_link(dir.join(kind)) {
+            Ok(target) => target.to_string_lossy().to_string(),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(format!("cannot read {}: {}", dir.display(), e));
+            }
+            Err(_) => continue, // not in this kernel (time is 5.6+), or the process is gone
+        };
+        let inode = target
+            .strip_prefix(kind)
+            .and_then(|t| t.strip_prefix(":["))
+            .and_then(|t| t.strip_suffix(']'))
+            .and_then(|t| t.parse::<u64>().ok());
+        if let Some(inode) = inode {
+            found.push((kind, inode));
+        }
+    }
+    if found.is_empty() {
+        return Err(format!("cannot read {}", dir.display()));
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{GroupBy, ProcessFilter, ProcessRecord, Snapshot};
+    use std::path::PathBuf;
+
+    const ID: &str = "3f2a9c1b7d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8";
+    const OTHER: &str = "9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f5e4d3c2b1a0f9e8d";
+
+    // A fake /proc under the temp directory, removed again when the test ends
+    struct FakeProc {
+        root: PathBuf,
+    }
+
+    impl FakeProc {
+        fn new(test: &str) -> FakeProc {
+            let root = std::env::temp_dir().join(format!("taskmanager-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            FakeProc { root }
+        }
+
+        fn cgroup(&self, pid: u32, text: &str) -> &Self {
+            std::fs::create_dir_all(self.root.join(pid.to_string())).unwrap();
+            std::fs::write(self.root.join(pid.to_string()).join("cgroup"), text).unwrap();
+            self
+        }
+
+        fn namespace(&self, pid: u32, kind: &str, inode: u64) -> &Self {
+            let dir = self.root.join(pid.to_string()).join("ns");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::os::unix::fs::symlink(format!("{}:[{}]", kind, inode), dir.join(kind)).unwrap();
+            self
+        }
+
+        // the container of a process, the way the snapshot finds it
+        fn container(&self, pid: u32) -> Option<ContainerRef> {
+            crate::cgroup::read_cgroup(&self.root, pid).as_deref().and_then(from_cgroup)
+        }
+
+        fn record(&self, pid: u32, name: &str) -> ProcessRecord {
+            let mut record: ProcessRecord =
+                serde_json::from_value(serde_json::json!({"pid": pid, "name": name, "mem": 1024, "cpu": 1.0, "st": "Sleep"})).unwrap();
+            record.cgroup = crate::cgroup::read_cgroup(&self.root, pid);
+            record
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn found(runtime: &'static str, id: &str) -> Option<ContainerRef> {
+        Some(ContainerRef { runtime, id: id.to_string() })
+    }
+
+    #[test]
+    fn docker_paths() {
+        let proc = FakeProc::new("docker");
+        proc.cgroup(10, &format!("0::/system.slice/docker-{}.scope\n", ID))
+            .cgroup(11, &format!("0::/docker/{}\n", ID))
+            // cgroup v1 host, only the systemd hierarchy names the group
+            .cgroup(12, &format!("12:memory:/docker/{}\n1:name=systemd:/docker/{}\n", ID, OTHER));
+        assert_eq!(proc.container(10), found("docker", ID));
+        assert_eq!(proc.container(11), found("docker", ID));
+        assert_eq!(proc.container(12), found("docker", OTHER));
+        assert_eq!(proc.container(10).unwrap().short_id(), "3f2a9c1b7d4e");
+    }
+
+    #[test]
+    fn podman_skips_conmon() {
+        let proc = FakeProc::new("podman");
+        proc.cgroup(20, &format!("0::/machine.slice/libpod-{}.scope/container\n", ID))
+            .cgroup(21, &format!("0::/machine.slice/libpod-conmon-{}.scope\n", ID))
+            // rootless, under the user's slice
+            .cgroup(22, &format!("0::/user.slice/user-1000.slice/user@1000.service/user.slice/libpod-{}.scope\n", OTHER));
+        assert_eq!(proc.container(20), found("podman", ID));
+        assert_eq!(proc.container(21), None);
+        assert_eq!(proc.container(22), found("podman", OTHER));
+    }
+
+    #[test]
+    fn kubernetes_runtimes() {
+        let proc = FakeProc::new("kubernetes");
+        let pod = "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1a2b3c4d_5e6f.slice";
+        proc.cgroup(30, &format!("0::{}/cri-containerd-{}.scope\n", pod, ID))
+            .cgroup(31, &format!("0::{}/crio-{}.scope\n", pod, OTHER))
+            // cgroupfs driver: bare IDs under the pod directory
+            .cgroup(32, &format!("0::/kubepods/burstable/pod1a2b3c4d-5e6f/{}\n", ID))
+            .cgroup(33, &format!("0::/kubepods/{}\n", OTHER))
+            // the pod cgroup itself is not a container
+            .cgroup(34, &format!("0::{}\n", pod));
+        assert_eq!(proc.container(30), found("containerd", ID));
+        assert_eq!(proc.container(31), found("cri-o", OTHER));
+        assert_eq!(proc.container(32), found("kubernetes", ID));
+        assert_eq!(proc.container(33), found("kubernetes", OTHER));
+        assert_eq!(proc.container(34), None);
+    }
+
+    #[test]
+    fn host_processes() {
+        let proc = FakeProc::new("host");
+        proc.cgroup(1, "0::/init.scope\n")
+            .cgroup(40, "0::/system.slice/nginx.service\n")
+            .cgroup(41, "0::/user.slice/user-1000.slice/session-2.scope\n")
+            // looks like a container but the ID is too short
+            .cgroup(42, "0::/system.slice/docker-3f2a9c1b7d4e.scope\n")
+            .cgroup(43, "0::/\n");
+        for pid in [1, 40, 41, 42, 43] {
+            assert_eq!(proc.container(pid), None, "pid {}", pid);
+        }
+        // gone or unreadable
+        assert_eq!(proc.container(99), None);
+    }
+
+    #[test]
+    fn namespaces_from_links() {
+        let proc = FakeProc::new("namespaces");
+        proc.namespace(1, "net", 4026531840).namespace(1, "pid", 4026531836).namespace(1, "mnt", 4026531841);
+        proc.namespace(50, "net", 4026532200).namespace(50, "pid", 4026531836);
+        assert_eq!(
+            namespaces(&proc.root, 1),
+            Ok(vec![("mnt", 4026531841), ("net", 4026531840), ("pid", 4026531836)])
+        );
+        // missing kinds (an older kernel without time namespaces) are left out
+        assert_eq!(namespaces(&proc.root, 50), Ok(vec![("net", 4026532200), ("pid", 4026531836)]));
+        assert!(namespaces(&proc.root, 99).is_err());
+    }
+
+    // what --container <prefix> and --group-by container make of the same fake /proc
+    fn fixture_snapshot(proc: &FakeProc) -> Snapshot {
+        proc.cgroup(1, "0::/init.scope\n")
+            .cgroup(60, &format!("0::/system.slice/docker-{}.scope\n", ID))
+            .cgroup(61, &format!("0::/system.slice/docker-{}.scope\n", ID))
+            .cgroup(62, &format!("0::/machine.slice/libpod-{}.scope\n", OTHER))
+            .cgroup(63, &format!("0::/machine.slice/libpod-conmon-{}.scope\n", OTHER));
+        Snapshot {
+            processes: vec![
+                proc.record(1, "systemd"),
+                proc.record(60, "nginx"),
+                proc.record(61, "nginx"),
+                proc.record(62, "postgres"),
+                proc.record(63, "conmon"),
+            ],
+            ..Snapshot::default()
+        }
+    }
+
+    fn pids(snapshot: &Snapshot, container: &str) -> Vec<u32> {
+        let filter = ProcessFilter { container: Some(container.to_string()), ..ProcessFilter::default() };
+        let mut pids: Vec<u32> = snapshot.processes.iter().filter(|p| filter.matches(p)).map(|p| p.pid).collect();
+        pids.sort();
+        pids
+    }
+
+    #[test]
+    fn container_prefix_filter() {
+        let proc = FakeProc::new("filter");
+        let snapshot = fixture_snapshot(&proc);
+        assert_eq!(pids(&snapshot, "3f2a"), vec![60, 61]);
+        assert_eq!(pids(&snapshot, &ID.to_uppercase()[..12]), vec![60, 61]); // any case
+        assert_eq!(pids(&snapshot, ID), vec![60, 61]);
+        assert_eq!(pids(&snapshot, "9e8d"), vec![62]);
+        assert_eq!(pids(&snapshot, "host"), vec![1, 63]);
+        assert_eq!(pids(&snapshot, "ffff"), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn group_by_container() {
+        let proc = FakeProc::new("group");
+        let snapshot = fixture_snapshot(&proc);
+        let mut groups: Vec<(String, usize)> = snapshot
+            .aggregate(&ProcessFilter::default(), GroupBy::Container)
+            .into_iter()
+            .map(|group| (group.name, group.processes))
+            .collect();
+        groups.sort();
+        assert_eq!(
+            groups,
+            vec![
+                ("(host)".to_string(), 2),
+                ("docker 3f2a9c1b7d4e".to_string(), 2),
+                ("podman 9e8d7c6b5a4f".to_string(), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use crate::snapshot::{ProcessRecord, Snapshot};
+
+// SNAPSHOT COMPARISON
+//
+// What happened between a frozen snapshot and a later one: processes that started, processes that exited and
+// processes whose CPU or memory changed a lot. A PID that now belongs to a program with another name was
+// reused, so it counts as one exit and one start. Threads are left out, their processes are compared.
+
+const CPU_CHANGE: f32 = 10.0; // percentage points (100 = one core)
+const MEMORY_CHANGE_BYTES: u64 = 10 * 1024 * 1024;
+const MEMORY_CHANGE_RATIO: f64 = 0.2; // and at least this part of the smaller value, 1 MB -> 12 MB is big, 4 GB -> 4.01 GB is not
+
+pub struct Change {
+    pub pid: u32,
+    pub name: String,
+    pub cpu: (f32, f32), // before, after
+    pub memory: (u64, u64),
+}
+
+impl Change {
+    pub fn cpu_delta(&self) -> f32 {
+        self.cpu.1 - self.cpu.0
+    }
+
+    pub fn memory_delta(&self) -> i64 {
+        self.memory.1 as i64 - self.memory.0 as i64
+    }
+}
+
+pub struct SnapshotDiff {
+    pub seconds: f64, // between the two snapshots
+    pub started: Vec<ProcessRecord>, // biggest memory first
+    pub exited: Vec<ProcessRecord>,
+    pub changed: Vec<Change>, // biggest CPU change first
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.started.is_empty() && self.exited.is_empty() && self.changed.is_empty()
+    }
+}
+
+pub fn diff(before: &Snapshot, after: &Snapshot) -> SnapshotDiff {
+    let old: HashMap<u32, &ProcessRecord> = before.processes.iter().filter(|p| !p.is_thread).map(|p| (p.pid, p)).collect();
+    let new: HashMap<u32, &ProcessRecord> = after.processes.iter().filter(|p| !p.is_thread).map(|p| (p.pid, p)).collect();
+    let same = |a: &ProcessRecord, b: &ProcessRecord| a.name == b.name;
+
+    let mut started: Vec<ProcessRecord> = new
+        .values()
+        .filter(|p| old.get(&p.pid).is_none_or(|o| !same(o, p)))
+        .map(|p| (*p).clone())
+        .collect();
+    let mut exited: Vec<ProcessRecord> = old
+        .values()
+        .filter(|p| new.get(&p.pid).is_none_or(|n| !same(n, p)))
+        .map(|p| (*p).clone())
+        .collect();
+    let mut changed: Vec<Change> = new
+        .values()
+        .filter_map(|p| {
+            let o = old.get(&p.pid).filter(|o| same(o, p))?;
+            let change = Change { pid: p.pid, name: p.name.clone(), cpu: (o.cpu, p.cpu), memory: (o.memory, p.memory) };
+            let smaller = o.memory.min(p.memory) as f64;
+            let memory_jump = change.memory_delta().unsigned_abs() >= MEMORY_CHANGE_BYTES
+                && change.memory_delta().unsigned_abs() as f64 >= smaller * MEMORY_CHANGE_RATIO;
+            (change.cpu_delta().abs() >= CPU_CHANGE || memory_jump).then_some(change)
+        })
+        .collect();
+
+    started.sort_by(|a, b| b.memory.cmp(&a.memory).then(a.pid.cmp(&b.pid)));
+    exited.sort_by(|a, b| b.memory.cmp(&a.memory).then(a.pid.cmp(&b.pid)));
+    changed.sort_by(|a, b| {
+        b.cpu_delta()
+            .abs()
+            .partial_cmp(&a.cpu_delta().abs())
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.memory_delta().abs().cmp(&a.memory_delta().abs()))
+            .then(a.pid.cmp(&b.pid))
+    });
+    SnapshotDiff {
+        seconds: after.timestamp_ms.saturating_sub(before.timestamp_ms) as f64 / 1000.0,
+        started,
+        exited,
+        changed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn record(pid: u32, name: &str, cpu: f32, memory: u64) -> ProcessRecord {
+        serde_json::from_value(serde_json::json!({"pid": pid, "name": name, "mem": memory, "cpu": cpu, "st": "Sleep"})).unwrap()
+    }
+
+    fn thread(pid: u32, name: &str, cpu: f32, memory: u64) -> ProcessRecord {
+        ProcessRecord { is_thread: true, ..record(pid, name, cpu, memory) }
+    }
+
+    fn snapshot(timestamp_ms: u64, processes: Vec<ProcessRecord>) -> Snapshot {
+        Snapshot { timestamp_ms, processes, ..Snapshot::default() }
+    }
+
+    fn changed_pids(diff: &SnapshotDiff) -> Vec<u32> {
+        let mut pids: Vec<u32> = diff.changed.iter().map(|change| change.pid).collect();
+        pids.sort();
+        pids
+    }
+
+    #[test]
+    fn started_and_exited() {
+        let before = snapshot(1_000, vec![record(1, "init", 0.0, MIB), record(10, "old", 0.0, MIB), record(11, "gone", 0.0, 5 * MIB)]);
+        let after = snapshot(3_500, vec![record(1, "init", 0.0, MIB), record(20, "new", 0.0, MIB), record(21, "big", 0.0, 9 * MIB)]);
+        let diff = diff(&before, &after);
+        assert_eq!(diff.seconds, 2.5);
+        // biggest memory first
+        assert_eq!(diff.started.iter().map(|p| p.pid).collect::<Vec<u32>>(), vec![21, 20]);
+        assert_eq!(diff.exited.iter().map(|p| p.pid).collect::<Vec<u32>>(), vec![11, 10]);
+        assert!(diff.changed.is_empty());
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn reused_pid_is_an_exit_and_a_start() {
+        let before = snapshot(0, vec![record(300, "bash", 1.0, MIB)]);
+        let after = snapshot(0, vec![record(300, "python3", 90.0, 500 * MIB)]);
+        let diff = diff(&before, &after);
+        assert_eq!(diff.exited.len(), 1);
+        assert_eq!(diff.exited[0].name, "bash");
+        assert_eq!(diff.started.len(), 1);
+        assert_eq!(diff.started[0].name, "python3");
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn cpu_threshold_edge() {
+        let before = snapshot(0, vec![record(1, "a", 5.0, MIB), record(2, "b", 5.0, MIB), record(3, "c", 30.0, MIB)]);
+        let after = snapshot(0, vec![record(1, "a", 15.0, MIB), record(2, "b", 14.5, MIB), record(3, "c", 20.0, MIB)]);
+        let diff = diff(&before, &after);
+        // exactly 10 points counts, up or down
+        assert_eq!(changed_pids(&diff), vec![1, 3]);
+        let change = diff.changed.iter().find(|change| change.pid == 3).unwrap();
+        assert_eq!(change.cpu, (30.0, 20.0));
+        assert_eq!(change.cpu_delta(), -10.0);
+    }
+
+    #[test]
+    fn memory_threshold_edges() {
+        let before = snapshot(
+            0,
+            vec![
+                record(1, "exact", 0.0, 50 * MIB),
+                record(2, "under_bytes", 0.0, 50 * MIB),
+                record(3, "under_ratio", 0.0, 100 * MIB),
+                record(4, "small", 0.0, MIB),
+                record(5, "huge", 0.0, 4096 * MIB),
+                record(6, "shrinks", 0.0, 60 * MIB),
+            ],
+        );
+        let after = snapshot(
+            0,
+            vec![
+                record(1, "exact", 0.0, 60 * MIB),           // +10 MiB, exactly 20% of 50 MiB
+                record(2, "under_bytes", 0.0, 60 * MIB - 1), // 20% but not quite 10 MiB
+                record(3, "under_ratio", 0.0, 110 * MIB),    // 10 MiB but only 10%
+                record(4, "small", 0.0, 12 * MIB),
+                record(5, "huge", 0.0, 4105 * MIB),
+                record(6, "shrinks", 0.0, 50 * MIB), // 20% of the smaller value
+            ],
+        );
+        let diff = diff(&before, &after);
+        assert_eq!(changed_pids(&diff), vec![1, 4, 6]);
+        let shrinks = diff.changed.iter().find(|change| change.pid == 6).unwrap();
+        assert_eq!(shrinks.memory_delta(), -10 * MIB as i64);
+    }
+
+    #[test]
+    fn threads_are_skipped() {
+        let before = snapshot(0, vec![record(40, "java", 10.0, 100 * MIB), thread(41, "java", 0.0, 100 * MIB), thread(42, "java", 0.0, 100 * MIB)]);
+        // a thread got busy, one exited and one started; only the process counts
+        let after = snapshot(0, vec![record(40, "java", 10.0, 100 * MIB), thread(41, "java", 95.0, 100 * MIB), thread(43, "java", 0.0, 100 * MIB)]);
+        let diff = diff(&before, &after);
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn biggest_cpu_change_first() {
+        let before = snapshot(0, vec![record(1, "a", 0.0, MIB), record(2, "b", 0.0, MIB), record(3, "c", 0.0, 10 * MIB)]);
+        let after = snapshot(0, vec![record(1, "a", 20.0, MIB), record(2, "b", 80.0, MIB), record(3, "c", 0.0, 100 * MIB)]);
+        let order: Vec<u32> = diff(&before, &after).changed.iter().map(|change| change.pid).collect();
+        assert_eq!(order, vec![2, 1, 3]);
+    }
+}
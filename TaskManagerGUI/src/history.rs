@@ -0,0 +1,359 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::config::HistoryConfig;
+use crate::snapshot::Snapshot;
+
+// PROCESS HISTORY (rotating NDJSON: one snapshot per line)
+//
+// history.ndjson is the file being written, history.ndjson.1 the previous one, up to .<keep_files>
+
+pub struct Recorder {
+    path: PathBuf,
+    interval: Duration,
+    max_bytes: u64,
+    keep_files: usize,
+    file: Option<File>,
+    written: u64,
+    last_record: Option<Instant>,
+}
+
+impl Recorder {
+    pub fn new(config: &HistoryConfig) -> Self {
+        Recorder {
+            path: config.path.clone(),
+            interval: Duration::from_millis(config.interval_ms),
+            max_bytes: config.max_file_mb * 1024 * 1024,
+            keep_files: config.keep_files,
+            file: None,
+            written: 0,
+            last_record: None,
+        }
+    }
+
+    // Only the config decides if we record, callers can always hold a Recorder
+    pub fn from_config(config: &HistoryConfig) -> Option<Self> {
+        if config.enabled {
+            Some(Recorder::new(config))
+        } else {
+            None
+        }
+    }
+
+    // Write the snapshot if the recording interval has passed since the last one
+    pub fn maybe_record(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        if let Some(last) = self.last_record {
+            if last.elapsed() < self.interval {
+                return Ok(());
+            }
+        }
+        self.last_record = Some(Instant::now());
+        self.record(snapshot)
+    }
+
+    pub fn record(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        let mut line = serde_json::to_string(snapshot).map_err(|e| format!("cannot serialize snapshot: {}", e))?;
+        line.push('\n');
+
+        if self.file.is_none() || self.written + line.len() as u64 > self.max_bytes {
+            self.open_next()?;
+        }
+        let file = self.file.as_mut().expect("history file was just opened");
+        file.write_all(line.as_bytes())
+            .map_err(|e| format!("cannot write {}: {}", self.path.display(), e))?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    // Rotate when the current file is full, then (re)open it for appending
+    fn open_next(&mut self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        }
+        let current_size = std::fs::metadata(&self.path).map_or(0, |m| m.len());
+        if current_size > 0 && (self.file.is_some() || current_size >= self.max_bytes) {
+            self.file = None;
+            // history.ndjson.N-1 -> .N, ..., history.ndjson -> .1 (the oldest one falls off)
+            for n in (1..self.keep_files).rev() {
+                let _ = std::fs::rename(rotated_path(&self.path, n), rotated_path(&self.path, n + 1));
+            }
+            if self.keep_files > 0 {
+                let _ = std::fs::rename(&self.path, rotated_path(&self.path, 1));
+            } else {
+                let _ = std::fs::remove_file(&self.path);
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("cannot open {}: {}", self.path.display(), e))?;
+        self.written = file.metadata().map_or(0, |m| m.len());
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+// Read a history file together with its rotated parts, oldest snapshot first
+pub fn load_history(path: &Path) -> Result<Vec<Snapshot>, String> {
+    let mut files: Vec<PathBuf> = (1..)
+        .map(|n| rotated_path(path, n))
+        .take_while(|p| p.exists())
+        .collect();
+    files.reverse();
+    files.push(path.to_path_buf());
+
+    let mut snapshots = Vec::new();
+    for file in files.iter().filter(|p| p.exists()) {
+        let reader = BufReader::new(File::open(file).map_err(|e| format!("cannot open {}: {}", file.display(), e))?);
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| format!("cannot read {}: {}", file.display(), e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Snapshot>(&line) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                // the last line can be cut off if the recorder was killed mid-write
+                Err(e) => eprintln!("Skipping {} line {}: {}", file.display(), number + 1, e),
+            }
+        }
+    }
+    if snapshots.is_empty() {
+        return Err(format!("no snapshots found in {}", path.display()));
+    }
+    snapshots.sort_by_key(|s| s.timestamp_ms);
+    // rates are not stored, they come from the totals of neighbouring snapshots
+    for i in 1..snapshots.len() {
+        let (before, after) = snapshots.split_at_mut(i);
+        after[0].compute_io_rates(&before[i - 1]);
+    }
+    Ok(snapshots)
+}
+
+// A position in recorded history that can be stepped, seeked or played back in real time
+pub struct Replay {
+    pub snapshots: Vec<Snapshot>,
+    pub position: usize,
+    pub playing: bool,
+    last_step: Instant,
+}
+
+impl Replay {
+    pub fn new(snapshots: Vec<Snapshot>) -> Self {
+        Replay {
+            snapshots,
+            position: 0,
+            playing: false,
+            last_step: Instant::now(),
+        }
+    }
+
+    pub fn current(&self) -> &Snapshot {
+        &self.snapshots[self.position]
+    }
+
+    pub fn step(&mut self, delta: isize) {
+        let last = self.snapshots.len() as isize - 1;
+        self.position = (self.position as isize + delta).clamp(0, last) as usize;
+        self.last_step = Instant::now();
+    }
+
+    // Jump to the last snapshot taken at or before `seconds` after the start of the recording
+    pub fn seek_seconds(&mut self, seconds: f64) {
+        let target = self.snapshots[0].timestamp_ms + (seconds.max(0.0) * 1000.0) as u64;
+        self.position = self
+            .snapshots
+            .iter()
+            .rposition(|s| s.timestamp_ms <= target)
+            .unwrap_or(0);
+        self.last_step = Instant::now();
+    }
+
+    // Seconds between the start of the recording and the current snapshot
+    pub fn offset_seconds(&self) -> f64 {
+        (self.current().timestamp_ms - self.snapshots[0].timestamp_ms) as f64 / 1000.0
+    }
+
+    // While playing, move forward once as much real time has passed as there was between the two snapshots.
+    // Returns true when the position changed.
+    pub fn advance(&mut self) -> bool {
+        if !self.playing {
+            return false;
+        }
+        if self.position + 1 >= self.snapshots.len() {
+            self.playing = false; // reached the end of the recording
+            return false;
+        }
+        let gap = self.snapshots[self.position + 1].timestamp_ms - self.current().timestamp_ms;
+        if self.last_step.elapsed() >= Duration::from_millis(gap) {
+            self.step(1);
+            return true;
+        }
+        false
+    }
+
+    // Time until `advance` will move on, used to schedule repaints
+    pub fn until_next(&self) -> Option<Duration> {
+        if !self.playing || self.position + 1 >= self.snapshots.len() {
+            return None;
+        }
+        let gap = Duration::from_millis(self.snapshots[self.position + 1].timestamp_ms - self.current().timestamp_ms);
+        Some(gap.saturating_sub(self.last_step.elapsed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A history directory under the temp directory, removed again when the test ends
+    struct HistoryDir {
+        root: PathBuf,
+    }
+
+    impl HistoryDir {
+        fn new(test: &str) -> HistoryDir {
+            let root = std::env::temp_dir().join(format!("taskmanager-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            HistoryDir { root }
+        }
+
+        fn path(&self) -> PathBuf {
+            self.root.join("history.ndjson")
+        }
+
+        fn recorder(&self, keep_files: usize) -> Recorder {
+            Recorder::new(&HistoryConfig {
+                enabled: true,
+                path: self.path(),
+                interval_ms: 0,
+                max_file_mb: 1,
+                keep_files,
+            })
+        }
+    }
+
+    impl Drop for HistoryDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    // one process that has read `read_bytes` so far
+    fn snapshot(timestamp_ms: u64, read_bytes: u64) -> Snapshot {
+        let process = serde_json::from_value(serde_json::json!(
+            {"pid": 42, "name": "app", "mem": 1024, "cpu": 1.0, "st": "Sleep", "rd": read_bytes}
+        )).unwrap();
+        Snapshot { timestamp_ms, cpus: 2, processes: vec![process], ..Snapshot::default() }
+    }
+
+    fn timestamps(snapshots: &[Snapshot]) -> Vec<u64> {
+        snapshots.iter().map(|s| s.timestamp_ms).collect()
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_only_keep_files() {
+        let dir = HistoryDir::new("history-rotate");
+        let mut recorder = dir.recorder(2);
+        // room for two lines per file (all lines are the same length)
+        let line = serde_json::to_string(&snapshot(1000, 0)).unwrap().len() as u64 + 1;
+        recorder.max_bytes = 2 * line;
+        for t in 1..=7 {
+            recorder.record(&snapshot(t * 1000, 0)).unwrap();
+        }
+
+        let lines = |path: PathBuf| std::fs::read_to_string(path).unwrap().lines().count();
+        assert_eq!(lines(dir.path()), 1);
+        assert_eq!(lines(rotated_path(&dir.path(), 1)), 2);
+        assert_eq!(lines(rotated_path(&dir.path(), 2)), 2);
+        // the first two snapshots fell off with the third rotation
+        assert!(!rotated_path(&dir.path(), 3).exists());
+        assert_eq!(timestamps(&load_history(&dir.path()).unwrap()), [3000, 4000, 5000, 6000, 7000]);
+    }
+
+    #[test]
+    fn no_kept_files_starts_over() {
+        let dir = HistoryDir::new("history-keep-none");
+        let mut recorder = dir.recorder(0);
+        recorder.max_bytes = serde_json::to_string(&snapshot(1000, 0)).unwrap().len() as u64 + 1;
+        for t in 1..=3 {
+            recorder.record(&snapshot(t * 1000, 0)).unwrap();
+        }
+        assert!(!rotated_path(&dir.path(), 1).exists());
+        assert_eq!(timestamps(&load_history(&dir.path()).unwrap()), [3000]);
+    }
+
+    #[test]
+    fn reads_back_what_was_written_with_rates() {
+        let dir = HistoryDir::new("history-read");
+        let mut recorder = dir.recorder(2);
+        recorder.record(&snapshot(1000, 0)).unwrap();
+        recorder.record(&snapshot(3000, 4 * 1024 * 1024)).unwrap();
+
+        let snapshots = load_history(&dir.path()).unwrap();
+        assert_eq!(timestamps(&snapshots), [1000, 3000]);
+        assert_eq!(snapshots[1].processes[0].name, "app");
+        assert_eq!(snapshots[1].processes[0].read_bytes, 4 * 1024 * 1024);
+        // rates are not stored but computed again from the totals
+        assert_eq!(snapshots[0].processes[0].read_rate, 0.0);
+        assert_eq!(snapshots[1].processes[0].read_rate, 2.0 * 1024.0 * 1024.0);
+    }
+
+    #[test]
+    fn corrupt_lines_are_skipped() {
+        let dir = HistoryDir::new("history-corrupt");
+        let mut recorder = dir.recorder(2);
+        recorder.record(&snapshot(1000, 0)).unwrap();
+        drop(recorder);
+        // garbage in the middle, a blank line and a line cut off by a killed recorder at the end
+        let mut file = OpenOptions::new().append(true).open(dir.path()).unwrap();
+        writeln!(file, "not json").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "{}", serde_json::to_string(&snapshot(2000, 0)).unwrap()).unwrap();
+        write!(file, "{{\"t\":3000,\"total_mem").unwrap();
+
+        assert_eq!(timestamps(&load_history(&dir.path()).unwrap()), [1000, 2000]);
+    }
+
+    #[test]
+    fn nothing_to_load_is_an_error() {
+        let dir = HistoryDir::new("history-empty");
+        assert!(load_history(&dir.path()).unwrap_err().contains("no snapshots found"));
+        std::fs::create_dir_all(&dir.root).unwrap();
+        std::fs::write(dir.path(), "{\"t\":1000\n").unwrap();
+        assert!(load_history(&dir.path()).unwrap_err().contains("no snapshots found"));
+    }
+
+    #[test]
+    fn replay_seek_and_step_stay_in_bounds() {
+        let mut replay = Replay::new(vec![snapshot(10_000, 0), snapshot(12_000, 0), snapshot(15_000, 0)]);
+        replay.step(-1);
+        assert_eq!(replay.position, 0);
+        replay.step(5);
+        assert_eq!(replay.position, 2);
+        assert_eq!(replay.offset_seconds(), 5.0);
+
+        // the last snapshot taken at or before the time
+        replay.seek_seconds(2.9);
+        assert_eq!(replay.position, 1);
+        replay.seek_seconds(2.0);
+        assert_eq!(replay.position, 1);
+        replay.seek_seconds(-3.0);
+        assert_eq!(replay.position, 0);
+        replay.seek_seconds(60.0);
+        assert_eq!(replay.position, 2);
+
+        // playing stops at the end instead of running past it
+        replay.playing = true;
+        assert!(!replay.advance());
+        assert!(!replay.playing);
+        assert_eq!(replay.until_next(), None);
+    }
+}
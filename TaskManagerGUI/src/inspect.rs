@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+// PROCESS INSPECTOR (lsof-like data for one pid)
+//
+// Open fds from /proc/<pid>/fd, the fd limit from /proc/<pid>/limits, memory from
+// /proc/<pid>/smaps (per mapping) and /proc/<pid>/smaps_rollup (totals), threads from /proc/<pid>/task.
+// Like the rest of /proc/<pid>, other users' processes need root.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FdKind {
+    File,
+    Socket,
+    Pipe,
+    AnonInode, // eventfd, epoll, timerfd, ...
+    Other,
+}
+
+impl FdKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            FdKind::File => "file",
+            FdKind::Socket => "socket",
+            FdKind::Pipe => "pipe",
+            FdKind::AnonInode => "anon",
+            FdKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OpenFile {
+    pub fd: u32,
+    pub kind: FdKind,
+    pub target: String, // where the fd link points, e.g. "/var/log/syslog" or "socket:[12345]"
+}
+
+impl OpenFile {
+    // Inode of a "socket:[12345]" target, to look it up in /proc/net
+    pub fn socket_inode(&self) -> Option<u64> {
+        self.target.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()
+    }
+}
+
+// Open fds of a process, lowest fd first
+pub fn open_files(proc_root: &Path, pid: u32) -> Result<Vec<OpenFile>, String> {
+    let dir = proc_root.join(pid.to_string()).join("fd");
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let fd: u32 = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+            Some(fd) => fd,
+            None => continue,
+        };
+        // the fd can be closed between read_dir and read_link
+        let target = match std::fs::read_link(entry.path()) {
+            Ok(target) => target.to_string_lossy().to_string(),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(format!("cannot read {}: {}", dir.display(), e));
+            }
+            Err(_) => continue,
+        };
+        let kind = if target.starts_with("socket:[") {
+            FdKind::Socket
+        } else if target.starts_with("pipe:[") {
+            FdKind::Pipe
+        } else if target.starts_with("anon_inode:") {
+            FdKind::AnonInode
+        } else if target.starts_with('/') {
+            FdKind::File
+        } else {
+            FdKind::Other
+        };
+        files.push(OpenFile { fd, kind, target });
+    }
+    files.sort_by_key(|f| f.fd);
+    Ok(files)
+}
+
+// (soft, hard) "Max open files" limit, None for unlimited
+pub fn fd_limit(proc_root: &Path, pid: u32) -> Result<(Option<u64>, Option<u64>), String> {
+    let path = proc_root.join(pid.to_string()).join("limits");
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    // Max open files            1024                 524288               files
+    let line = text
+        .lines()
+        .find(|line| line.starts_with("Max open files"))
+        .ok_or(format!("no 'Max open files' line in {}", path.display()))?;
+    let mut values = line["Max open files".len()..].split_whitespace().map(|v| v.parse::<u64>().ok());
+    let soft = values.next().flatten();
+    let hard = values.next().flatten();
+    Ok((soft, hard))
+}
+
+// Sizes from smaps, all in bytes
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MemoryUsage {
+    pub size: u64, // virtual
+    pub rss: u64,
+    pub pss: u64, // RSS with shared pages split between the processes sharing them
+    pub shared_clean: u64,
+    pub shared_dirty: u64,
+    pub private_clean: u64,
+    pub private_dirty: u64,
+    pub swap: u64,
+}
+
+impl MemoryUsage {
+    pub fn shared(&self) -> u64 {
+        self.shared_clean + self.shared_dirty
+    }
+
+    pub fn private(&self) -> u64 {
+        self.private_clean + self.private_dirty
+    }
+
+    fn merge(&mut self, other: &MemoryUsage) {
+        self.size += other.size;
+        self.rss += other.rss;
+        self.pss += other.pss;
+        self.shared_clean += other.shared_clean;
+        self.shared_dirty += other.shared_dirty;
+        self.private_clean += other.private_clean;
+        self.private_dirty += other.private_dirty;
+        self.swap += other.swap;
+    }
+
+    // One "Key:   123 kB" line, unknown keys are ignored
+    fn add(&mut self, key: &str, kb: u64) {
+        let bytes = kb * 1024;
+        match key {
+            "Size" => self.size += bytes,
+            "Rss" => self.rss += bytes,
+            "Pss" => self.pss += bytes,
+            "Shared_Clean" => self.shared_clean += bytes,
+            "Shared_Dirty" => self.shared_dirty += bytes,
+            "Private_Clean" => self.private_clean += bytes,
+            "Private_Dirty" => self.private_dirty += bytes,
+            "Swap" => self.swap += bytes,
+            _ => {}
+        }
+    }
+}
+
+// All mappings of the same file (or [heap], [stack], [anon], ...) added together
+#[derive(Clone, Debug)]
+pub struct MappingGroup {
+    pub name: String,
+    pub mappings: usize,
+    pub usage: MemoryUsage,
+}
+
+pub struct MemoryMaps {
+    pub total: MemoryUsage, // from smaps_rollup, or the sum of the groups on kernels without it
+    pub groups: Vec<MappingGroup>, // biggest RSS first
+}
+
+pub fn memory_maps(proc_root: &Path, pid: u32) -> Result<MemoryMaps, String> {
+    let dir = proc_root.join(pid.to_string());
+    let path = dir.join("smaps");
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+
+    let mut groups: HashMap<String, MappingGroup> = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let first = match fields.next() {
+            Some(first) => first,
+            None => continue,
+        };
+        match first.strip_suffix(':') {
+            // "Rss:   176 kB" belongs to the mapping above it
+            Some(key) => {
+                if let (Some(name), Some(kb)) = (&current, fields.next().and_then(|v| v.parse::<u64>().ok())) {
+                    if let Some(group) = groups.get_mut(name) {
+                        group.usage.add(key, kb);
+                    }
+                }
+            }
+            // "55d0c3a00000-55d0c3a2c000 r--p 00000000 08:01 1234   /usr/bin/bash" starts a new mapping
+            None => {
+                let name = mapping_name(line);
+                let group = groups.entry(name.clone()).or_insert_with(|| MappingGroup {
+                    name: name.clone(),
+                    mappings: 0,
+                    usage: MemoryUsage::default(),
+                });
+                group.mappings += 1;
+                current = Some(name);
+            }
+        }
+    }
+
+    let mut groups: Vec<MappingGroup> = groups.into_values().collect();
+    groups.sort_by(|a, b| b.usage.rss.cmp(&a.usage.rss).then(a.name.cmp(&b.name)));
+
+    let total = match std::fs::read_to_string(dir.join("smaps_rollup")) {
+        Ok(rollup) => {
+            let mut total = MemoryUsage::default();
+            for line in rollup.lines() {
+                let mut fields = line.split_whitespace();
+                if let (Some(key), Some(kb)) = (
+                    fields.next().and_then(|k| k.strip_suffix(':')),
+                    fields.next().and_then(|v| v.parse::<u64>().ok()),
+                ) {
+                    total.add(key, kb);
+                }
+            }
+            // the rollup has no Size line
+            total.size = groups.iter().map(|g| g.usage.size).sum();
+            total
+        }
+        // smaps_rollup is Linux 4.14+
+        Err(_) => {
+            let mut total = MemoryUsage::default();
+            for group in &groups {
+                total.merge(&group.usage);
+            }
+            total
+        }
+    };
+    Ok(MemoryMaps { total, groups })
+}
+
+// The path after the 5 fixed columns of a mapping line (paths can contain spaces), or [anon]
+fn mapping_name(line: &str) -> String {
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start();
+        rest = rest.find(char::is_whitespace).map_or("", |end| &rest[end..]);
+    }
+    match rest.trim() {
+        "" => "[anon]".to_string(),
+        name => name.to_string(),
+    }
+}
+
+// One thread from /proc/<pid>/task/<tid>/stat
+#[derive(Clone, Debug)]
+pub struct ThreadInfo {
+    pub tid: u32,
+    pub name: String,  // comm, set with prctl / pthread_setname_np
+    pub state: String, // "Run", "Sleep", ...
+    pub cpu_ticks: u64, // user + system time in clock ticks
+    pub last_cpu: u32, // CPU the thread last ran on
+    pub cpu: f32, // percent of one core since the previous sample, see `compute_thread_cpu`
+}
+
+// Threads of a process, lowest tid first
+pub fn threads(proc_root: &Path, pid: u32) -> Result<Vec<ThreadInfo>, String> {
+    let dir = proc_root.join(pid.to_string()).join("task");
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+    let mut threads = Vec::new();
+    for entry in entries.flatten() {
+        // threads can exit while we look
+        if let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) {
+            if let Some(thread) = parse_thread_stat(&stat) {
+                threads.push(thread);
+            }
+        }
+    }
+    threads.sort_by_key(|t| t.tid);
+    Ok(threads)
+}
+
+// "1234 (tokio-runtime-w) S 1 ... utime stime ... processor ..."
+fn parse_thread_stat(stat: &str) -> Option<ThreadInfo> {
+    // the name can contain spaces and parentheses, so split around the last ')'
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let tid = stat[..open].trim().parse().ok()?;
+    let name = stat[open + 1..close].to_string();
+    let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+    // fields[0] is field 3 of proc(5)
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let state = match *fields.first()? {
+        "R" => "Run",
+        "S" => "Sleep",
+        "D" => "Disk sleep",
+        "Z" => "Zombie",
+        "T" => "Stop",
+        "t" => "Tracing",
+        "X" => "Dead",
+        "I" => "Idle",
+        other => other,
+    };
+    Some(ThreadInfo {
+        tid,
+        name,
+        state: state.to_string(),
+        cpu_ticks: utime + stime,
+        last_cpu: fields.get(36).and_then(|v| v.parse().ok()).unwrap_or(0),
+        cpu: 0.0,
+    })
+}
+
+// CPU usage of every thread from the ticks it used since an earlier sample, 100% = one core
+pub fn compute_thread_cpu(threads: &mut [ThreadInfo], previous: &[ThreadInfo], seconds: f64) {
+    let ticks_per_second = match nix::unistd::sysconf(nix::unistd::SysconfVar::CLK_TCK) {
+        Ok(Some(ticks)) if ticks > 0 => ticks as f64,
+        _ => 100.0, // USER_HZ on every common Linux
+    };
+    if seconds <= 0.0 {
+        return;
+    }
+    for thread in threads.iter_mut() {
+        if let Some(before) = previous.iter().find(|p| p.tid == thread.tid) {
+            let used = thread.cpu_ticks.saturating_sub(before.cpu_ticks) as f64 / ticks_per_second;
+            thread.cpu = (used / seconds * 100.0) as f32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const KIB: u64 = 1024;
+
+    const SMAPS: &str = "55d0c3a00000-55d0c3a2c000 r--p 00000000 08:01 1234                       /usr/bin/bash
+Size:                176 kB
+Rss:                 176 kB
+Pss:                  88 kB
+Shared_Clean:        176 kB
+THPeligible:    0
+55d0c3a2c000-55d0c3b00000 r-xp 0002c000 08:01 1234                       /usr/bin/bash
+Size:                848 kB
+Rss:                 800 kB
+Pss:                 800 kB
+Private_Clean:       800 kB
+55d0c4000000-55d0c4100000 rw-p 00000000 00:00 0                          [heap]
+Rss:                1024 kB
+Private_Dirty:      1024 kB
+7f0000000000-7f0000100000 rw-p 00000000 00:00 0 
+Rss:                  64 kB
+Private_Dirty:        64 kB
+7f0000100000-7f0000200000 rw-p 00000000 00:00 0
+Rss:                  32 kB
+Swap:                 16 kB
+7f1000000000-7f1000010000 r--p 00000000 08:01 999                        /opt/my app/lib (x).so
+Rss:                   8 kB
+7ffd00000000-7ffd00021000 rw-p 00000000 00:00 0                          [stack]
+Rss:                 132 kB
+Private_Dirty:       132 kB
+VmFlags: rd wr mr mw me gd ac
+";
+
+    // A fake /proc/<pid> under the temp directory, removed again when the test ends
+    struct FakeProc {
+        root: PathBuf,
+        pid: u32,
+    }
+
+    impl FakeProc {
+        fn new(test: &str, pid: u32) -> FakeProc {
+            let root = std::env::temp_dir().join(format!("taskmanager-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(root.join(pid.to_string())).unwrap();
+            FakeProc { root, pid }
+        }
+
+        fn file(&self, name: &str, text: &str) -> &Self {
+            std::fs::write(self.root.join(self.pid.to_string()).join(name), text).unwrap();
+            self
+        }
+
+        fn task(&self, tid: u32, stat: &str) -> &Self {
+            let dir = self.root.join(self.pid.to_string()).join("task").join(tid.to_string());
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("stat"), stat).unwrap();
+            self
+        }
+
+        fn fd(&self, fd: u32, target: &str) -> &Self {
+            let dir = self.root.join(self.pid.to_string()).join("fd");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::os::unix::fs::symlink(target, dir.join(fd.to_string())).unwrap();
+            self
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn fd_kinds_from_the_link_targets() {
+        let proc = FakeProc::new("inspect-fds", 321);
+        proc.fd(0, "/dev/pts/0")
+            .fd(10, "anon_inode:[eventfd]")
+            .fd(2, "socket:[200]")
+            .fd(1, "pipe:[100]")
+            .fd(3, "/home/me/my notes.txt (deleted)")
+            .fd(4, "net:[4026531840]");
+
+        let files = open_files(&proc.root, 321).unwrap();
+        // numeric order, 10 after 4
+        let kinds: Vec<(u32, FdKind)> = files.iter().map(|f| (f.fd, f.kind)).collect();
+        assert_eq!(kinds, [
+            (0, FdKind::File),
+            (1, FdKind::Pipe),
+            (2, FdKind::Socket),
+            (3, FdKind::File),
+            (4, FdKind::Other),
+            (10, FdKind::AnonInode),
+        ]);
+        assert_eq!(files[3].target, "/home/me/my notes.txt (deleted)");
+        assert_eq!(files[2].socket_inode(), Some(200));
+        assert_eq!(files[1].socket_inode(), None);
+
+        assert!(open_files(&proc.root, 999).unwrap_err().contains("cannot read"));
+    }
+
+    #[test]
+    fn fd_limit_soft_and_hard() {
+        let proc = FakeProc::new("inspect-limits", 321);
+        proc.file("limits", "Limit                     Soft Limit           Hard Limit           Units     
+Max cpu time              unlimited            unlimited            seconds   
+Max open files            1024                 524288               files     
+Max locked memory         8388608              8388608              bytes     
+");
+        assert_eq!(fd_limit(&proc.root, 321).unwrap(), (Some(1024), Some(524288)));
+        proc.file("limits", "Max open files            unlimited            unlimited            files\n");
+        assert_eq!(fd_limit(&proc.root, 321).unwrap(), (None, None));
+        proc.file("limits", "Max cpu time              unlimited            unlimited            seconds\n");
+        assert!(fd_limit(&proc.root, 321).unwrap_err().contains("no 'Max open files' line"));
+    }
+
+    #[test]
+    fn mappings_are_summed_by_path() {
+        let proc = FakeProc::new("inspect-smaps", 321);
+        proc.file("smaps", SMAPS);
+        let maps = memory_maps(&proc.root, 321).unwrap();
+
+        let groups: Vec<(&str, usize, u64)> = maps.groups.iter().map(|g| (g.name.as_str(), g.mappings, g.usage.rss)).collect();
+        assert_eq!(groups, [
+            ("[heap]", 1, 1024 * KIB),
+            ("/usr/bin/bash", 2, 976 * KIB),
+            ("[stack]", 1, 132 * KIB),
+            // mappings without a path, with or without the trailing space
+            ("[anon]", 2, 96 * KIB),
+            ("/opt/my app/lib (x).so", 1, 8 * KIB),
+        ]);
+        let bash = &maps.groups[1].usage;
+        assert_eq!((bash.size, bash.pss, bash.shared(), bash.private()), (1024 * KIB, 888 * KIB, 176 * KIB, 800 * KIB));
+        assert_eq!(maps.groups[3].usage.swap, 16 * KIB);
+
+        // no smaps_rollup (before Linux 4.14): the total is the sum of the groups
+        assert_eq!((maps.total.rss, maps.total.size, maps.total.private_dirty), (2236 * KIB, 1024 * KIB, 1220 * KIB));
+    }
+
+    #[test]
+    fn rollup_gives_the_total() {
+        let proc = FakeProc::new("inspect-rollup", 321);
+        proc.file("smaps", SMAPS).file("smaps_rollup", "55d0c3a00000-7ffd00021000 ---p 00000000 00:00 0                          [rollup]
+Rss:                2300 kB
+Pss:                2000 kB
+Swap:                 16 kB
+");
+        let maps = memory_maps(&proc.root, 321).unwrap();
+        assert_eq!((maps.total.rss, maps.total.pss, maps.total.swap), (2300 * KIB, 2000 * KIB, 16 * KIB));
+        // the rollup has no Size, it comes from the mappings
+        assert_eq!(maps.total.size, 1024 * KIB);
+    }
+
+    // A /proc/<pid>/task/<tid>/stat line with everything but the fields we read set to 0
+    fn task_stat(tid: u32, comm: &str, state: &str, utime: u64, stime: u64, processor: u32) -> String {
+        // fields 4 to 52 of proc(5): utime is field 14, stime 15 and processor 39
+        let mut fields = vec!["0".to_string(); 49];
+        fields[14 - 4] = utime.to_string();
+        fields[15 - 4] = stime.to_string();
+        fields[39 - 4] = processor.to_string();
+        format!("{} ({}) {} {}\n", tid, comm, state, fields.join(" "))
+    }
+
+    #[test]
+    fn thread_names_with_spaces_and_parentheses() {
+        let thread = parse_thread_stat(&task_stat(4321, "a) b (c", "S", 250, 75, 3)).unwrap();
+        assert_eq!((thread.tid, thread.name.as_str(), thread.state.as_str()), (4321, "a) b (c", "Sleep"));
+        assert_eq!((thread.cpu_ticks, thread.last_cpu), (325, 3));
+
+        let thread = parse_thread_stat(&task_stat(7, "(sd-pam)", "I", 0, 1, 0)).unwrap();
+        assert_eq!((thread.name.as_str(), thread.state.as_str()), ("(sd-pam)", "Idle"));
+        // cut off before utime / stime
+        assert!(parse_thread_stat("4321 (worker) S 1 4321 4321 0 -1").is_none());
+        assert!(parse_thread_stat("").is_none());
+    }
+
+    #[test]
+    fn threads_of_a_process() {
+        let proc = FakeProc::new("inspect-threads", 321);
+        proc.task(330, &task_stat(330, "tokio-runtime-w", "R", 10, 5, 1))
+            .task(321, &task_stat(321, "server", "S", 100, 20, 0));
+        // a thread that exited between listing the directory and reading its stat
+        std::fs::create_dir_all(proc.root.join("321").join("task").join("325")).unwrap();
+
+        let threads = threads(&proc.root, 321).unwrap();
+        let found: Vec<(u32, &str, &str)> = threads.iter().map(|t| (t.tid, t.name.as_str(), t.state.as_str())).collect();
+        assert_eq!(found, [(321, "server", "Sleep"), (330, "tokio-runtime-w", "Run")]);
+    }
+
+    #[test]
+    fn thread_cpu_from_two_samples() {
+        let hz = match nix::unistd::sysconf(nix::unistd::SysconfVar::CLK_TCK) {
+            Ok(Some(ticks)) if ticks > 0 => ticks as u64,
+            _ => 100,
+        };
+        let sample = |tid: u32, ticks: u64| parse_thread_stat(&task_stat(tid, "worker", "R", ticks, 0, 0)).unwrap();
+        let before = [sample(1, 1000), sample(2, 1000), sample(3, 1000)];
+        // 2 seconds later: thread 1 used one full core, thread 2 a quarter, thread 3 is gone and 4 is new
+        let mut after = [sample(1, 1000 + 2 * hz), sample(2, 1000 + hz / 2), sample(4, 5000)];
+        compute_thread_cpu(&mut after, &before, 2.0);
+        let cpu: Vec<f32> = after.iter().map(|t| t.cpu).collect();
+        assert_eq!(cpu, [100.0, 25.0, 0.0]);
+
+        // no time passed, nothing to divide by
+        let mut again = [sample(1, 1000 + 4 * hz)];
+        compute_thread_cpu(&mut again, &before, 0.0);
+        assert_eq!(again[0].cpu, 0.0);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::config::LeakConfig;
+use crate::snapshot::Snapshot;
+
+// PER-PROCESS RESOURCE HISTORY AND LEAK DETECTION
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    pub timestamp_ms: u64,
+    pub memory: u64, // RSS in bytes
+    pub cpu: f32,
+    pub threads: u32,
+}
+
+// Samples of one PID, oldest first
+pub struct ProcessSeries {
+    pub name: String,
+    pub samples: VecDeque<Sample>,
+}
+
+// A process whose memory only went up over the whole window, faster than the configured slope
+#[derive(Clone, Debug)]
+pub struct SuspectedLeak {
+    pub pid: u32,
+    pub name: String,
+    pub slope_bytes_per_sec: f64,
+    pub first_memory: u64,
+    pub last_memory: u64,
+    pub window_secs: f64,
+    pub average_cpu: f32, // raw sysinfo value, scale with `cpus` for display
+    pub cpus: usize,
+    pub threads: u32,
+}
+
+pub struct ResourceHistory {
+    window_ms: u64,
+    min_samples: usize,
+    min_slope_bytes_per_sec: f64,
+    cpus: usize, // of the latest snapshot
+    pub series: HashMap<u32, ProcessSeries>,
+}
+
+impl ResourceHistory {
+    pub fn new(config: &LeakConfig) -> Self {
+        ResourceHistory {
+            window_ms: config.window_secs * 1000,
+            min_samples: config.min_samples,
+            min_slope_bytes_per_sec: config.min_slope_kb_per_sec * 1024.0,
+            cpus: 1,
+            series: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.series.clear();
+    }
+
+    // Add one sample per process, forget processes that are gone and samples older than the window
+    pub fn push(&mut self, snapshot: &Snapshot) {
+        // going back in time (e.g. seeking in a replay) makes the old samples meaningless
+        let newest = self
+            .series
+            .values()
+            .filter_map(|s| s.samples.back())
+            .map(|s| s.timestamp_ms)
+            .max();
+        if newest.is_some_and(|t| t >= snapshot.timestamp_ms) {
+            self.clear();
+        }
+
+        self.cpus = snapshot.cpus;
+        let mut seen = HashSet::with_capacity(snapshot.processes.len());
+        for process in snapshot.processes.iter().filter(|p| !p.is_thread) {
+            let series = self.series.entry(process.pid).or_insert_with(|| ProcessSeries {
+                name: process.name.clone(),
+                samples: VecDeque::new(),
+            });
+            // a reused PID is a different process
+            if series.name != process.name {
+                series.name = process.name.clone();
+                series.samples.clear();
+            }
+            series.samples.push_back(Sample {
+                timestamp_ms: snapshot.timestamp_ms,
+                memory: process.memory,
+                cpu: process.cpu,
+                threads: process.threads,
+            });
+            while series
+                .samples
+                .front()
+                .is_some_and(|s| snapshot.timestamp_ms - s.timestamp_ms > self.window_ms)
+            {
+                series.samples.pop_front();
+            }
+            seen.insert(process.pid);
+        }
+        self.series.retain(|pid, _| seen.contains(pid));
+    }
+
+    // Processes whose memory never went down over the window and grew faster than the slope threshold
+    pub fn suspected_leaks(&self) -> Vec<SuspectedLeak> {
+        let mut leaks = Vec::new();
+        for (pid, series) in &self.series {
+            let samples = &series.samples;
+            if samples.len() < self.min_samples.max(2) {
+                continue;
+            }
+            let monotonic = samples.iter().zip(samples.iter().skip(1)).all(|(a, b)| b.memory >= a.memory);
+            let (first, last) = (samples[0], samples[samples.len() - 1]);
+            if !monotonic || last.memory == first.memory {
+                continue;
+            }
+            let slope = memory_slope(samples);
+            if slope >= self.min_slope_bytes_per_sec {
+                leaks.push(SuspectedLeak {
+                    pid: *pid,
+                    name: series.name.clone(),
+                    slope_bytes_per_sec: slope,
+                    first_memory: first.memory,
+                    last_memory: last.memory,
+                    window_secs: (last.timestamp_ms - first.timestamp_ms) as f64 / 1000.0,
+                    average_cpu: samples.iter().map(|s| s.cpu).sum::<f32>() / samples.len() as f32,
+                    cpus: self.cpus,
+                    threads: last.threads,
+                });
+            }
+        }
+        // fastest growing first
+        leaks.sort_by(|a, b| b.slope_bytes_per_sec.partial_cmp(&a.slope_bytes_per_sec).unwrap_or(std::cmp::Ordering::Equal));
+        leaks
+    }
+}
+
+// Least squares slope of memory over time, in bytes per second
+fn memory_slope(samples: &VecDeque<Sample>) -> f64 {
+    let start = samples[0].timestamp_ms;
+    let n = samples.len() as f64;
+    let xs: Vec<f64> = samples.iter().map(|s| (s.timestamp_ms - start) as f64 / 1000.0).collect();
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = samples.iter().map(|s| s.memory as f64).sum::<f64>() / n;
+    let mut covariance = 0.0;
+    let mut variance = 0.0;
+    for (x, sample) in xs.iter().zip(samples) {
+        covariance += (x - mean_x) * (sample.memory as f64 - mean_y);
+        variance += (x - mean_x) * (x - mean_x);
+    }
+    if variance == 0.0 {
+        0.0
+    } else {
+        covariance / variance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::ProcessRecord;
+
+    const KIB: u64 = 1024;
+
+    fn record(pid: u32, name: &str, memory: u64) -> ProcessRecord {
+        serde_json::from_value(serde_json::json!({"pid": pid, "name": name, "mem": memory, "cpu": 2.0, "st": "Sleep", "threads": 3})).unwrap()
+    }
+
+    // 60 s window, at least 4 samples, 10 KiB/s
+    fn history() -> ResourceHistory {
+        ResourceHistory::new(&LeakConfig { window_secs: 60, min_samples: 4, min_slope_kb_per_sec: 10.0 })
+    }
+
+    // one sample per second for a single process
+    fn feed(history: &mut ResourceHistory, memory: &[u64]) {
+        for (second, memory) in memory.iter().enumerate() {
+            let snapshot = Snapshot {
+                timestamp_ms: 1000 * (second as u64 + 1),
+                cpus: 4,
+                processes: vec![record(42, "app", *memory)],
+                ..Snapshot::default()
+            };
+            history.push(&snapshot);
+        }
+    }
+
+    #[test]
+    fn steady_growth_above_the_slope_is_a_leak() {
+        let mut history = history();
+        feed(&mut history, &[1000 * KIB, 1020 * KIB, 1040 * KIB, 1060 * KIB, 1080 * KIB]);
+        let leaks = history.suspected_leaks();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!((leaks[0].pid, leaks[0].name.as_str()), (42, "app"));
+        assert!((leaks[0].slope_bytes_per_sec - 20.0 * KIB as f64).abs() < 1e-6);
+        assert_eq!((leaks[0].first_memory, leaks[0].last_memory), (1000 * KIB, 1080 * KIB));
+        assert_eq!(leaks[0].window_secs, 4.0);
+        assert_eq!((leaks[0].average_cpu, leaks[0].cpus, leaks[0].threads), (2.0, 4, 3));
+    }
+
+    #[test]
+    fn growth_below_the_slope_is_not() {
+        let mut history = history();
+        feed(&mut history, &[1000 * KIB, 1005 * KIB, 1010 * KIB, 1015 * KIB, 1020 * KIB]);
+        assert!(history.suspected_leaks().is_empty());
+        // flat memory has no slope at all
+        let mut history = self::history();
+        feed(&mut history, &[1000 * KIB; 6]);
+        assert!(history.suspected_leaks().is_empty());
+    }
+
+    #[test]
+    fn going_down_once_is_not_a_leak() {
+        // fast growth overall, but memory was freed in between
+        let mut history = history();
+        feed(&mut history, &[1000 * KIB, 1100 * KIB, 1050 * KIB, 1200 * KIB, 1300 * KIB]);
+        assert!(history.suspected_leaks().is_empty());
+    }
+
+    #[test]
+    fn too_few_samples() {
+        let mut history = history();
+        feed(&mut history, &[1000 * KIB, 2000 * KIB, 3000 * KIB]);
+        assert!(history.suspected_leaks().is_empty());
+        feed(&mut history, &[1000 * KIB, 2000 * KIB, 3000 * KIB, 4000 * KIB]);
+        assert_eq!(history.suspected_leaks().len(), 1);
+    }
+
+    #[test]
+    fn least_squares_slope() {
+        let samples: VecDeque<Sample> = [(0, 100), (2000, 300), (4000, 500)]
+            .iter()
+            .map(|&(timestamp_ms, memory)| Sample { timestamp_ms, memory, cpu: 0.0, threads: 1 })
+            .collect();
+        assert_eq!(memory_slope(&samples), 100.0);
+        // all samples at the same time have no slope instead of dividing by zero
+        let same_time: VecDeque<Sample> = samples.iter().map(|s| Sample { timestamp_ms: 0, ..*s }).collect();
+        assert_eq!(memory_slope(&same_time), 0.0);
+    }
+
+    #[test]
+    fn clear_and_going_back_in_time_forget_the_samples() {
+        let mut history = history();
+        feed(&mut history, &[1000 * KIB, 1020 * KIB, 1040 * KIB, 1060 * KIB]);
+        assert_eq!(history.series[&42].samples.len(), 4);
+        history.clear();
+        assert!(history.series.is_empty());
+        assert!(history.suspected_leaks().is_empty());
+
+        // feeding the same timestamps again is a seek back in a replay, not 8 samples
+        feed(&mut history, &[1000 * KIB, 1020 * KIB, 1040 * KIB, 1060 * KIB]);
+        feed(&mut history, &[1000 * KIB, 1020 * KIB]);
+        assert_eq!(history.series[&42].samples.len(), 2);
+    }
+}
@@ -1,21 +1,79 @@
-use sysinfo::{System, ProcessStatus, Process};
-use std::{collections::HashMap, time::Duration, io::{self, Write}};
+use sysinfo::System;
+use std::{collections::{HashMap, HashSet}, time::Duration, io::{self, Write}};
 use std::time::{Instant};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
 use clearscreen; // clear terminal screen
 use crossterm::{event, terminal};
 use nix::sys::signal::{self, Signal}; // For sending signals like SIGSTOP/SIGCONT
 use nix::unistd::Pid; // For working with PIDs
 use eframe::{self, egui};
 
+mod affinity;
+mod cgroup;
+mod columns;
+mod config;
+mod container;
+mod diff;
+mod history;
+mod inspect;
+mod leaks;
+mod net;
+mod notifications;
+mod priority;
+mod refresh;
+mod sampler;
+mod snapshot;
+mod systemd;
+mod units;
+mod users;
+mod watch;
+use columns::{CellContext, Column};
+use config::{Config, DisplayConfig};
+use history::{Recorder, Replay};
+use inspect::{MemoryMaps, OpenFile, ThreadInfo};
+use leaks::{ResourceHistory, SuspectedLeak};
+use net::Connection;
+use notifications::{DesktopNotifier, NotificationAction, Severity};
+use users::Permission;
+use priority::{IoClass, IoPriority, Target};
+use refresh::{RefreshPlan, SelfUsage};
+use sampler::Sampler;
+use snapshot::{Aggregated, GroupBy, ProcessFilter, ProcessRecord, Snapshot};
+use systemd::Unit;
+use units::{CpuMode, MemoryUnit};
+
 
 // PROCESS DISPLAY GUI
 
 struct ProcessDisplay {
-    last_update: Instant,
     refresh_interval: Duration,
-    system: sysinfo::System, // default value
+    sampler: Option<Sampler>, // live snapshots, started with the first frame; stays None while replaying
+    self_usage: SelfUsage, // our own CPU and memory for the status bar
+    snapshot: Snapshot, // what is on screen, either live or from the replay
+    paused: bool, // the snapshot on screen is frozen, live ones still arrive for alerts, history and compare
+    live: Option<Snapshot>, // newest live snapshot while paused
+    compare_open: bool, // window diffing the frozen snapshot against live data
+    recorder: Option<Recorder>, // writes history when enabled in the config
+    replay: Option<Replay>, // Some when scrubbing through a history file instead of live data
+    resource_history: ResourceHistory, // per-PID samples for the leak detector
+    leaks_open: bool,
     sort_criteria: SortCriteria,
     reverse_sort: bool, // ASC or DEC
+    group_by: GroupBy, // what one row of the table adds together
+    container_filter: String, // container ID prefix or "host", empty shows everything
+    unit_filter: String, // systemd unit name, empty shows everything
+    user_filter: String, // user name or UID, empty shows everything
+    mine_only: bool, // only our own processes, overrides user_filter
+    command_filter: String, // part of the command line, empty shows everything
+    action_message: Option<(bool, String)>, // (is error, text) from the last context menu action
+    restart_confirm: Option<Unit>, // asked before a unit is restarted
+    restarting: Option<(Unit, Receiver<Result<(), String>>)>, // systemctl running in the background
+    cgroup_limits: HashMap<String, String>, // cgroup path -> limits text, read with each snapshot when grouping by cgroup
+    snapshot_generation: u64, // bumped by every show_snapshot, tells the table its rows are stale
+    table: Option<TableRows>, // the rows on screen, aggregated and sorted once per snapshot
+    selected: Option<String>, // group name of the selected row, kept across refreshes
+    table_viewport: (f32, f32, f32), // scroll offset, height and row pitch (row + spacing) last frame, to keep the selection in view
 
     // For alerts
     check_alerts: CheckAlerts,
@@ -24,95 +82,1303 @@ struct ProcessDisplay {
     alert_name: String,
     alert_cpu: f32,
     alert_memory: u64,
+    alert_io: f64, // bytes per second
+    notifier: Option<DesktopNotifier>, // None when there is no session bus / notification daemon
+
+    // For the connections window (always live, also while replaying)
+    connections_open: bool,
+    connections: Vec<Connection>,
+    connections_error: Option<String>,
+    connections_filter: String, // port, address or process name
+    connections_listening_only: bool,
+    connections_unix: bool,
+
+    // For the process inspector, opened by clicking a PID
+    inspector: Option<Inspector>,
+
+    // For the renice / ionice window, opened from a PID's context menu
+    priority_dialog: Option<PriorityDialog>,
+
+    // For settings
+    config: Config, // for this run, with the TASKMANAGER_* variables and flags applied
+    file_config: Config, // as in the file, settings are saved on top of this
+    config_path: PathBuf,
+    settings_open: bool,
+    settings_draft: Config, // edited copy, only applied when saved
+    settings_message: Option<(bool, String)>, // (is error, text) shown under the Save button
 }
 
 impl ProcessDisplay {
-    pub fn new() -> Self {
+    pub fn new(config: Config, file_config: Config, config_path: PathBuf, replay: Option<Replay>) -> Self {
+        let snapshot = match &replay {
+            Some(replay) => replay.current().clone(),
+            None => Snapshot::capture(&System::new_all()),
+        };
         Self {
-            last_update: Instant::now(),
-            refresh_interval: Duration::from_millis(400),
-            system: System::new_all(),
-            sort_criteria: SortCriteria::Memory,
-            reverse_sort: false,
-
-            check_alerts: CheckAlerts::new(90.0, 2 * 1024 * 1024 * 1024), // 90% CPU and 2 GB memory
+            refresh_interval: config.gui_interval(),
+            sampler: None,
+            self_usage: SelfUsage::new(),
+            snapshot,
+            paused: false,
+            live: None,
+            compare_open: false,
+            // nothing new to record while replaying
+            recorder: if replay.is_none() { Recorder::from_config(&config.history) } else { None },
+            replay,
+            resource_history: ResourceHistory::new(&config.leaks),
+            leaks_open: false,
+            sort_criteria: config.display.sort,
+            reverse_sort: config.display.ascending,
+            group_by: config.display.group_by,
+            container_filter: config.display.container.clone().unwrap_or_default(),
+            unit_filter: config.display.unit.clone().unwrap_or_default(),
+            user_filter: config.display.user.clone().unwrap_or_default(),
+            mine_only: false,
+            command_filter: String::new(),
+            action_message: None,
+            restart_confirm: None,
+            restarting: None,
+            cgroup_limits: HashMap::new(),
+            snapshot_generation: 0,
+            table: None,
+            selected: None,
+            table_viewport: (0.0, 0.0, ROW_HEIGHT),
+
+            check_alerts: CheckAlerts::new(&config),
             show_alert_popup: false,
             alert_pid: 0,
             alert_name: String::new(),
             alert_cpu: 0.0,
             alert_memory: 0,
+            alert_io: 0.0,
+            notifier: DesktopNotifier::connect().ok(),
+
+            settings_open: false,
+            inspector: None,
+            priority_dialog: None,
+            connections_open: false,
+            connections: Vec::new(),
+            connections_error: None,
+            connections_filter: String::new(),
+            connections_listening_only: true,
+            connections_unix: false,
+            settings_draft: config.clone(),
+            settings_message: None,
+            config,
+            file_config,
+            config_path,
+        }
+    }
+
+    // Every new snapshot (live or replayed) goes through here so the per-PID history sees it too.
+    // While paused it is kept aside and the frozen one stays on screen.
+    fn show_snapshot(&mut self, mut snapshot: Snapshot) {
+        snapshot.compute_io_rates(self.newest());
+        self.resource_history.push(&snapshot);
+        if self.paused {
+            self.live = Some(snapshot);
+        } else {
+            self.put_on_screen(snapshot);
+        }
+    }
+
+    // The newest data there is, the frozen snapshot is only what the table shows
+    fn newest(&self) -> &Snapshot {
+        self.live.as_ref().unwrap_or(&self.snapshot)
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if let Some(live) = self.live.take().filter(|_| !paused) {
+            self.put_on_screen(live);
+        }
+    }
+
+    fn put_on_screen(&mut self, snapshot: Snapshot) {
+        self.snapshot = snapshot;
+        self.snapshot_generation += 1;
+        // limits are live values, there are none to show for a replayed snapshot
+        self.cgroup_limits.clear();
+        if self.group_by == GroupBy::Cgroup && self.replay.is_none() {
+            for process in &self.snapshot.processes {
+                if let Some(path) = &process.cgroup {
+                    if !self.cgroup_limits.contains_key(path) {
+                        self.cgroup_limits.insert(path.clone(), cgroup_limits_text(path, self.config.display.memory_unit));
+                    }
+                }
+            }
+        }
+    }
+
+    // Window listing processes whose memory keeps growing
+    fn show_leaks(&mut self, ctx: &egui::Context) {
+        let leaks = self.resource_history.suspected_leaks();
+        let unit = self.config.display.memory_unit;
+        egui::Window::new(egui::RichText::new("Suspected leaks").size(20.0).strong())
+            .open(&mut self.leaks_open)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Memory only growing for at least {} samples, faster than {} KB/s (window {} s)",
+                    self.config.leaks.min_samples, self.config.leaks.min_slope_kb_per_sec, self.config.leaks.window_secs
+                ));
+                ui.add_space(10.0);
+                if leaks.is_empty() {
+                    ui.label(egui::RichText::new("No suspected leaks.").color(egui::Color32::GREEN));
+                    return;
+                }
+                egui::Grid::new("leaks_grid").striped(true).show(ui, |ui| {
+                    let cpu_header = format!("Avg CPU (% {})", self.config.display.cpu_mode.label());
+                    for header in ["PID", "Name", "Growth", "Memory", "Over (s)", &cpu_header, "Threads"] {
+                        ui.label(egui::RichText::new(header).strong());
+                    }
+                    ui.end_row();
+                    for leak in &leaks {
+                        ui.label(leak.pid.to_string());
+                        ui.label(&leak.name);
+                        ui.label(
+                            egui::RichText::new(units::format_rate(leak.slope_bytes_per_sec, unit))
+                                .color(egui::Color32::from_rgb(255, 165, 0)),
+                        );
+                        ui.label(format!(
+                            "{} -> {}",
+                            units::format_bytes(leak.first_memory, unit),
+                            units::format_bytes(leak.last_memory, unit)
+                        ));
+                        ui.label(format!("{:.0}", leak.window_secs));
+                        ui.label(format!("{:.2}", self.config.display.cpu_mode.scale(leak.average_cpu, leak.cpus)));
+                        ui.label(leak.threads.to_string());
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    // Window listing what changed between the frozen snapshot and the newest live one
+    fn show_compare(&mut self, ctx: &egui::Context) {
+        let diff = diff::diff(&self.snapshot, self.newest());
+        let unit = self.config.display.memory_unit;
+        let (cpu_mode, cpus) = (self.config.display.cpu_mode, self.snapshot.cpus);
+        let cpu = |usage: f32| format!("{:.1}%", cpu_mode.scale(usage, cpus));
+        let mut inspect = None;
+        egui::Window::new(egui::RichText::new("Compare").size(20.0).strong())
+            .open(&mut self.compare_open)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Frozen at {}, live data {:.0} s later. Click a PID to inspect it.",
+                    snapshot::format_time_of_day(self.snapshot.timestamp_ms),
+                    diff.seconds
+                ));
+                ui.add_space(10.0);
+                if diff.is_empty() {
+                    ui.label(egui::RichText::new("Nothing started, exited or changed much yet.").color(egui::Color32::GREEN));
+                    return;
+                }
+                // started and exited: PID, name, memory, CPU, command
+                let sections = [
+                    ("Started", egui::Color32::GREEN, &diff.started),
+                    ("Exited", egui::Color32::LIGHT_RED, &diff.exited),
+                ];
+                for (title, color, processes) in sections {
+                    if processes.is_empty() {
+                        continue;
+                    }
+                    ui.label(egui::RichText::new(format!("{} ({})", title, processes.len())).color(color).strong());
+                    egui::Grid::new(format!("compare_{}", title)).striped(true).show(ui, |ui| {
+                        for process in processes.iter() {
+                            if ui.link(process.pid.to_string()).clicked() {
+                                inspect = Some((process.pid, process.name.clone()));
+                            }
+                            ui.label(egui::RichText::new(&process.name).color(color));
+                            ui.label(units::format_bytes(process.memory, unit));
+                            ui.label(cpu(process.cpu));
+                            ui.label(snapshot::truncate_middle(process.cmd.as_deref().unwrap_or(""), 60));
+                            ui.end_row();
+                        }
+                    });
+                    ui.add_space(10.0);
+                }
+                if !diff.changed.is_empty() {
+                    let color = egui::Color32::YELLOW;
+                    ui.label(egui::RichText::new(format!("Changed a lot ({})", diff.changed.len())).color(color).strong());
+                    egui::Grid::new("compare_changed").striped(true).show(ui, |ui| {
+                        for header in ["PID", "Name", "CPU", "Memory"] {
+                            ui.label(egui::RichText::new(header).strong());
+                        }
+                        ui.end_row();
+                        for change in &diff.changed {
+                            if ui.link(change.pid.to_string()).clicked() {
+                                inspect = Some((change.pid, change.name.clone()));
+                            }
+                            ui.label(egui::RichText::new(&change.name).color(color));
+                            ui.label(format!("{} -> {}", cpu(change.cpu.0), cpu(change.cpu.1)));
+                            ui.label(format!(
+                                "{} -> {}",
+                                units::format_bytes(change.memory.0, unit),
+                                units::format_bytes(change.memory.1, unit)
+                            ));
+                            ui.end_row();
+                        }
+                    });
+                }
+            });
+        if let Some((pid, name)) = inspect {
+            self.inspector = Some(Inspector::new(pid, name, self.snapshot.cpus));
         }
     }
+
+    fn refresh_connections(&mut self) {
+        match net::connections(Path::new("/proc"), self.connections_unix) {
+            Ok(connections) => {
+                self.connections = connections;
+                self.connections_error = None;
+            }
+            Err(e) => self.connections_error = Some(e),
+        }
+    }
+
+    // Window listing sockets with their owning process, filtered by port / address / name, with a kill button
+    fn show_connections(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut refresh = false;
+        let mut kill = None;
+        egui::Window::new(egui::RichText::new("Connections").size(20.0).strong())
+            .open(&mut open)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.connections_filter);
+                    refresh |= ui.checkbox(&mut self.connections_listening_only, "Listening only").changed();
+                    refresh |= ui.checkbox(&mut self.connections_unix, "Unix sockets").changed();
+                    refresh |= ui.button("Refresh").clicked();
+                });
+                if let Some(e) = &self.connections_error {
+                    ui.label(egui::RichText::new(e).color(egui::Color32::LIGHT_RED));
+                }
+                ui.add_space(10.0);
+
+                // ":8080" matches the port exactly, anything else is a substring of the address or name
+                let filter = self.connections_filter.trim().to_lowercase();
+                let port = filter.strip_prefix(':').and_then(|_| net::parse_port(&filter));
+                let shown = self.connections.iter().filter(|c| {
+                    if self.connections_listening_only && !c.is_listening() {
+                        return false;
+                    }
+                    match port {
+                        Some(port) => c.port == Some(port),
+                        None => {
+                            filter.is_empty()
+                                || c.local.to_lowercase().contains(&filter)
+                                || c.remote.to_lowercase().contains(&filter)
+                                || c.owners.iter().any(|(_, name)| name.to_lowercase().contains(&filter))
+                        }
+                    }
+                });
+
+                egui::Grid::new("connections_grid").striped(true).show(ui, |ui| {
+                    for header in ["Proto", "Local", "Remote", "State", "PID", "Process", ""] {
+                        ui.label(egui::RichText::new(header).strong());
+                    }
+                    ui.end_row();
+                    for connection in shown {
+                        ui.label(connection.protocol.label());
+                        ui.label(&connection.local);
+                        ui.label(&connection.remote);
+                        let state_color = if connection.is_listening() { egui::Color32::GREEN } else { egui::Color32::from_gray(160) };
+                        ui.label(egui::RichText::new(&connection.state).color(state_color));
+                        if connection.owners.is_empty() {
+                            // the owner's /proc/<pid>/fd could not be read
+                            ui.label("-");
+                            ui.label(egui::RichText::new("unknown").weak());
+                            ui.label("");
+                        } else {
+                            let (pids, names) = connection.owner_text();
+                            ui.label(pids);
+                            ui.label(names);
+                            // every worker of a pre-fork server holds the socket, killing one would leave it open
+                            let label = if connection.owners.len() > 1 { "Kill all" } else { "Kill" };
+                            if ui.button(label).clicked() {
+                                kill = Some(connection.owners.iter().map(|(pid, _)| *pid).collect::<Vec<u32>>());
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if let Some(pids) = kill {
+            let outcomes: Vec<Result<String, String>> = pids.into_iter().map(kill_process).collect();
+            let failed = outcomes.iter().any(Result::is_err);
+            let lines: Vec<String> = outcomes.into_iter().map(|outcome| outcome.unwrap_or_else(|e| e)).collect();
+            self.action_message = Some((failed, lines.join("\n")));
+            refresh = true;
+        }
+        if refresh {
+            self.refresh_connections();
+        }
+        self.connections_open = open;
+    }
+
+    // Restarting stops every process of the service, so it is only done after asking
+    fn show_restart_confirm(&mut self, ctx: &egui::Context) {
+        let Some(unit) = self.restart_confirm.clone() else {
+            return;
+        };
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new(egui::RichText::new("Restart unit").size(20.0).strong())
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Restart {}?", unit.label()));
+                ui.label(egui::RichText::new("All its processes are stopped and started again.").weak());
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    confirmed = ui.button(egui::RichText::new("Restart").color(egui::Color32::LIGHT_RED)).clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        if confirmed {
+            let ctx = ctx.clone();
+            let result = systemd::restart_in_background(unit.clone(), move || ctx.request_repaint());
+            self.action_message = Some((false, format!("Restarting {}...", unit.label())));
+            self.restarting = Some((unit, result));
+        }
+        if confirmed || cancelled || !open {
+            self.restart_confirm = None;
+        }
+    }
+
+    // Renice / ionice window for one process, its subtree or every process with its name
+    fn show_priority(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut renice = false;
+        let mut ionice = false;
+        if let Some(dialog) = self.priority_dialog.as_mut() {
+            egui::Window::new(egui::RichText::new(format!("Priority: {} ({})", dialog.name, dialog.pid)).size(20.0).strong())
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.label("Apply to");
+                    ui.radio_value(&mut dialog.target, Target::Pid(dialog.pid), "This process");
+                    ui.radio_value(&mut dialog.target, Target::Tree(dialog.pid), "This process and all its children");
+                    ui.radio_value(&mut dialog.target, Target::Name(dialog.name.clone()), format!("Every process named {}", dialog.name));
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        ui.label("Nice");
+                        ui.add(egui::Slider::new(&mut dialog.nice, priority::NICE_MIN..=priority::NICE_MAX));
+                        renice = ui.button("Renice").clicked();
+                    });
+                    ui.label(egui::RichText::new("Lowering the nice value needs root.").weak());
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        ui.label("I/O class");
+                        egui::ComboBox::from_id_source("io_class")
+                            .selected_text(dialog.io_class.label())
+                            .show_ui(ui, |ui| {
+                                for class in priority::IO_CLASSES {
+                                    ui.selectable_value(&mut dialog.io_class, class, class.label());
+                                }
+                            });
+                        if dialog.io_class.has_level() {
+                            ui.add(egui::Slider::new(&mut dialog.io_level, 0..=7).text("level (0 = highest)"));
+                        }
+                        ionice = ui.button("Set I/O priority").clicked();
+                    });
+
+                    if !dialog.results.is_empty() {
+                        ui.add_space(10.0);
+                        for line in &dialog.results {
+                            ui.label(line);
+                        }
+                    }
+                });
+        }
+        if !open {
+            self.priority_dialog = None;
+            return;
+        }
+
+        if renice || ionice {
+            // always the live processes, the snapshot on screen may be from a replay
+            let processes = match self.replay {
+                Some(_) => Snapshot::capture(&System::new_all()).processes,
+                None => self.newest().processes.clone(),
+            };
+            if let Some(dialog) = self.priority_dialog.as_mut() {
+                let targets = dialog.target.resolve(&processes);
+                let proc_root = Path::new("/proc");
+                dialog.results = if targets.is_empty() {
+                    vec!["No matching process is running.".to_string()]
+                } else if renice {
+                    let nice = dialog.nice;
+                    priority::apply(&targets, &format!("nice set to {}", nice), |pid| priority::renice(proc_root, pid, nice))
+                } else {
+                    let io = IoPriority { class: dialog.io_class, level: dialog.io_level };
+                    priority::apply(&targets, &format!("I/O priority set to {}", io.describe()), |pid| {
+                        priority::ionice(proc_root, pid, io.class, io.level)
+                    })
+                };
+            }
+        }
+    }
+
+    // Inspector window for one process, a tab each for open files and memory maps
+    fn show_inspector(&mut self, ctx: &egui::Context) {
+        let unit = self.config.display.memory_unit;
+        let cpu_mode = self.config.display.cpu_mode;
+        let cpus = self.snapshot.cpus;
+        let refresh_interval = self.refresh_interval;
+        let mut open = true;
+        if let Some(inspector) = self.inspector.as_mut() {
+            egui::Window::new(egui::RichText::new(format!("{} ({})", inspector.name, inspector.pid)).size(20.0).strong())
+                .id(egui::Id::new("inspector"))
+                .open(&mut open)
+                .vscroll(true)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut inspector.tab, InspectorTab::Files, "Open files");
+                        ui.selectable_value(&mut inspector.tab, InspectorTab::Maps, "Memory maps");
+                        ui.selectable_value(&mut inspector.tab, InspectorTab::Threads, "Threads");
+                        ui.selectable_value(&mut inspector.tab, InspectorTab::Affinity, "CPU affinity");
+                        ui.selectable_value(&mut inspector.tab, InspectorTab::Container, "Container");
+                        if ui.button("Refresh").clicked() {
+                            inspector.refresh();
+                        }
+                    });
+                    ui.separator();
+                    match inspector.tab {
+                        InspectorTab::Files => match &inspector.files {
+                            Ok(files) => {
+                                ui.label(fd_usage_text(files.len(), &inspector.fd_limit));
+                                egui::Grid::new("inspector_files").striped(true).show(ui, |ui| {
+                                    for header in ["FD", "Type", "Target"] {
+                                        ui.label(egui::RichText::new(header).strong());
+                                    }
+                                    ui.end_row();
+                                    for file in files {
+                                        ui.label(file.fd.to_string());
+                                        ui.label(file.kind.label());
+                                        ui.label(describe_fd(file, &inspector.sockets));
+                                        ui.end_row();
+                                    }
+                                });
+                            }
+                            Err(e) => {
+                                ui.label(egui::RichText::new(e).color(egui::Color32::LIGHT_RED));
+                            }
+                        },
+                        InspectorTab::Maps => {
+                            if inspector.maps.is_none() {
+                                inspector.maps = Some(inspect::memory_maps(Path::new("/proc"), inspector.pid));
+                            }
+                            match inspector.maps.as_ref() {
+                                Some(Ok(maps)) => {
+                                    let total = &maps.total;
+                                    ui.label(format!(
+                                        "RSS {}   PSS {}   Shared {}   Private {}   Swap {}",
+                                        units::format_bytes(total.rss, unit),
+                                        units::format_bytes(total.pss, unit),
+                                        units::format_bytes(total.shared(), unit),
+                                        units::format_bytes(total.private(), unit),
+                                        units::format_bytes(total.swap, unit)
+                                    ));
+                                    egui::Grid::new("inspector_maps").striped(true).show(ui, |ui| {
+                                        for header in ["Mapping", "Count", "Size", "RSS", "PSS", "Shared", "Private", "Swap"] {
+                                            ui.label(egui::RichText::new(header).strong());
+                                        }
+                                        ui.end_row();
+                                        for group in &maps.groups {
+                                            ui.label(&group.name);
+                                            ui.label(group.mappings.to_string());
+                                            for bytes in [
+                                                group.usage.size,
+                                                group.usage.rss,
+                                                group.usage.pss,
+                                                group.usage.shared(),
+                                                group.usage.private(),
+                                                group.usage.swap,
+                                            ] {
+                                                ui.label(units::format_bytes(bytes, unit));
+                                            }
+                                            ui.end_row();
+                                        }
+                                    });
+                                }
+                                Some(Err(e)) => {
+                                    ui.label(egui::RichText::new(e).color(egui::Color32::LIGHT_RED));
+                                }
+                                None => {}
+                            }
+                        }
+                        InspectorTab::Threads => {
+                            // threads are always live, even while replaying
+                            if inspector.threads_sampled.elapsed() >= refresh_interval {
+                                inspector.refresh_threads();
+                            }
+                            ui.ctx().request_repaint_after(refresh_interval);
+                            match inspector.threads.as_mut() {
+                                Ok(threads) => {
+                                    if inspector.threads_by_cpu {
+                                        threads.sort_by(|a, b| b.cpu.partial_cmp(&a.cpu).unwrap_or(std::cmp::Ordering::Equal).then(a.tid.cmp(&b.tid)));
+                                    } else {
+                                        threads.sort_by_key(|t| t.tid);
+                                    }
+                                    ui.label(format!("{} threads", threads.len()));
+                                    egui::Grid::new("inspector_threads").striped(true).show(ui, |ui| {
+                                        if ui.button(egui::RichText::new("TID").strong()).clicked() {
+                                            inspector.threads_by_cpu = false;
+                                        }
+                                        ui.label(egui::RichText::new("Name").strong());
+                                        ui.label(egui::RichText::new("State").strong());
+                                        if ui.button(egui::RichText::new(cpu_mode.header()).strong()).clicked() {
+                                            inspector.threads_by_cpu = true;
+                                        }
+                                        ui.label(egui::RichText::new("Last CPU").strong());
+                                        ui.end_row();
+                                        for thread in threads.iter() {
+                                            let cpu = cpu_mode.scale(thread.cpu, cpus);
+                                            ui.label(thread.tid.to_string());
+                                            ui.label(&thread.name);
+                                            ui.label(&thread.state);
+                                            ui.label(
+                                                egui::RichText::new(format!("{:.2}%", cpu))
+                                                    .color(usage_color(cpu, &self.config.colors.cpu_breakpoints)),
+                                            );
+                                            ui.label(thread.last_cpu.to_string());
+                                            ui.end_row();
+                                        }
+                                    });
+                                }
+                                Err(e) => {
+                                    ui.label(egui::RichText::new(e.as_str()).color(egui::Color32::LIGHT_RED));
+                                }
+                            }
+                        }
+                        InspectorTab::Affinity => {
+                            match &inspector.affinity {
+                                Ok(mask) if affinity::is_unpinned(mask, inspector.cpus) => {
+                                    ui.label(format!("Runs on all {} CPUs", inspector.cpus));
+                                }
+                                Ok(mask) => {
+                                    ui.label(format!("Pinned to CPUs {} ({} of {})", affinity::format_cpu_list(mask), mask.len(), inspector.cpus));
+                                }
+                                Err(e) => {
+                                    ui.label(egui::RichText::new(e.as_str()).color(egui::Color32::LIGHT_RED));
+                                }
+                            }
+                            if inspector.pinned_threads > 0 {
+                                ui.label(
+                                    egui::RichText::new(format!("{} thread(s) have a mask of their own", inspector.pinned_threads))
+                                        .color(egui::Color32::YELLOW),
+                                );
+                            }
+                            ui.add_space(10.0);
+                            // eight cores per row
+                            egui::Grid::new("inspector_affinity").show(ui, |ui| {
+                                for (cpu, allowed) in inspector.affinity_draft.iter_mut().enumerate() {
+                                    ui.checkbox(allowed, format!("CPU{}", cpu));
+                                    if cpu % 8 == 7 {
+                                        ui.end_row();
+                                    }
+                                }
+                            });
+                            ui.add_space(10.0);
+                            let mut apply = false;
+                            ui.horizontal(|ui| {
+                                if ui.button("All").clicked() {
+                                    inspector.affinity_draft.iter_mut().for_each(|allowed| *allowed = true);
+                                }
+                                if ui.button("None").clicked() {
+                                    inspector.affinity_draft.iter_mut().for_each(|allowed| *allowed = false);
+                                }
+                                ui.checkbox(&mut inspector.affinity_all_threads, "All threads");
+                                apply = ui.button("Apply").clicked();
+                            });
+                            if apply {
+                                inspector.apply_affinity();
+                            }
+                            if let Some((is_error, text)) = &inspector.affinity_message {
+                                let color = if *is_error { egui::Color32::LIGHT_RED } else { egui::Color32::GREEN };
+                                ui.label(egui::RichText::new(text).color(color));
+                            }
+                        }
+                        InspectorTab::Container => {
+                            ui.label(container_text(inspector.cgroup.as_deref()));
+                            ui.add_space(10.0);
+                            match &inspector.namespaces {
+                                Ok(namespaces) => {
+                                    egui::Grid::new("inspector_namespaces").striped(true).show(ui, |ui| {
+                                        for header in ["Namespace", "Inode", ""] {
+                                            ui.label(egui::RichText::new(header).strong());
+                                        }
+                                        ui.end_row();
+                                        for (kind, inode, shared) in namespace_rows(namespaces, &inspector.host_namespaces) {
+                                            ui.label(kind);
+                                            ui.label(inode.to_string());
+                                            ui.label(shared);
+                                            ui.end_row();
+                                        }
+                                    });
+                                }
+                                Err(e) => {
+                                    ui.label(egui::RichText::new(e.as_str()).color(egui::Color32::LIGHT_RED));
+                                }
+                            }
+                        }
+                    }
+                });
+        }
+        if !open {
+            self.inspector = None;
+        }
+    }
+
+    // Settings window, edits a draft copy of the config which is validated and written on Save
+    fn show_settings(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut save = false;
+        let sample_cost = self.sampler.as_ref().and_then(Sampler::cost).map(|cost| cost.describe());
+        egui::Window::new(egui::RichText::new("Settings").size(20.0).strong())
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let draft = &mut self.settings_draft;
+                egui::Grid::new("settings_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("CPU alert threshold (%)");
+                    ui.add(egui::DragValue::new(&mut draft.alerts.cpu_threshold).speed(1.0));
+                    ui.end_row();
+                    ui.label("Memory alert threshold (MB)");
+                    ui.add(egui::DragValue::new(&mut draft.alerts.memory_threshold_mb).speed(16.0));
+                    ui.end_row();
+                    ui.label("Disk I/O alert threshold (MB/s, 0 = off)");
+                    ui.add(egui::DragValue::new(&mut draft.alerts.io_threshold_mb_per_sec).speed(1.0));
+                    ui.end_row();
+
+                    ui.label("GUI refresh (ms)");
+                    ui.add(egui::DragValue::new(&mut draft.refresh.gui_ms).speed(10.0));
+                    ui.end_row();
+                    // what the background sampler costs with the current columns
+                    if let Some(cost) = &sample_cost {
+                        ui.label("Last refresh");
+                        ui.label(egui::RichText::new(cost).weak());
+                        ui.end_row();
+                    }
+                    ui.label("CLI refresh (ms)");
+                    ui.add(egui::DragValue::new(&mut draft.refresh.cli_ms).speed(10.0));
+                    ui.end_row();
+                    ui.label("Watch interval (ms)");
+                    ui.add(egui::DragValue::new(&mut draft.refresh.watch_ms).speed(10.0));
+                    ui.end_row();
+                    ui.label("Restart units");
+                    ui.checkbox(&mut draft.actions.restart_units, "offer \"Restart unit\" for processes of a systemd service");
+                    ui.end_row();
+                    ui.label("Low impact mode");
+                    ui.checkbox(&mut draft.refresh.low_impact, format!("refresh every {} ms or slower, cheap columns only", config::LOW_IMPACT_MIN_MS))
+                        .on_hover_text(format!(
+                            "Refreshes only every {} s while the window is minimized or in the background",
+                            config::LOW_IMPACT_BACKGROUND_MS / 1000
+                        ));
+                    ui.end_row();
+
+                    // gray / green / yellow / orange / red
+                    ui.label("CPU color breakpoints (%)");
+                    ui.horizontal(|ui| {
+                        for breakpoint in draft.colors.cpu_breakpoints.iter_mut() {
+                            ui.add(egui::DragValue::new(breakpoint).speed(1.0));
+                        }
+                    });
+                    ui.end_row();
+                    ui.label("Memory color breakpoints (%)");
+                    ui.horizontal(|ui| {
+                        for breakpoint in draft.colors.memory_breakpoints.iter_mut() {
+                            ui.add(egui::DragValue::new(breakpoint).speed(1.0));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Default sort");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut draft.display.sort, SortCriteria::Memory, "Memory");
+                        ui.radio_value(&mut draft.display.sort, SortCriteria::CPU, "CPU");
+                        ui.radio_value(&mut draft.display.sort, SortCriteria::IO, "Disk I/O");
+                        ui.checkbox(&mut draft.display.ascending, "Ascending");
+                    });
+                    ui.end_row();
+
+                    ui.label("Group processes by");
+                    egui::ComboBox::from_id_source("settings_group_by")
+                        .selected_text(draft.display.group_by.label())
+                        .show_ui(ui, |ui| {
+                            for mode in snapshot::GROUP_BY_MODES {
+                                ui.selectable_value(&mut draft.display.group_by, mode, mode.label());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("CPU mode");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut draft.display.cpu_mode, CpuMode::Irix, "Per core (Irix, can exceed 100%)");
+                        ui.radio_value(&mut draft.display.cpu_mode, CpuMode::Solaris, "Whole machine (Solaris)");
+                    });
+                    ui.end_row();
+
+                    ui.label("Memory unit");
+                    egui::ComboBox::from_id_source("memory_unit")
+                        .selected_text(draft.display.memory_unit.label())
+                        .show_ui(ui, |ui| {
+                            for unit in units::MEMORY_UNITS {
+                                ui.selectable_value(&mut draft.display.memory_unit, unit, unit.label());
+                            }
+                        });
+                    ui.end_row();
+                });
+
+                // shown columns in order, hidden ones can be added back at the end
+                ui.add_space(10.0);
+                ui.label(egui::RichText::new("Process table columns").strong());
+                let mut move_up = None;
+                let mut remove = None;
+                egui::Grid::new("columns_grid").num_columns(4).show(ui, |ui| {
+                    let shown = draft.display.columns.len();
+                    for (i, column) in draft.display.columns.iter().enumerate() {
+                        ui.label(column.key());
+                        if ui.add_enabled(i > 0, egui::Button::new("▲").small()).clicked() {
+                            move_up = Some(i);
+                        }
+                        if ui.add_enabled(i + 1 < shown, egui::Button::new("▼").small()).clicked() {
+                            move_up = Some(i + 1);
+                        }
+                        if ui.add_enabled(shown > 1, egui::Button::new("Hide").small()).clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = move_up {
+                    draft.display.columns.swap(i - 1, i);
+                }
+                if let Some(i) = remove {
+                    draft.display.columns.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    let mut add = None;
+                    egui::ComboBox::from_id_source("add_column").selected_text("Add column").show_ui(ui, |ui| {
+                        for column in columns::ALL_COLUMNS.iter().filter(|c| !draft.display.columns.contains(c)) {
+                            if ui.selectable_label(false, column.key()).clicked() {
+                                add = Some(*column);
+                            }
+                        }
+                    });
+                    if let Some(column) = add {
+                        draft.display.columns.push(column);
+                    }
+                    // widths are set by dragging the lines between the table headers
+                    if ui.button("Fit all widths to content").clicked() {
+                        draft.display.column_widths.clear();
+                    }
+                });
+
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        save = true;
+                    }
+                    if ui.button("Reset to defaults").clicked() {
+                        *draft = Config::default();
+                    }
+                });
+                ui.label(egui::RichText::new(self.config_path.display().to_string()).weak());
+                if let Some((is_error, message)) = &self.settings_message {
+                    let color = if *is_error { egui::Color32::LIGHT_RED } else { egui::Color32::GREEN };
+                    ui.label(egui::RichText::new(message).color(color));
+                }
+            });
+
+        if save {
+            // only what was edited in the window is written, not the overrides of this run
+            let saved = self
+                .settings_draft
+                .validate()
+                .and_then(|()| self.file_config.with_edits(&self.config, &self.settings_draft))
+                .and_then(|file_config| file_config.save(&self.config_path).map(|()| file_config));
+            match saved {
+                Ok(file_config) => {
+                    self.file_config = file_config;
+                    self.apply_config(self.settings_draft.clone());
+                    self.settings_message = Some((false, "Saved.".to_string()));
+                }
+                Err(e) => self.settings_message = Some((true, e)),
+            }
+        }
+        self.settings_open = open;
+    }
+
+    // Use a (validated) config from the settings window right away
+    fn apply_config(&mut self, config: Config) {
+        self.refresh_interval = config.gui_interval();
+        if let Some(sampler) = &self.sampler {
+            sampler.set_interval(self.refresh_interval);
+        }
+        self.check_alerts.cpu_threshold = config.alerts.cpu_threshold;
+        self.check_alerts.memory_threshold = config.memory_threshold_bytes();
+        self.check_alerts.cpu_mode = config.display.cpu_mode;
+        self.check_alerts.io_threshold = config.io_threshold_bytes_per_sec();
+        self.sort_criteria = config.display.sort;
+        self.reverse_sort = config.display.ascending;
+        self.group_by = config.display.group_by;
+        self.container_filter = config.display.container.clone().unwrap_or_default();
+        self.unit_filter = config.display.unit.clone().unwrap_or_default();
+        self.user_filter = config.display.user.clone().unwrap_or_default();
+        if config.leaks != self.config.leaks {
+            self.resource_history = ResourceHistory::new(&config.leaks);
+        }
+        self.config = config;
+    }
 }
 
 impl Default for ProcessDisplay {
     fn default() -> Self {
-        ProcessDisplay {
-            last_update: Instant::now(),
-            refresh_interval: Duration::from_millis(400),
-            system: System::new_all(),
-            sort_criteria: SortCriteria::Memory,
-            reverse_sort: false,
-
-            check_alerts: CheckAlerts::new(90.0, 2 * 1024 * 1024 * 1024), // 90% CPU and 2 GB memory
-            show_alert_popup: false,
-            alert_pid: 0,
-            alert_name: String::new(),
-            alert_cpu: 0.0,
-            alert_memory: 0,
-        }
+        ProcessDisplay::new(Config::default(), Config::default(), config::default_config_path(), None)
     }
 }
 
 // TREE VIEW GUI
 
 struct TreeView {
-    system: sysinfo::System,
+    refresh_interval: Duration,
+    low_impact: bool, // no sampling while the window is in the background
+    sampler: Option<Sampler>, // started with the first frame, None while replaying
+    snapshot: Snapshot,
+    replay: Option<Replay>, // Some when showing a history file instead of live data
 }
 
 impl TreeView {
-    pub fn new() -> Self {
+    pub fn new(replay: Option<Replay>, config: &Config) -> Self {
+        let snapshot = match &replay {
+            Some(replay) => replay.current().clone(),
+            None => Snapshot::capture(&System::new_all()),
+        };
         Self {
-            system: System::new_all(),
+            refresh_interval: config.gui_interval(),
+            low_impact: config.refresh.low_impact,
+            sampler: None,
+            snapshot,
+            replay,
         }
     }
 }
 
 impl Default for TreeView {
     fn default() -> Self {
-        TreeView {
-            system: System::new_all(),
-        }
+        TreeView::new(None, &Config::default())
     }
 }
 
 // CHECK ALERTS GUI
 
 struct CheckAlerts {
-    system: System,
     alert_message: Option<String>,
-    cpu_threshold: f32,
+    cpu_threshold: f32, // compared against the CPU usage in `cpu_mode`
     memory_threshold: u64, // in bytes (e.g., 2 GB = 2 * 1024 * 1024 * 1024)
+    io_threshold: f64, // disk read + write in bytes per second, 0 = off
+    cpu_mode: CpuMode,
+}
+
+
+// PROCESS INSPECTOR GUI
+
+#[derive(PartialEq, Clone, Copy)]
+enum InspectorTab {
+    Files,
+    Maps,
+    Threads,
+    Affinity,
+    Container,
+}
+
+// What the inspector window shows for one pid, read once and on Refresh (smaps can be slow for big processes)
+struct Inspector {
+    pid: u32,
+    name: String,
+    tab: InspectorTab,
+    files: Result<Vec<OpenFile>, String>,
+    fd_limit: Result<(Option<u64>, Option<u64>), String>,
+    sockets: HashMap<u64, Connection>, // to say what a socket fd is connected to
+    maps: Option<Result<MemoryMaps, String>>, // only read when the tab is opened
+    threads: Result<Vec<ThreadInfo>, String>, // sampled again every refresh interval while the tab is open
+    threads_sampled: Instant,
+    threads_by_cpu: bool, // busiest thread first, otherwise by TID
+    cpus: usize,
+    affinity: Result<Vec<usize>, String>, // CPUs of the main thread
+    affinity_draft: Vec<bool>, // one checkbox per CPU, applied with the Apply button
+    affinity_all_threads: bool,
+    pinned_threads: usize, // threads with a mask different from the main thread's
+    affinity_message: Option<(bool, String)>, // (is error, text)
+    cgroup: Option<String>,
+    namespaces: Result<Vec<(&'static str, u64)>, String>,
+    host_namespaces: Vec<(&'static str, u64)>, // PID 1's, to tell which namespaces are the process' own
+}
+
+impl Inspector {
+    fn new(pid: u32, name: String, cpus: usize) -> Self {
+        let mut inspector = Inspector {
+            pid,
+            name,
+            tab: InspectorTab::Files,
+            files: Ok(Vec::new()),
+            fd_limit: Ok((None, None)),
+            sockets: HashMap::new(),
+            maps: None,
+            threads: Ok(Vec::new()),
+            threads_sampled: Instant::now(),
+            threads_by_cpu: true,
+            cpus,
+            affinity: Ok(Vec::new()),
+            affinity_draft: vec![true; cpus],
+            affinity_all_threads: true,
+            pinned_threads: 0,
+            affinity_message: None,
+            cgroup: None,
+            namespaces: Ok(Vec::new()),
+            host_namespaces: Vec::new(),
+        };
+        inspector.refresh();
+        inspector
+    }
+
+    fn refresh(&mut self) {
+        let proc_root = Path::new("/proc");
+        self.files = inspect::open_files(proc_root, self.pid);
+        self.fd_limit = inspect::fd_limit(proc_root, self.pid);
+        self.sockets = net::sockets_by_inode(proc_root).unwrap_or_default();
+        self.maps = match self.tab {
+            InspectorTab::Maps => Some(inspect::memory_maps(proc_root, self.pid)),
+            _ => None,
+        };
+        self.refresh_threads();
+        self.refresh_affinity();
+        self.cgroup = cgroup::read_cgroup(proc_root, self.pid);
+        self.namespaces = container::namespaces(proc_root, self.pid);
+        self.host_namespaces = container::namespaces(proc_root, 1).unwrap_or_default();
+    }
+
+    fn refresh_affinity(&mut self) {
+        self.affinity = affinity::get(self.pid);
+        if let Ok(mask) = &self.affinity {
+            self.affinity_draft = (0..self.cpus).map(|cpu| mask.contains(&cpu)).collect();
+            self.pinned_threads = affinity::thread_masks(Path::new("/proc"), self.pid)
+                .iter()
+                .filter(|(_, thread_mask)| thread_mask.as_ref().is_ok_and(|m| m != mask))
+                .count();
+        }
+    }
+
+    fn apply_affinity(&mut self) {
+        let cpus: Vec<usize> = (0..self.cpus).filter(|cpu| self.affinity_draft[*cpu]).collect();
+        self.affinity_message = Some(match affinity::set(Path::new("/proc"), self.pid, &cpus, self.affinity_all_threads) {
+            Ok(changed) => (false, format!("Pinned {} thread(s) to CPUs {}", changed, affinity::format_cpu_list(&cpus))),
+            Err(e) => (true, e),
+        });
+        self.refresh_affinity();
+    }
+
+    // New thread sample, CPU usage comes from the ticks used since the previous one
+    fn refresh_threads(&mut self) {
+        let mut threads = inspect::threads(Path::new("/proc"), self.pid);
+        if let (Ok(threads), Ok(previous)) = (threads.as_mut(), self.threads.as_ref()) {
+            inspect::compute_thread_cpu(threads, previous, self.threads_sampled.elapsed().as_secs_f64());
+        }
+        self.threads = threads;
+        self.threads_sampled = Instant::now();
+    }
+}
+
+// Nice / I/O priority being edited, starting from the process' current values
+struct PriorityDialog {
+    pid: u32,
+    name: String,
+    target: Target,
+    nice: i32,
+    io_class: IoClass,
+    io_level: u8,
+    results: Vec<String>, // one line per process from the last Renice / Set I/O priority
 }
 
+impl PriorityDialog {
+    fn new(pid: u32, name: String, snapshot: &Snapshot) -> Self {
+        let nice = snapshot.process(pid).map_or(0, |p| p.nice);
+        // best-effort 4 is what the kernel uses for a nice 0 process without a class
+        let io = priority::io_priority(pid).unwrap_or(IoPriority { class: IoClass::BestEffort, level: 4 });
+        PriorityDialog {
+            target: Target::Pid(pid),
+            pid,
+            name,
+            nice,
+            io_class: io.class,
+            io_level: io.level,
+            results: Vec::new(),
+        }
+    }
+}
 
 // ---------------------------------------------------------------------------------
 
 // used to determine sort style
-#[derive(PartialEq)] //this is an attribute it can be derived from so that it allows comparisons (If sort_crit==mem)
+#[derive(PartialEq, Clone, Copy, Debug, serde::Serialize, serde::Deserialize)] //this is an attribute it can be derived from so that it allows comparisons (If sort_crit==mem)
+#[serde(rename_all = "lowercase")] // written as "memory" / "cpu" in the config file
 enum SortCriteria {
     Memory,
     CPU,
+    IO, // disk read + write rate
+}
+
+// Biggest first for the chosen criterion, ties by pid; `ascending` flips the whole order
+fn sort_processes(processes: &mut [Aggregated], criteria: SortCriteria, ascending: bool) {
+    processes.sort_by(|a, b| {
+        let primary = match criteria {
+            SortCriteria::Memory => b.memory.cmp(&a.memory),
+            SortCriteria::CPU => b.cpu.partial_cmp(&a.cpu).unwrap_or(std::cmp::Ordering::Equal), // Descending order
+            SortCriteria::IO => b.io_rate().partial_cmp(&a.io_rate()).unwrap_or(std::cmp::Ordering::Equal),
+        };
+        //check if same value then sort by pid
+        primary.then(a.pid.cmp(&b.pid))
+    });
+    if ascending {
+        processes.reverse();
+    }
+}
+
+// Replay bar: time of the shown snapshot, step / play buttons and a slider over the recording.
+// Returns true when the user moved to another snapshot.
+fn replay_controls(ui: &mut egui::Ui, replay: &mut Replay) -> bool {
+    let before = replay.position;
+    ui.horizontal(|ui| {
+        ui.label(
+            egui::RichText::new(format!(
+                "Replay {} UTC (+{:.0}s)  {}/{}",
+                snapshot::format_time_of_day(replay.current().timestamp_ms),
+                replay.offset_seconds(),
+                replay.position + 1,
+                replay.snapshots.len()
+            ))
+            .color(egui::Color32::YELLOW)
+            .size(18.0),
+        );
+        if ui.button("|<").clicked() {
+            replay.step(-(replay.snapshots.len() as isize));
+        }
+        if ui.button("<").clicked() {
+            replay.step(-1);
+        }
+        if ui.button(if replay.playing { "Pause" } else { "Play" }).clicked() {
+            replay.playing = !replay.playing;
+            replay.step(0); // restart the timer for the next step
+        }
+        if ui.button(">").clicked() {
+            replay.step(1);
+        }
+        if ui.button(">|").clicked() {
+            replay.step(replay.snapshots.len() as isize);
+        }
+        let mut position = replay.position;
+        let last = replay.snapshots.len() - 1;
+        if ui.add(egui::Slider::new(&mut position, 0..=last).show_value(false)).changed() {
+            replay.step(position as isize - replay.position as isize);
+        }
+    });
+    // keep repainting so playback moves on by itself
+    if let Some(wait) = replay.until_next() {
+        ui.ctx().request_repaint_after(wait);
+    }
+    replay.position != before
+}
+
+// Summary above the process list: uptime and load, task counts, memory and swap, one bar per core
+fn show_overview(ui: &mut egui::Ui, snapshot: &Snapshot, unit: MemoryUnit) {
+    let overview = &snapshot.overview;
+    let counts = snapshot.status_counts();
+    let format = |bytes: u64| units::format_bytes(bytes, unit);
+    let text = |text: String| egui::RichText::new(text).color(egui::Color32::LIGHT_GRAY).size(16.0);
+
+    ui.label(text(format!(
+        "Up {}   Load average: {:.2} {:.2} {:.2}   Tasks: {} total, {} running, {} sleeping, {} stopped",
+        snapshot::format_uptime(overview.uptime_secs),
+        overview.load_average[0],
+        overview.load_average[1],
+        overview.load_average[2],
+        counts.total,
+        counts.running,
+        counts.sleeping,
+        counts.stopped
+    )));
+    ui.horizontal(|ui| {
+        ui.label(text("Mem".to_string()));
+        let used = units::percent_of(overview.used_memory, snapshot.total_memory) / 100.0;
+        ui.add(
+            egui::ProgressBar::new(used)
+                .desired_width(200.0)
+                .text(format!("{} / {}", format(overview.used_memory), format(snapshot.total_memory))),
+        );
+        ui.label(text(format!(
+            "{} available, {} cached",
+            format(overview.available_memory),
+            format(overview.cached_memory)
+        )));
+        ui.add_space(20.0);
+        ui.label(text("Swap".to_string()));
+        let used = units::percent_of(overview.used_swap, overview.total_swap) / 100.0;
+        ui.add(
+            egui::ProgressBar::new(used)
+                .desired_width(200.0)
+                .text(format!("{} / {}", format(overview.used_swap), format(overview.total_swap))),
+        );
+    });
+    // one small bar per core, wrapping to as many rows as needed
+    egui::Grid::new("cores_grid").show(ui, |ui| {
+        for (i, usage) in overview.core_usage.iter().enumerate() {
+            ui.label(text(format!("CPU{}", i)));
+            ui.add(
+                egui::ProgressBar::new(usage.clamp(0.0, 100.0) / 100.0)
+                    .desired_width(120.0)
+                    .text(format!("{:.1}%", usage)),
+            );
+            if i % 4 == 3 {
+                ui.end_row();
+            }
+        }
+    });
+    ui.add_space(10.0);
 }
 
-fn get_total_memory_mb(system: &sysinfo::System) -> f32 {
-    system.total_memory() as f32 / 1024.0 // Convert from KB to MB
+// Process table layout, in points
+const HEADER_HEIGHT: f32 = 26.0;
+const ROW_HEIGHT: f32 = 22.0;
+const COLUMN_GAP: f32 = 10.0; // between columns, in the header it is the resize handle
+
+// Rows of the process table and what they were built from
+#[derive(PartialEq)]
+struct TableKey {
+    generation: u64, // ProcessDisplay::snapshot_generation
+    filter: ProcessFilter,
+    group_by: GroupBy,
+    sort: (SortCriteria, bool),
+    columns: Vec<Column>,
+    units: (MemoryUnit, CpuMode),
+}
+
+struct TableRows {
+    key: TableKey,
+    rows: Vec<Aggregated>,
+    fitted: Vec<f32>, // width of every column fitted to its content, see fit_column_widths
+}
+
+// Width of every column fitted to its header and cells (up to max_chars). Only the cell with the most
+// characters is measured, laying out thousands of rows per refresh would cost more than the table itself.
+fn fit_column_widths(ui: &egui::Ui, columns: &[Column], rows: &[Aggregated], cx: &CellContext) -> Vec<f32> {
+    let measure = |text: String, size: f32| {
+        ui.fonts(|fonts| fonts.layout_no_wrap(text, egui::FontId::proportional(size), egui::Color32::WHITE).size().x)
+    };
+    columns
+        .iter()
+        .map(|column| {
+            // a digit is about as wide as an average character, good enough for the cap
+            let cap = measure("0".repeat(column.max_chars()), 15.0);
+            let longest = rows.iter().map(|row| column.text(row, cx)).max_by_key(|text| text.chars().count()).unwrap_or_default();
+            measure(column.header(cx), 18.0).max(measure(longest, 15.0).min(cap)).ceil()
+        })
+        .collect()
+}
+
+// The text cut in the middle so it fits in `width` points, and whether it had to be cut
+fn fit_text(ui: &egui::Ui, text: &str, width: f32, size: f32) -> (String, bool) {
+    let measure = |text: &str| ui.fonts(|fonts| fonts.layout_no_wrap(text.to_string(), egui::FontId::proportional(size), egui::Color32::WHITE).size().x);
+    let full = measure(text);
+    if full <= width {
+        return (text.to_string(), false);
+    }
+    // scale the character count down by how much too wide it is, the painter clips whatever is left over
+    let chars = (text.chars().count() as f32 * width / full) as usize;
+    (snapshot::truncate_middle(text, chars.saturating_sub(1)), true)
+}
+
+// Text color of a table cell
+fn cell_color(column: Column, row: &Aggregated, config: &Config, snapshot: &Snapshot) -> egui::Color32 {
+    let gray = egui::Color32::from_gray(128);
+    let orange = egui::Color32::from_rgb(255, 165, 0);
+    match column {
+        // color by share of total memory, both in bytes
+        Column::Memory => usage_color(units::percent_of(row.memory, snapshot.total_memory), &config.colors.memory_breakpoints),
+        Column::Cpu => usage_color(config.display.cpu_mode.scale(row.cpu, snapshot.cpus), &config.colors.cpu_breakpoints),
+        // white while the process is actually reading or writing
+        Column::Io | Column::Read | Column::Write if row.io_rate() > 0.0 => egui::Color32::WHITE,
+        Column::Io | Column::Read | Column::Write => gray,
+        // nicer than default in gray, greedier than default in orange
+        Column::Nice => match row.nice {
+            Some(n) if n < 0 => orange,
+            Some(n) if n > 0 => gray,
+            _ => egui::Color32::WHITE,
+        },
+        // pinned processes stand out in yellow
+        Column::Affinity if row.affinity.is_some() => egui::Color32::YELLOW,
+        Column::Affinity => gray,
+        Column::Status if row.status.as_deref() == Some("Run") => egui::Color32::GREEN,
+        Column::Status => gray,
+        // setuid programs run with someone else's permissions, worth spotting
+        Column::Uid => match row.uids.split_once('/') {
+            Some((real, effective)) if real != effective => orange,
+            _ => gray,
+        },
+        Column::Container | Column::Unit => egui::Color32::LIGHT_BLUE,
+        Column::Cgroup | Column::Command | Column::Exe => egui::Color32::LIGHT_GRAY,
+        _ => egui::Color32::WHITE,
+    }
+}
+
+// pick a text color for a usage percentage using the configured breakpoints
+fn usage_color(percent: f32, breakpoints: &[f32; 4]) -> egui::Color32 {
+    if percent < breakpoints[0] {
+        egui::Color32::from_gray(128) // gray if below the first breakpoint (5% by default)
+    } else if percent < breakpoints[1] {
+        egui::Color32::GREEN
+    } else if percent < breakpoints[2] {
+        egui::Color32::YELLOW
+    } else if percent < breakpoints[3] {
+        egui::Color32::from_rgb(255, 165, 0) // this is orange because it isn't predefined like the others
+    } else {
+        egui::Color32::RED
+    }
 }
 
 impl eframe::App for TreeView {
 
-    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
-        self.system.refresh_all(); // Refresh system info
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let window = &frame.info().window_info;
+        let in_background = self.low_impact && (window.minimized || !window.focused);
+        match self.replay.as_mut() {
+            Some(replay) => {
+                if replay.advance() {
+                    self.snapshot = replay.current().clone();
+                }
+            }
+            None => {
+                // the sampler wakes us up when it has a new snapshot, nothing to refresh here
+                let refresh_interval = self.refresh_interval;
+                let sampler = self.sampler.get_or_insert_with(|| {
+                    let ctx = ctx.clone();
+                    let sampler = Sampler::start(refresh_interval, move || ctx.request_repaint());
+                    // the tree only shows PIDs, names and parents
+                    sampler.set_plan(RefreshPlan::for_view(&[Column::Pid, Column::Name], GroupBy::Name, &ProcessFilter::default(), false, false));
+                    sampler
+                });
+                sampler.set_paused(in_background);
+                if let Some(snapshot) = sampler.latest() {
+                    self.snapshot = snapshot;
+                }
+            }
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.allocate_space(egui::vec2(0.0, 20.0));
@@ -129,14 +1395,23 @@ impl eframe::App for TreeView {
     
             ui.allocate_space(egui::vec2(0.0, 20.0));
 
+            if let Some(replay) = self.replay.as_mut() {
+                if replay_controls(ui, replay) {
+                    self.snapshot = replay.current().clone();
+                }
+                ui.allocate_space(egui::vec2(0.0, 20.0));
+            }
+
             //HashMap to store parent-child relationships
             let mut tree_map: HashMap<u32, Vec<u32>> = HashMap::new();
-            for process in self.system.processes().values() {
-                let parent_pid = process.parent().map_or(0, |p| p.as_u32());
+            let mut processes: HashMap<u32, &ProcessRecord> = HashMap::new();
+            for process in &self.snapshot.processes {
+                let parent_pid = process.parent.unwrap_or(0);
                 tree_map
                     .entry(parent_pid)
                     .or_default()
-                    .push(process.pid().as_u32());
+                    .push(process.pid);
+                processes.insert(process.pid, process);
             }
             egui::ScrollArea::vertical().show(ui, |ui| {
                 //this is a utlity function that changes process color based on its depth
@@ -159,13 +1434,13 @@ impl eframe::App for TreeView {
                 fn show_tree(
                     ui: &mut egui::Ui,
                     tree_map: &HashMap<u32, Vec<u32>>,
-                    system: &sysinfo::System,
+                    processes: &HashMap<u32, &ProcessRecord>,
                     pid: u32,
                     depth: usize,
                 ) {
                     if let Some(children) = tree_map.get(&pid) {
                         for &child_pid in children {
-                            if let Some(child) = system.process(sysinfo::Pid::from_u32(child_pid)) {
+                            if let Some(child) = processes.get(&child_pid) {
                                 ui.horizontal(|ui| {
                                     ui.add_space(depth as f32 * 60.0); //increase the value to increase space between parent and child
                                     
@@ -179,7 +1454,7 @@ impl eframe::App for TreeView {
                                         "{} PID: {} - Name: {}",
                                         label_text,
                                         child_pid,
-                                        child.name().to_string_lossy()
+                                        child.name
                                     ))
                                     .color(get_color_for_depth(depth))
                                     .size(15.0);
@@ -190,14 +1465,14 @@ impl eframe::App for TreeView {
                                 // space between elements vertically
                                 ui.add_space(7.0);
                 
-                                show_tree(ui, tree_map, system, child_pid, depth + 1);
+                                show_tree(ui, tree_map, processes, child_pid, depth + 1);
                             }
                         }
                     }
                 }
                 ui.horizontal(|ui| {
                     ui.vertical(|ui| {
-                        show_tree(ui, &tree_map, &self.system, 0, 0);
+                        show_tree(ui, &tree_map, &processes, 0, 0);
                     });
                     ui.add_space(50.0); //this adds horizental space between most depth child and scroll bar
                 });
@@ -207,35 +1482,53 @@ impl eframe::App for TreeView {
 }
 
 impl CheckAlerts {
-    // Create a new instance of CheckAlerts with CPU and memory thresholds
-    fn new(cpu_threshold: f32, memory_threshold: u64) -> Self {
-        let mut system = System::new_all();
-        system.refresh_all();
+    // Create a new instance of CheckAlerts with the thresholds from the config
+    fn new(config: &Config) -> Self {
         CheckAlerts {
-            system,
             alert_message: None,
-            cpu_threshold,
-            memory_threshold,
+            cpu_threshold: config.alerts.cpu_threshold,
+            memory_threshold: config.memory_threshold_bytes(),
+            io_threshold: config.io_threshold_bytes_per_sec(),
+            cpu_mode: config.display.cpu_mode,
         }
     }
 
-    // Method to check if any process exceeds the thresholds
-    fn check_for_alerts(&mut self) -> Option<(u32, String, f32, u64)> {
-        // Refresh system data (e.g., processes, memory, CPU usage)
-        self.system.refresh_all();
+    fn io_over(&self, io_rate: f64) -> bool {
+        self.io_threshold > 0.0 && io_rate > self.io_threshold
+    }
+
+    // Critical when a process is over more than one limit, otherwise just a warning
+    fn severity(&self, cpu_usage: f32, memory_usage: u64, io_rate: f64) -> Severity {
+        let over = [cpu_usage > self.cpu_threshold, memory_usage > self.memory_threshold, self.io_over(io_rate)];
+        if over.iter().filter(|over| **over).count() > 1 {
+            Severity::Critical
+        } else {
+            Severity::Warning
+        }
+    }
 
-        // Check each process's CPU and memory usage
-        for (_, process) in self.system.processes() {
-            let cpu_usage = process.cpu_usage();
-            let memory_usage = process.memory();
+    // Check a snapshot whose disk rates are already computed (see `compute_io_rates`), the same data the
+    // GUI and the CLI display show. The returned CPU usage is already scaled to the configured CPU mode.
+    // PIDs the user clicked Ignore for are skipped, otherwise the popup would come back with every snapshot.
+    fn find_alert(&self, snapshot: &Snapshot, ignored: &HashSet<u32>) -> Option<(u32, String, f32, u64, f64)> {
+        // Check each process's CPU, memory and disk usage
+        for process in &snapshot.processes {
+            // a thread reports its process' memory, the process itself is checked too
+            if process.is_thread || ignored.contains(&process.pid) {
+                continue;
+            }
+            let cpu_usage = self.cpu_mode.scale(process.cpu, snapshot.cpus);
+            let memory_usage = process.memory;
+            let io_rate = process.read_rate + process.write_rate;
 
             // If the process exceeds the threshold, return the process info
-            if cpu_usage > self.cpu_threshold || memory_usage > self.memory_threshold {
+            if cpu_usage > self.cpu_threshold || memory_usage > self.memory_threshold || self.io_over(io_rate) {
                 return Some((
-                    process.pid().as_u32(),
-                    process.name().to_string_lossy().to_string(),
+                    process.pid,
+                    process.name.clone(),
                     cpu_usage,
                     memory_usage,
+                    io_rate,
                 ));
             }
         }
@@ -247,34 +1540,118 @@ impl eframe::App for ProcessDisplay { // this is 3rd time struct is used
 
     // update here is a special function that is called automatically every frame
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        
-        // Check for alerts on every update
-        if let Some((pid, name, cpu, memory)) = self.check_alerts.check_for_alerts() {
-            self.alert_pid = pid;
-            self.alert_name = name;
-            self.alert_cpu = cpu;
-            self.alert_memory = memory;
-            self.show_alert_popup = true; // Show popup when a threshold is exceeded
-        }
+        // Low impact mode: nobody looks at a minimized or unfocused window, so sample it only rarely. Not at all
+        // would also stop the alerts and desktop notifications, which are there for exactly that case.
+        let window = &frame.info().window_info;
+        let in_background = self.config.refresh.low_impact && (window.minimized || !window.focused);
+        let sample_interval = if in_background {
+            self.refresh_interval.max(Duration::from_millis(config::LOW_IMPACT_BACKGROUND_MS))
+        } else {
+            self.refresh_interval
+        };
+
+        if let Some(replay) = self.replay.as_mut() {
+            // Replaying: move through the recording instead of refreshing, alerts are only for live data
+            if replay.advance() {
+                let snapshot = replay.current().clone();
+                self.show_snapshot(snapshot);
+            }
+        } else {
+            // The sampler thread refreshes in the background and wakes us up when it has a new snapshot
+            let refresh_interval = self.refresh_interval;
+            let sampler = self.sampler.get_or_insert_with(|| {
+                let ctx = ctx.clone();
+                Sampler::start(refresh_interval, move || ctx.request_repaint())
+            });
+            sampler.set_interval(sample_interval);
+            if let Some(snapshot) = sampler.latest() {
+                self.show_snapshot(snapshot);
+                if self.connections_open {
+                    self.refresh_connections();
+                }
 
-        let now = Instant::now();
+                // live data also while paused
+                let newest = self.live.as_ref().unwrap_or(&self.snapshot);
+                if let Some(recorder) = self.recorder.as_mut() {
+                    if let Err(e) = recorder.maybe_record(newest) {
+                        eprintln!("History recording stopped: {}", e);
+                        self.recorder = None;
+                    }
+                }
 
-        // Refresh system info only if 0.1 seconds have passed
-        if now.duration_since(self.last_update) >= self.refresh_interval {
-            self.system.refresh_all();
-            self.last_update = now;
+                // Check for alerts with every new snapshot
+                let no_ignored = HashSet::new();
+                let ignored = self.notifier.as_ref().map_or(&no_ignored, DesktopNotifier::ignored);
+                if let Some((pid, name, cpu, memory, io_rate)) = self.check_alerts.find_alert(newest, ignored) {
+                    self.alert_pid = pid;
+                    self.alert_name = name;
+                    self.alert_cpu = cpu;
+                    self.alert_memory = memory;
+                    self.alert_io = io_rate;
+                    self.show_alert_popup = true; // Show popup when a threshold is exceeded
+
+                    // Also send a desktop notification so the alert is seen when the window is minimized
+                    if let Some(notifier) = self.notifier.as_mut() {
+                        let severity = self.check_alerts.severity(cpu, memory, io_rate);
+                        if let Err(e) = notifier.notify_alert(pid, &self.alert_name, cpu, memory, io_rate, severity) {
+                            eprintln!("Failed to send desktop notification: {}", e);
+                        }
+                    }
+                }
+            }
+
+            // Handle Kill / Ignore buttons clicked on desktop notifications, seen at the latest with the next snapshot
+            if let Some(notifier) = self.notifier.as_mut() {
+                for action in notifier.poll_actions() {
+                    match action {
+                        NotificationAction::Kill(pid) => {
+                            self.action_message = Some(outcome_message(kill_process(pid)));
+                            self.show_alert_popup = false;
+                        }
+                        NotificationAction::Ignore(_) => {
+                            self.show_alert_popup = false;
+                        }
+                    }
+                }
+            }
         }
 
-        // Request a repaint
-        ctx.request_repaint();
+        // No repaint request here: the sampler, the replay controls and user input each ask for one when needed
+
+        // Status bar: what the task manager itself costs
+        self.self_usage.update();
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Task manager: {:.1}% CPU, {} RSS",
+                    self.self_usage.cpu_percent,
+                    units::format_bytes(self.self_usage.rss, self.config.display.memory_unit)
+                ));
+                if let Some(cost) = self.sampler.as_ref().and_then(Sampler::cost) {
+                    ui.separator();
+                    ui.label(format!("sampling {:.1} ms every {} ms", cost.elapsed.as_secs_f64() * 1000.0, self.refresh_interval.as_millis()));
+                }
+                if self.paused {
+                    ui.separator();
+                    ui.label(egui::RichText::new("Paused, the table shows the frozen snapshot").color(egui::Color32::YELLOW));
+                }
+                if self.config.refresh.low_impact {
+                    ui.separator();
+                    let window = &frame.info().window_info;
+                    let text = if window.minimized || !window.focused {
+                        format!("Low impact, sampling every {} s in the background", config::LOW_IMPACT_BACKGROUND_MS / 1000)
+                    } else {
+                        "Low impact".to_string()
+                    };
+                    ui.label(egui::RichText::new(text).weak());
+                }
+            });
+        });
 
         egui::CentralPanel::default().show(ctx, |ui| {
             
             // Alert message popup
             if self.show_alert_popup {
-                // let total_memory = get_total_memory_mb(&self.system) * 1024.0 * 1024.0;
-                let num_cores = self.system.cpus().len() as f32;
-
                 egui::Window::new(egui::RichText::new("High Resource Usage Alert")
                     .size(20.0)
                     .color(egui::Color32::RED)
@@ -285,11 +1662,13 @@ impl eframe::App for ProcessDisplay { // this is 3rd time struct is used
                         ui.add_space(10.0);
                         ui.label(
                             egui::RichText::new(format!(
-                                "PID: {}\nName: {}\nCPU Usage: {:.2}%\nMemory Usage: {} MB",
+                                "PID: {}\nName: {}\nCPU Usage: {:.2}% {}\nMemory Usage: {}\nDisk I/O: {}/s",
                                 self.alert_pid,
                                 self.alert_name,
-                                self.alert_cpu / num_cores,
-                                self.alert_memory / 1024 / 1024 // Convert memory from bytes to MB
+                                self.alert_cpu, // already in the configured CPU mode
+                                self.config.display.cpu_mode.label(),
+                                units::format_bytes(self.alert_memory, self.config.display.memory_unit),
+                                units::format_bytes(self.alert_io as u64, self.config.display.memory_unit)
                             ))
                             .size(16.0)
                             .color(egui::Color32::LIGHT_RED),
@@ -315,6 +1694,59 @@ impl eframe::App for ProcessDisplay { // this is 3rd time struct is used
                     });
             }
 
+            if self.settings_open {
+                self.show_settings(ctx);
+            }
+
+            if self.leaks_open {
+                self.show_leaks(ctx);
+            }
+
+            if self.connections_open {
+                self.show_connections(ctx);
+            }
+
+            if self.inspector.is_some() {
+                self.show_inspector(ctx);
+            }
+
+            if self.priority_dialog.is_some() {
+                self.show_priority(ctx);
+            }
+
+            if self.compare_open {
+                self.show_compare(ctx);
+            }
+
+            if self.restart_confirm.is_some() {
+                self.show_restart_confirm(ctx);
+            }
+            if let Some((unit, result)) = &self.restarting {
+                match result.try_recv() {
+                    Ok(result) => {
+                        self.action_message = Some(match result {
+                            Ok(()) => (false, format!("Restarted {}", unit.label())),
+                            Err(e) => (true, e),
+                        });
+                        self.restarting = None;
+                    }
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Disconnected) => self.restarting = None,
+                }
+            }
+
+            let mut moved_to = None;
+            if let Some(replay) = self.replay.as_mut() {
+                if replay_controls(ui, replay) {
+                    moved_to = Some(replay.current().clone());
+                }
+            }
+            if let Some(snapshot) = moved_to {
+                self.show_snapshot(snapshot);
+            }
+
+            show_overview(ui, &self.snapshot, self.config.display.memory_unit);
+
             //some vertical space
             ui.allocate_space(egui::vec2(0.0, 20.0));
             ui.end_row();
@@ -337,314 +1769,884 @@ impl eframe::App for ProcessDisplay { // this is 3rd time struct is used
                 match self.sort_criteria {
                     SortCriteria::Memory => "Memory",
                     SortCriteria::CPU => "CPU",
+                    SortCriteria::IO => "Disk I/O",
                 },
                 if self.reverse_sort { "ASC" } else { "DESC" }
             );            
-            ui.label(
-                egui::RichText::new(sorting_text)
-                    .color(egui::Color32::LIGHT_BLUE)
-                    .size(20.0),
-            );
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(sorting_text)
+                        .color(egui::Color32::LIGHT_BLUE)
+                        .size(20.0),
+                );
+                ui.add_space(40.0);
+                // opens the settings window with a fresh copy of the current config
+                if ui.button(egui::RichText::new("Settings").size(18.0)).clicked() {
+                    self.settings_draft = self.config.clone();
+                    self.settings_message = None;
+                    self.settings_open = true;
+                }
+                if ui.button(egui::RichText::new("Suspected leaks").size(18.0)).clicked() {
+                    self.leaks_open = !self.leaks_open;
+                }
+                if ui.button(egui::RichText::new("Connections").size(18.0)).clicked() {
+                    self.connections_open = !self.connections_open;
+                    if self.connections_open {
+                        self.refresh_connections();
+                    }
+                }
+                // a replay has its own play / pause
+                if self.replay.is_none() {
+                    let pause_text = if self.paused { "Resume" } else { "Pause" };
+                    if ui.button(egui::RichText::new(pause_text).size(18.0)).on_hover_text("Freeze the table (P)").clicked() {
+                        self.set_paused(!self.paused);
+                    }
+                    if ui.button(egui::RichText::new("Compare").size(18.0)).on_hover_text("Diff the frozen table against live data").clicked() {
+                        // comparing needs something frozen
+                        self.set_paused(true);
+                        self.compare_open = !self.compare_open;
+                    }
+                }
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("Group by").size(18.0));
+                let before = self.group_by;
+                egui::ComboBox::from_id_source("group_by")
+                    .selected_text(self.group_by.label())
+                    .show_ui(ui, |ui| {
+                        for mode in snapshot::GROUP_BY_MODES {
+                            ui.selectable_value(&mut self.group_by, mode, mode.label());
+                        }
+                    });
+                if self.group_by != before {
+                    // regroup the current snapshot right away, also reads the cgroup limits
+                    let snapshot = self.snapshot.clone();
+                    self.put_on_screen(snapshot);
+                }
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("Container").size(18.0));
+                ui.add(egui::TextEdit::singleline(&mut self.container_filter).hint_text("ID prefix or host").desired_width(140.0));
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("Unit").size(18.0));
+                ui.add(egui::TextEdit::singleline(&mut self.unit_filter).hint_text("e.g. nginx.service").desired_width(140.0));
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("User").size(18.0));
+                ui.add_enabled(
+                    !self.mine_only,
+                    egui::TextEdit::singleline(&mut self.user_filter).hint_text("name or UID").desired_width(100.0),
+                );
+                ui.checkbox(&mut self.mine_only, egui::RichText::new("Mine only").size(18.0));
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("Search").size(18.0));
+                ui.add(egui::TextEdit::singleline(&mut self.command_filter).hint_text("command line").desired_width(180.0));
+            });
+            if let Some((is_error, text)) = &self.action_message {
+                let color = if *is_error { egui::Color32::LIGHT_RED } else { egui::Color32::GREEN };
+                ui.label(egui::RichText::new(text).color(color).size(16.0));
+            }
             //some vertical space
             ui.allocate_space(egui::vec2(0.0, 40.0));
             ui.end_row();
 
-            egui::Grid::new("header_grid").show(ui, |ui| {
-                ui.label(//this creates a UI label with text PID, color white, and size 18
-                    egui::RichText::new("PID")
-                        .color(egui::Color32::WHITE)
-                        .size(18.0)
-                );
-                ui.allocate_space(egui::vec2(20.0, 0.0));//this creates a space, as 2d vector where
-                //20 is the horizontal value and 0 is the vertical one it creates only hroizontal space
-                ui.label(
-                    egui::RichText::new("Name")
-                        .color(egui::Color32::WHITE)
-                        .size(18.0),
-                );
-                ui.allocate_space(egui::vec2(110.0, 0.0));
-
-                // create a button, same setup as label but it has clicked event which decides what happens 
-                // once it is clicked, here we change ProcessDisplay struct reverse_sort and sort criteria 
-                if ui.button(
-                    egui::RichText::new("Memory (MB)")
-                        .color(egui::Color32::WHITE)
-                        .size(18.0),).clicked() {
-                    if let SortCriteria::Memory = self.sort_criteria {
-                        self.reverse_sort = !self.reverse_sort; // Reverse order
+            // P freezes / unfreezes the live table
+            if self.replay.is_none() && !ctx.wants_keyboard_input() && ctx.input(|input| input.key_pressed(egui::Key::P)) {
+                self.set_paused(!self.paused);
+            }
+
+            // Collect processes aggregated by name (or cgroup, ...), then sort them by the chosen column.
+            // Only redone when the snapshot or what is asked of it changes, not every frame.
+            let key = TableKey {
+                generation: self.snapshot_generation,
+                filter: ProcessFilter {
+                    status: None,
+                    container: Some(self.container_filter.trim().to_lowercase()).filter(|prefix| !prefix.is_empty()),
+                    unit: Some(self.unit_filter.trim().to_string()).filter(|unit| !unit.is_empty()),
+                    user: if self.mine_only {
+                        Some(nix::unistd::getuid().as_raw().to_string())
+                    } else {
+                        Some(self.user_filter.trim().to_string()).filter(|user| !user.is_empty())
+                    },
+                    command: Some(self.command_filter.trim().to_string()).filter(|text| !text.is_empty()),
+                },
+                group_by: self.group_by,
+                sort: (self.sort_criteria, self.reverse_sort),
+                columns: self.config.table_columns(),
+                units: (self.config.display.memory_unit, self.config.display.cpu_mode),
+            };
+            let cx = CellContext {
+                group_by: self.group_by,
+                memory_unit: self.config.display.memory_unit,
+                cpu_mode: self.config.display.cpu_mode,
+                cpus: self.snapshot.cpus,
+                cgroup_limits: &self.cgroup_limits,
+            };
+            if self.table.as_ref().is_none_or(|table| table.key != key) {
+                let mut rows = self.snapshot.aggregate(&key.filter, self.group_by);
+                sort_processes(&mut rows, self.sort_criteria, self.reverse_sort);
+                let fitted = fit_column_widths(ui, &key.columns, &rows, &cx);
+                self.table = Some(TableRows { key, rows, fitted });
+            }
+            let table = self.table.as_ref().expect("table rows were just built");
+            let rows = &table.rows;
+            let columns = &table.key.columns;
+            // widths dragged by the user win over the fitted ones
+            let widths: Vec<f32> = columns
+                .iter()
+                .zip(&table.fitted)
+                .map(|(column, fitted)| *self.config.display.column_widths.get(column.key()).unwrap_or(fitted))
+                .collect();
+
+            // keyboard: arrows / page / home / end move the selection, Enter inspects it (not while typing in a filter)
+            let mut selected = self.selected.as_ref().and_then(|name| rows.iter().position(|row| &row.name == name));
+            let mut scroll_to_selection = false;
+            if !ctx.wants_keyboard_input() && !rows.is_empty() {
+                let page = ((self.table_viewport.1 / self.table_viewport.2) as isize - 1).max(1);
+                let step = ctx.input(|input| {
+                    if input.key_pressed(egui::Key::ArrowDown) {
+                        Some(1)
+                    } else if input.key_pressed(egui::Key::ArrowUp) {
+                        Some(-1)
+                    } else if input.key_pressed(egui::Key::PageDown) {
+                        Some(page)
+                    } else if input.key_pressed(egui::Key::PageUp) {
+                        Some(-page)
+                    } else if input.key_pressed(egui::Key::Home) {
+                        Some(isize::MIN / 2)
+                    } else if input.key_pressed(egui::Key::End) {
+                        Some(isize::MAX / 2)
                     } else {
-                        self.sort_criteria = SortCriteria::Memory;
-                        self.reverse_sort = false; // Reset order
+                        None
                     }
+                });
+                if let Some(step) = step {
+                    let from = selected.map_or(-1, |i| i as isize);
+                    selected = Some(from.saturating_add(step).clamp(0, rows.len() as isize - 1) as usize);
+                    scroll_to_selection = true;
                 }
-                ui.allocate_space(egui::vec2(20.0, 0.0));
-                if ui.button( 
-                    egui::RichText::new("CPU Usage (%)")
-                .color(egui::Color32::WHITE)
-                .size(18.0),).clicked()
-                {
-                    if let SortCriteria::CPU = self.sort_criteria {
-                        self.reverse_sort = !self.reverse_sort;
-                    } else {
-                        self.sort_criteria = SortCriteria::CPU;
-                        self.reverse_sort = false;
+            }
+
+            let mut inspect = None;
+            let mut change_priority = None;
+            let mut restart_unit = None;
+            let restart_units = self.config.actions.restart_units;
+            let mut save_widths = false;
+            let mut resized = Vec::new();
+            let mut sort_by = None;
+            let mut clicked_row = None;
+            if let Some(row) = selected.map(|i| &rows[i]) {
+                if !ctx.wants_keyboard_input() && ctx.input(|input| input.key_pressed(egui::Key::Enter)) {
+                    if let Some(pid) = row.pid {
+                        inspect = Some((pid, row.name.clone()));
                     }
                 }
-                ui.allocate_space(egui::vec2(20.0, 0.0));
-                ui.label(
-                    egui::RichText::new("Status")
-                        .color(egui::Color32::WHITE)
-                        .size(18.0),
-                );
-                ui.allocate_space(egui::vec2(20.0, 0.0));
-                ui.end_row();//this creates a new row
-                ui.allocate_space(egui::vec2(0.0, 20.0));
-                ui.end_row();
-            });
-            
-            // Create a scrollable area for displaying processes
-            egui::ScrollArea::vertical().show(ui, |ui| { // Use `vertical()` for vertical scrolling
-                // Create a table layout to show processes
-                egui::Grid::new("process_grid").show(ui, |ui| {
-
-                    // Collect and sort processes by memory
-                    let mut aggregated_processes: HashMap<String, (u64, f32, Option<u32>, Option<ProcessStatus>)> = HashMap::new();
-                    for process in self.system.processes().values() {
-                        if process.memory() > 0 {
-                            let entry = aggregated_processes
-                                .entry(process.name().to_string_lossy().to_string())
-                                .or_insert((0, 0.0, None, None));
-                            entry.0 += process.memory(); // Sum memory usage
-                            entry.1 += process.cpu_usage(); // Sum CPU usage
-                    
-                            if entry.2.is_none() {
-                                entry.2 = Some(process.pid().as_u32());
+            }
+            // wide layouts scroll sideways, header and rows together; the header stays put when scrolling down
+            egui::ScrollArea::horizontal().show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 0.0;
+                    for (column, width) in columns.iter().zip(&widths) {
+                        let sort = match column {
+                            Column::Memory => Some(SortCriteria::Memory),
+                            Column::Cpu => Some(SortCriteria::CPU),
+                            Column::Io | Column::Read | Column::Write => Some(SortCriteria::IO),
+                            _ => None,
+                        };
+                        let header = egui::RichText::new(column.header(&cx)).color(egui::Color32::WHITE).size(18.0);
+                        match sort {
+                            // clicking a sortable header sorts by it, clicking again flips the order
+                            Some(criteria) => {
+                                if ui.add_sized([*width, HEADER_HEIGHT], egui::Button::new(header)).clicked() {
+                                    sort_by = Some(criteria);
+                                }
                             }
-                    
-                            if entry.3.is_none() {
-                                entry.3 = Some(process.status());
+                            None => {
+                                ui.add_sized([*width, HEADER_HEIGHT], egui::Label::new(header).truncate(true));
                             }
                         }
-                    }
-                    
-                    // Sort the aggregated data by memory
-                    let mut sorted_processes: Vec<_> = aggregated_processes.into_iter().collect();
-                    match self.sort_criteria {
-                        SortCriteria::Memory => {
-                            sorted_processes.sort_by(|a, b| {
-                                let primary = b.1 .0.cmp(&a.1 .0);
-                                //check if same memory then sort by pid
-                                if primary == std::cmp::Ordering::Equal {
-                                    a.1 .2.cmp(&b.1 .2)
-                                } else {
-                                    primary
-                                }
-                            });
+                        // the gap after each header is its resize handle, a double click fits it to the content again
+                        let (handle, drag) = ui.allocate_exact_size(egui::vec2(COLUMN_GAP, HEADER_HEIGHT), egui::Sense::click_and_drag());
+                        let drag = drag.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+                        ui.painter().vline(handle.center().x, handle.y_range(), egui::Stroke::new(1.0, egui::Color32::from_gray(80)));
+                        if drag.dragged() {
+                            resized.push((*column, Some((*width + drag.drag_delta().x).max(20.0))));
                         }
-                        SortCriteria::CPU => {
-                            sorted_processes.sort_by(|a, b| {
-                                let primary = b.1 .1.partial_cmp(&a.1 .1).unwrap_or(std::cmp::Ordering::Equal); // Descending order
-                                //check if same memory then sort by pid
-                                if primary == std::cmp::Ordering::Equal {
-                                    a.1 .2.cmp(&b.1 .2)
-                                } else {
-                                    primary
-                                }
-                            });
+                        if drag.double_clicked() {
+                            resized.push((*column, None));
+                        }
+                        if drag.drag_released() || drag.double_clicked() {
+                            save_widths = true;
                         }
                     }
-                    
-
-                    if self.reverse_sort {
-                        sorted_processes.reverse();
+                });
+                ui.add_space(10.0);
+
+                // Only the rows in view are laid out, the rest is just scroll height
+                let row_pitch = ROW_HEIGHT + ui.spacing().item_spacing.y;
+                let mut scroll = egui::ScrollArea::vertical().auto_shrink([false, false]);
+                if let Some(i) = selected.filter(|_| scroll_to_selection) {
+                    let (offset, height, _) = self.table_viewport;
+                    let top = i as f32 * row_pitch;
+                    if top < offset {
+                        scroll = scroll.vertical_scroll_offset(top);
+                    } else if top + row_pitch > offset + height {
+                        scroll = scroll.vertical_scroll_offset(top + row_pitch - height);
                     }
-                    let total_memory = get_total_memory_mb(&self.system) * 1024.0 * 1024.0;
-                    let num_cores = self.system.cpus().len() as f32;
-                    
-                    // Display sorted processes in the table by looping over them one by one
-                    for (name, (memory, cpu, pid, status)) in sorted_processes {
-                        let normalized_cpu = cpu / num_cores;
-                        ui.label(
-                            egui::RichText::new(pid.map_or("Unknown".to_string(), |v| v.to_string()))
-                                    .color(egui::Color32::WHITE)
-                                    .size(15.0),
-                            );//here it creates a label and displays pid in it
-                        ui.allocate_space(egui::vec2(20.0, 0.0));//horizental space to match headers
-                        ui.label(
-                            egui::RichText::new(name)
-                                    .color(egui::Color32::WHITE)
-                                    .size(15.0),
-                            );
-                        ui.allocate_space(egui::vec2(30.0, 0.0));
-                        let temp = memory as f32;
-                        let memory_bytes = temp * 1024.0 as f32;
-                        let memory_color = if memory_bytes < total_memory * 0.05 {
-                            egui::Color32::from_gray(128)
-                        } else if memory_bytes < total_memory * 0.20 {
-                            egui::Color32::GREEN
-                        } else if memory_bytes < total_memory * 0.50 {
-                            egui::Color32::YELLOW
-                        } else if memory_bytes < total_memory * 0.75 {
-                            egui::Color32::from_rgb(255, 165, 0)
-                        } else {
-                            egui::Color32::RED
-                        };
-                        ui.label(                          
-                            egui::RichText::new((memory / (1024 * 1024)).to_string())
-                                 .color(memory_color)
-                                    .size(15.0),
-                            );
-                        ui.allocate_space(egui::vec2(110.0, 0.0));
-                        let rounded_cpu = format!("{:.2}%", normalized_cpu); // here we set cpu text color based on cpu value
-                            let cpu_color = if normalized_cpu < 5.0 {
-                                egui::Color32::from_gray(128) // gray if less than 5%
-                            } else if normalized_cpu < 30.0 {
-                                egui::Color32::GREEN // green if less than 30%
-                            } else if normalized_cpu < 60.0 {
-                                egui::Color32::YELLOW // yellow if less thann 60% etc...
-                            } else if normalized_cpu < 80.0 {
-                                egui::Color32::from_rgb(255, 165, 0) // this is orange because it isn't predefined like the others
-                            } else {
-                                egui::Color32::RED
-                            };
-                            ui.label( // then create the label with the desired color and text
-                                egui::RichText::new(rounded_cpu)
-                                    .color(cpu_color)
-                                    .size(15.0),
-                            );
-                        // Same as cpu
-                        ui.allocate_space(egui::vec2(110.0, 0.0));
-                        let st_color = match status {
-                            Some(ProcessStatus::Run) => {
-                                egui::Color32::GREEN
-                            },
-                            _ => {
-                                egui::Color32::from_gray(128)
+                }
+                let output = scroll.show_rows(ui, ROW_HEIGHT, rows.len(), |ui, visible| {
+                    for index in visible {
+                        let process = &rows[index];
+                        ui.horizontal(|ui| {
+                            ui.spacing_mut().item_spacing.x = 0.0;
+                            let row_rect = egui::Rect::from_min_size(ui.cursor().min, egui::vec2(widths.iter().map(|w| w + COLUMN_GAP).sum(), ROW_HEIGHT));
+                            if selected == Some(index) {
+                                ui.painter().rect_filled(row_rect, 2.0, egui::Color32::from_gray(60));
+                            }
+                            for (i, (column, width)) in columns.iter().zip(&widths).enumerate() {
+                                let text = column.text(process, &cx);
+                                let (rect, response) = ui.allocate_exact_size(egui::vec2(*width + COLUMN_GAP, ROW_HEIGHT), egui::Sense::click());
+                                let (shown, truncated) = fit_text(ui, &text, *width, 15.0);
+                                ui.painter_at(rect).text(
+                                    rect.left_center(),
+                                    egui::Align2::LEFT_CENTER,
+                                    shown,
+                                    egui::FontId::proportional(15.0),
+                                    cell_color(*column, process, &self.config, &self.snapshot),
+                                );
+                                // the full text of cut cells, and where the command line runs from
+                                let response = match column {
+                                    Column::Command => response.on_hover_text(format!("{}\n\nExecutable: {}", text, process.exe.as_deref().unwrap_or("unknown"))),
+                                    _ if truncated => response.on_hover_text(&text),
+                                    _ => response,
+                                };
+                                if response.clicked() || response.secondary_clicked() {
+                                    clicked_row = Some(index);
+                                }
+                                // the first column carries the actions for the row
+                                if i > 0 {
+                                    continue;
+                                }
+                                if let Some(pid) = process.pid {
+                                    let response = response.on_hover_text("Inspect this process, right click for more");
+                                    if response.clicked() {
+                                        inspect = Some((pid, process.name.clone()));
+                                    }
+                                    // right click menu with the actions for this process
+                                    let unit = process.cgroup.as_deref().and_then(systemd::unit_from_cgroup);
+                                    response.context_menu(|ui| {
+                                        if ui.button("Inspect").clicked() {
+                                            inspect = Some((pid, process.name.clone()));
+                                            ui.close_menu();
+                                        }
+                                        if ui.button("Priority (renice / ionice)").clicked() {
+                                            change_priority = Some((pid, process.name.clone()));
+                                            ui.close_menu();
+                                        }
+                                        // only for a known service, scopes can't be restarted
+                                        if let Some(unit) = unit.as_ref().filter(|unit| restart_units && unit.can_restart()) {
+                                            if ui.button(format!("Restart unit {}", unit.label())).clicked() {
+                                                restart_unit = Some(unit.clone());
+                                                ui.close_menu();
+                                            }
+                                        }
+                                    });
+                                }
                             }
-                        };       
-                        // This is because status can have no value so if that is the case we set its value to unknown                                       
-                        ui.label(
-                            egui::RichText::new(status.map_or_else(|| "Unknown".to_string(), |s| format!("{:?}", s)))
-                                .color(st_color)
-                                .size(15.0),
-                        );
-                        ui.allocate_space(egui::vec2(50.0, 0.0));
-                        ui.end_row();
-                        ui.allocate_space(egui::vec2(0.0, 2.0));
-                        ui.end_row();
+                        });
                     }
                 });
+                self.table_viewport = (output.state.offset.y, output.inner_rect.height(), row_pitch);
             });
+            if let Some(index) = clicked_row {
+                selected = Some(index);
+            }
+            self.selected = selected.map(|i| rows[i].name.clone());
+
+            // tell the sampler what the next refresh has to read, see refresh.rs
+            if let Some(sampler) = &self.sampler {
+                let mut plan = if self.recorder.is_some() {
+                    // the history keeps every field, a replay may show other columns
+                    RefreshPlan::everything()
+                } else {
+                    let sort_by_io = self.sort_criteria == SortCriteria::IO;
+                    RefreshPlan::for_view(columns, self.group_by, &table.key.filter, sort_by_io, self.check_alerts.io_threshold > 0.0)
+                };
+                // command lines of the rows on screen and the selected one are read fresh every time
+                if !self.config.refresh.low_impact {
+                    // rows are laid out ROW_HEIGHT plus the item spacing apart
+                    let (offset, height, row_pitch) = self.table_viewport;
+                    let first = (offset / row_pitch) as usize;
+                    let visible = (height / row_pitch).ceil() as usize + 1;
+                    plan.detail_pids = rows.iter().skip(first).take(visible).chain(selected.map(|i| &rows[i])).filter_map(|row| row.pid).collect();
+                }
+                sampler.set_plan(plan);
+            }
+            for (column, width) in resized {
+                match width {
+                    Some(width) => self.config.display.column_widths.insert(column.key().to_string(), width),
+                    None => self.config.display.column_widths.remove(column.key()),
+                };
+            }
+            if let Some(criteria) = sort_by {
+                if self.sort_criteria == criteria {
+                    self.reverse_sort = !self.reverse_sort;
+                } else {
+                    self.sort_criteria = criteria;
+                    self.reverse_sort = false;
+                }
+            }
+            if save_widths {
+                // only the widths go to the file, this run's config may hold one-off flags and env overrides
+                self.file_config.display.column_widths = self.config.display.column_widths.clone();
+                if let Err(e) = self.file_config.save(&self.config_path) {
+                    self.action_message = Some((true, format!("Column widths not saved: {}", e)));
+                }
+            }
+            if let Some((pid, name)) = inspect {
+                self.inspector = Some(Inspector::new(pid, name, self.snapshot.cpus));
+            }
+            if let Some(unit) = restart_unit {
+                match &self.restarting {
+                    Some((busy, _)) => self.action_message = Some((true, format!("Still restarting {}", busy.label()))),
+                    None => self.restart_confirm = Some(unit),
+                }
+            }
+            if let Some((pid, name)) = change_priority {
+                self.priority_dialog = Some(PriorityDialog::new(pid, name, &self.snapshot));
+            }
         });
     }
 }
 
-fn display(system: &mut sysinfo::System)
+// What `display` shows between refreshes
+#[derive(PartialEq, Clone, Copy)]
+enum DisplayMode {
+    Live,
+    Paused,  // the frozen table stays on screen, refreshes still go to alerts and history
+    Compare, // live data diffed against the frozen snapshot
+}
+
+// `frozen` is shared with the prompt, so 'compare' can diff against a table paused here after the view ended
+fn display(system: &mut sysinfo::System, config: &Config, frozen: &mut Option<Snapshot>)
 {
+    // Alerts are sent as desktop notifications since there is no popup in the terminal
+    let check_alerts = CheckAlerts::new(config);
+    let mut notifier = DesktopNotifier::connect().ok();
+    let mut recorder = Recorder::from_config(&config.history);
+    let mut previous: Option<Snapshot> = None;
+    let mut self_usage = SelfUsage::new();
+    // low impact mode shows fewer columns
+    let display_config = DisplayConfig { columns: config.table_columns(), ..config.display.clone() };
+    let mut mode = DisplayMode::Live;
+
     loop {
-        if event::poll(Duration::from_millis(100)).expect("Failed to poll event") {
-            if let event::Event::Key(_) = event::read().expect("Failed to read event") {
-                terminal::disable_raw_mode().expect("Failed to disable raw mode");
-                println!("Process data view ended.");
-                break;
+        // waiting for a key is the pause between refreshes, so a key ends the view right away
+        let wait = if previous.is_some() { config.cli_interval() } else { Duration::ZERO };
+        let mut mode_changed = false;
+        if event::poll(wait).expect("Failed to poll event") {
+            if let event::Event::Key(key) = event::read().expect("Failed to read event") {
+                // p / space freeze the table, c compares the frozen table with live data, anything else ends the view
+                let next = match (key.code, mode) {
+                    (event::KeyCode::Char('p') | event::KeyCode::Char(' '), DisplayMode::Live) => DisplayMode::Paused,
+                    (event::KeyCode::Char('p') | event::KeyCode::Char(' '), _) => DisplayMode::Live,
+                    (event::KeyCode::Char('c'), DisplayMode::Compare) => DisplayMode::Paused,
+                    (event::KeyCode::Char('c'), _) => DisplayMode::Compare,
+                    _ => {
+                        terminal::disable_raw_mode().expect("Failed to disable raw mode");
+                        println!("Process data view ended.");
+                        break;
+                    }
+                };
+                // the table on screen is what gets frozen
+                if mode == DisplayMode::Live {
+                    *frozen = previous.clone();
+                }
+                mode = next;
+                mode_changed = true;
             }
         }
 
-        // Refresh system and process information
-        system.refresh_all();
-
-        // Collect process data and aggregate by name
-        let mut aggregated_processes: HashMap<String, (u64, f32, Option<u32>, Option<ProcessStatus>)> = HashMap::new();
-        for (_, process) in system.processes() {
-            if process.memory() > 0 {
-                let entry = aggregated_processes
-                    .entry(process.name().to_string_lossy().to_string())
-                    .or_insert((0, 0.0, None, None));
-                entry.0 += process.memory();  // Sum memory usage
-                entry.1 += process.cpu_usage();  // Sum CPU usage
+        // Refresh what the table shows (and the disk counters for the I/O alert), see refresh.rs
+        let plan = match &recorder {
+            Some(_) => RefreshPlan::everything(),
+            None => RefreshPlan::for_view(
+                &display_config.columns,
+                display_config.group_by,
+                &display_filter(&display_config, None),
+                config.display.sort == SortCriteria::IO,
+                check_alerts.io_threshold > 0.0,
+            ),
+        };
+        plan.refresh(system);
+        let mut snapshot = Snapshot::capture_planned(system, &plan);
+        if let Some(previous) = &previous {
+            snapshot.compute_io_rates(previous);
+        }
 
-                if entry.2.is_none() {
-                    entry.2 = Some(process.pid().as_u32());
+        if let Some(notifier) = notifier.as_mut() {
+            if let Some((pid, name, cpu, memory, io_rate)) = check_alerts.find_alert(&snapshot, notifier.ignored()) {
+                let severity = check_alerts.severity(cpu, memory, io_rate);
+                let _ = notifier.notify_alert(pid, &name, cpu, memory, io_rate, severity);
+            }
+            for action in notifier.poll_actions() {
+                if let NotificationAction::Kill(pid) = action {
+                    print_outcome(kill_process(pid));
                 }
+            }
+        }
+
+        if let Some(rec) = recorder.as_mut() {
+            if let Err(e) = rec.maybe_record(&snapshot) {
+                eprintln!("History recording stopped: {}", e);
+                recorder = None;
+            }
+        }
 
-                if entry.3.is_none() {
-                    entry.3 = Some(process.status());
+        // a paused table is drawn once, redrawing the same thing would only flicker
+        if mode != DisplayMode::Paused || mode_changed {
+            clearscreen::clear().unwrap();
+            terminal::disable_raw_mode().expect("Failed to re-enter raw mode");
+            match (mode, frozen.as_ref()) {
+                (DisplayMode::Paused, Some(frozen)) => {
+                    print_overview(frozen, config.display.memory_unit);
+                    print_processes(frozen, None, &display_config);
+                }
+                (DisplayMode::Compare, Some(frozen)) => {
+                    print_overview(&snapshot, config.display.memory_unit);
+                    print_diff(frozen, &snapshot, &config.display);
+                }
+                _ => {
+                    print_overview(&snapshot, config.display.memory_unit);
+                    print_processes(&snapshot, None, &display_config);
                 }
             }
+            self_usage.update();
+            println!(
+                "\nTask manager: {:.1}% CPU, {} RSS, refreshing every {} ms{}",
+                self_usage.cpu_percent,
+                units::format_bytes(self_usage.rss, config.display.memory_unit),
+                config.cli_interval().as_millis(),
+                if config.refresh.low_impact { " (low impact)" } else { "" }
+            );
+            let frozen_at = frozen.as_ref().map_or(String::new(), |frozen| snapshot::format_time_of_day(frozen.timestamp_ms));
+            println!(
+                "{}",
+                match mode {
+                    DisplayMode::Live => "p: pause, c: compare with live data, any other key: back to the prompt".to_string(),
+                    DisplayMode::Paused => format!(
+                        "PAUSED at {}. p: resume, c: compare with live data, any other key: back to the prompt (kill, renice, ... work there)",
+                        frozen_at
+                    ),
+                    DisplayMode::Compare => format!("COMPARING with {}. c: back to the frozen table, p: resume, any other key: back to the prompt", frozen_at),
+                }
+            );
+            terminal::enable_raw_mode().expect("Failed to re-enter raw mode");
         }
-        let mut sorted_processes: Vec<_> = aggregated_processes.into_iter().collect();
-        sorted_processes.sort_by(|a, b| b.1 .0.cmp(&a.1 .0)); // Compare the memory usage values
 
+        previous = Some(snapshot);
+    }
+}
 
-        clearscreen::clear().unwrap();
-        terminal::disable_raw_mode().expect("Failed to re-enter raw mode");
-        // Print header
-        println!("{:<10} {:<20} {:<15} {:<15} {:<15}", "PID", "Name", "Memory (MB)", "CPU Usage (%)", "Status");
-
-        // Print aggregated process details
-        for (name, (memory, cpu, pid, status)) in sorted_processes {
-            println! (
-                "{:<10} {:<20} {:<15.2} {:<15.2} {:<15}",
-                pid.unwrap_or(0),
-                name,
-                memory / (1024*1024),
-                cpu,
-                status.map_or("Unknown".to_string(), |s| format!("{:?}", s))
+fn display_processes(system: &mut System, status_filter: Option<&str>, display: &DisplayConfig) {
+    // Refresh system and process information
+    RefreshPlan::everything().refresh(system);
+    let snapshot = Snapshot::capture(system);
+    print_overview(&snapshot, display.memory_unit);
+    print_processes(&snapshot, status_filter, display);
+}
+
+// top-style summary: uptime and load, task counts, memory and swap, one bar per core
+fn print_overview(snapshot: &Snapshot, unit: MemoryUnit) {
+    let overview = &snapshot.overview;
+    let counts = snapshot.status_counts();
+    let format = |bytes: u64| units::format_bytes(bytes, unit);
+    println!(
+        "Up {}, load average: {:.2} {:.2} {:.2}",
+        snapshot::format_uptime(overview.uptime_secs),
+        overview.load_average[0],
+        overview.load_average[1],
+        overview.load_average[2]
+    );
+    println!(
+        "Tasks: {} total, {} running, {} sleeping, {} stopped",
+        counts.total, counts.running, counts.sleeping, counts.stopped
+    );
+    println!(
+        "Mem:  {} total, {} used, {} available, {} cached",
+        format(snapshot.total_memory),
+        format(overview.used_memory),
+        format(overview.available_memory),
+        format(overview.cached_memory)
+    );
+    println!("Swap: {} total, {} used", format(overview.total_swap), format(overview.used_swap));
+
+    // four cores per line, each as a 20 character bar
+    for (row, cores) in overview.core_usage.chunks(4).enumerate() {
+        let line: Vec<String> = cores
+            .iter()
+            .enumerate()
+            .map(|(i, usage)| {
+                let filled = ((usage.clamp(0.0, 100.0) / 5.0).round() as usize).min(20);
+                format!("CPU{:<3}[{:<20}] {:>5.1}%", row * 4 + i, "|".repeat(filled), usage)
+            })
+            .collect();
+        println!("{}", line.join("  "));
+    }
+    println!();
+}
+
+// Started, exited and much changed processes between a frozen snapshot and a later one
+fn print_diff(before: &Snapshot, after: &Snapshot, display: &DisplayConfig) {
+    let diff = diff::diff(before, after);
+    let unit = display.memory_unit;
+    let cpu = |usage: f32| format!("{:.1}%", display.cpu_mode.scale(usage, after.cpus));
+    println!(
+        "Compared with the snapshot from {} ({:.0} s earlier):",
+        snapshot::format_time_of_day(before.timestamp_ms),
+        diff.seconds
+    );
+    if diff.is_empty() {
+        println!("Nothing started, exited or changed much.");
+        return;
+    }
+    // + started, - exited, like a diff
+    for (title, mark, processes) in [("Started", '+', &diff.started), ("Exited", '-', &diff.exited)] {
+        if processes.is_empty() {
+            continue;
+        }
+        println!("\n{} ({}):", title, processes.len());
+        for process in processes.iter() {
+            println!(
+                "{} {:<7} {:<16} {:>10} {:>7}  {}",
+                mark,
+                process.pid,
+                snapshot::truncate_middle(&process.name, 16),
+                units::format_bytes(process.memory, unit),
+                cpu(process.cpu),
+                snapshot::truncate_middle(process.cmd.as_deref().unwrap_or(""), 60)
             );
         }
-        terminal::enable_raw_mode().expect("Failed to re-enter raw mode");
+    }
+    if !diff.changed.is_empty() {
+        println!("\nChanged a lot ({}):", diff.changed.len());
+        for change in &diff.changed {
+            println!(
+                "~ {:<7} {:<16} CPU {:>7} -> {:<7}  Memory {:>10} -> {}",
+                change.pid,
+                snapshot::truncate_middle(&change.name, 16),
+                cpu(change.cpu.0),
+                cpu(change.cpu.1),
+                units::format_bytes(change.memory.0, unit),
+                units::format_bytes(change.memory.1, unit)
+            );
+        }
+    }
+}
 
-        std::thread::sleep(Duration::from_millis(100));
+// The filters from [display] (and the command line), plus an optional status
+fn display_filter(display: &DisplayConfig, status_filter: Option<&str>) -> ProcessFilter {
+    ProcessFilter {
+        status: status_filter.map(str::to_string),
+        container: display.container.clone(),
+        unit: display.unit.clone(),
+        user: display.user.clone(),
+        command: None,
     }
 }
 
-fn display_processes(system: &mut System, status_filter: Option<&str>) {
-    // Refresh system and process information
-    system.refresh_all();
+// Print the aggregated processes as a table, sorted by [display] sort (biggest memory users first by default)
+fn print_processes(snapshot: &Snapshot, status_filter: Option<&str>, display: &DisplayConfig) {
+    let filter = display_filter(display, status_filter);
+    let mut sorted_processes = snapshot.aggregate(&filter, display.group_by);
+    sort_processes(&mut sorted_processes, display.sort, display.ascending);
+
+    // when the rows are cgroups the cgroup column shows their limits
+    let mut cgroup_limits = HashMap::new();
+    if display.group_by == GroupBy::Cgroup && display.columns.contains(&Column::Cgroup) {
+        // "(unknown)" is not a path
+        for process in sorted_processes.iter().filter(|p| p.name.starts_with('/')) {
+            cgroup_limits.insert(process.name.clone(), cgroup_limits_text(&process.name, display.memory_unit));
+        }
+    }
+    let cx = CellContext {
+        group_by: display.group_by,
+        memory_unit: display.memory_unit,
+        cpu_mode: display.cpu_mode,
+        cpus: snapshot.cpus,
+        cgroup_limits: &cgroup_limits,
+    };
+    // header first, every column as wide as its content
+    for line in columns::format_table(&display.columns, &sorted_processes, &cx) {
+        println!("{}", line);
+    }
+}
 
-    // Collect process data and aggregate by name
-    let mut aggregated_processes: HashMap<String, (u64, f32, Option<u32>, Option<ProcessStatus>)> = HashMap::new();
-    for (_, process) in system.processes() {
-        if process.memory() > 0 {
-            if let Some(status_filter) = status_filter {
-                if !format!("{:?}", process.status()).to_lowercase().contains(status_filter) {
-                    continue;
-                }
-            }
-            let entry = aggregated_processes
-                .entry(process.name().to_string_lossy().to_string())
-                .or_insert((0, 0.0, None, None));
-            entry.0 += process.memory();  // Sum memory usage
-            entry.1 += process.cpu_usage();  // Sum CPU usage
+// Limits and usage of a cgroup as one line, or why they can't be read
+fn cgroup_limits_text(path: &str, unit: MemoryUnit) -> String {
+    match cgroup::unified_root(Path::new("/sys")) {
+        Some(root) => match cgroup::read_limits(&root, path) {
+            Ok(limits) => limits.describe(unit),
+            Err(e) => e,
+        },
+        None => "cgroup v2 is not mounted".to_string(),
+    }
+}
 
-            if entry.2.is_none() {
-                entry.2 = Some(process.pid().as_u32());
+// `cgroup <pid>`: the process' cgroup with its limits and usage
+fn print_cgroup(pid: u32, unit: MemoryUnit) {
+    let path = match cgroup::read_cgroup(Path::new("/proc"), pid) {
+        Some(path) => path,
+        None => {
+            println!("Cannot read the cgroup of PID {}.", pid);
+            return;
+        }
+    };
+    println!("PID {} is in cgroup {}", pid, path);
+    let root = match cgroup::unified_root(Path::new("/sys")) {
+        Some(root) => root,
+        None => {
+            println!("cgroup v2 is not mounted, no limits to show.");
+            return;
+        }
+    };
+    match cgroup::read_limits(&root, &path) {
+        Ok(limits) => {
+            println!("{}", limits.describe(unit));
+            if let Some(usec) = limits.cpu_usage_usec {
+                println!("CPU time used: {:.1} s", usec as f64 / 1_000_000.0);
             }
+        }
+        Err(e) => println!("Cannot read limits: {}", e),
+    }
+}
+
+fn print_leaks(leaks: &[SuspectedLeak], display: &DisplayConfig) {
+    let unit = display.memory_unit;
+    if leaks.is_empty() {
+        println!("No suspected leaks.");
+        return;
+    }
+    println!("{:<10} {:<20} {:<18} {:<20} {:<10} {:<22} {:<8}", "PID", "Name", "Growth", "Memory", "Over (s)", format!("Avg CPU (% {})", display.cpu_mode.label()), "Threads");
+    for leak in leaks {
+        println!(
+            "{:<10} {:<20} {:<18} {:<20} {:<10.0} {:<22.2} {:<8}",
+            leak.pid,
+            leak.name,
+            units::format_rate(leak.slope_bytes_per_sec, unit),
+            format!("{} -> {}", units::format_bytes(leak.first_memory, unit), units::format_bytes(leak.last_memory, unit)),
+            leak.window_secs,
+            display.cpu_mode.scale(leak.average_cpu, leak.cpus),
+            leak.threads
+        );
+    }
+}
+
+// Sample live processes for a while and report the ones whose memory only grew
+fn sample_leaks(system: &mut System, config: &Config, seconds: u64) {
+    let mut resource_history = ResourceHistory::new(&config.leaks);
+    let interval = Duration::from_millis(config.refresh.cli_ms.max(1000)); // at most once a second, this runs for a while
+    let end = Instant::now() + Duration::from_secs(seconds);
+    println!("Sampling processes for {} seconds...", seconds);
+    // the leak detector only looks at memory, CPU and threads
+    let plan = RefreshPlan::for_view(&[Column::Pid, Column::Name], GroupBy::Name, &ProcessFilter::default(), false, false);
+    while Instant::now() < end {
+        plan.refresh(system);
+        resource_history.push(&Snapshot::capture_planned(system, &plan));
+        std::thread::sleep(interval);
+    }
+    print_leaks(&resource_history.suspected_leaks(), &config.display);
+}
 
-            if entry.3.is_none() {
-                entry.3 = Some(process.status());
+// One way of refreshing, timed against the others
+type Strategy = Box<dyn Fn(&mut System) -> Snapshot>;
+
+// What one refresh + capture costs with refresh_all(), with every field and with only what `display` shows
+fn measure_overhead(config: &Config, samples: u32) {
+    let display_plan = RefreshPlan::for_view(
+        &config.display.columns,
+        config.display.group_by,
+        &display_filter(&config.display, None),
+        config.display.sort == SortCriteria::IO,
+        config.io_threshold_bytes_per_sec() > 0.0,
+    );
+    let strategies: Vec<(String, Strategy)> = vec![
+        (
+            "refresh_all()".to_string(),
+            Box::new(|system: &mut System| {
+                system.refresh_all();
+                Snapshot::capture(system)
+            }),
+        ),
+        (
+            "every field".to_string(),
+            Box::new(|system: &mut System| {
+                RefreshPlan::everything().refresh(system);
+                Snapshot::capture(system)
+            }),
+        ),
+        (
+            format!("display ({})", display_plan.describe()),
+            Box::new(move |system: &mut System| {
+                display_plan.refresh(system);
+                Snapshot::capture_planned(system, &display_plan)
+            }),
+        ),
+    ];
+    // one System each, the first refresh of a System reads everything and is not counted
+    let mut systems: Vec<System> = strategies.iter().map(|_| System::new()).collect();
+    let mut totals = vec![(Duration::ZERO, Duration::ZERO); strategies.len()];
+    let mut processes = 0;
+    println!("Timing {} refreshes per strategy...", samples);
+    for round in 0..=samples {
+        // taking turns, so all of them see the same load
+        for (i, (_, sample)) in strategies.iter().enumerate() {
+            let (started, cpu_before) = (Instant::now(), refresh::thread_cpu_time());
+            processes = sample(&mut systems[i]).processes.len();
+            if round > 0 {
+                totals[i].0 += started.elapsed();
+                totals[i].1 += refresh::thread_cpu_time().saturating_sub(cpu_before);
             }
         }
+        std::thread::sleep(Duration::from_millis(config.refresh.cli_ms));
+    }
+    println!("Average per refresh, {} processes:", processes);
+    for ((name, _), (elapsed, cpu_time)) in strategies.iter().zip(totals) {
+        println!(
+            "{:>9.1} ms ({:.1} ms CPU)  {}",
+            elapsed.as_secs_f64() * 1000.0 / samples as f64,
+            cpu_time.as_secs_f64() * 1000.0 / samples as f64,
+            name
+        );
     }
-    let mut sorted_processes: Vec<_> = aggregated_processes.into_iter().collect();
-    sorted_processes.sort_by(|a, b| b.1 .0.cmp(&a.1 .0)); // Compare the memory usage values
+}
 
-    // Print header
-    println!("{:<10} {:<20} {:<15} {:<15} {:<15}", "PID", "Name", "Memory (MB)", "CPU Usage (%)", "Status");
+// Run the leak detector over recorded snapshots, oldest first
+fn history_leaks(snapshots: &[Snapshot], config: &Config) -> Vec<SuspectedLeak> {
+    let mut resource_history = ResourceHistory::new(&config.leaks);
+    for snapshot in snapshots {
+        resource_history.push(snapshot);
+    }
+    resource_history.suspected_leaks()
+}
 
-    // Print aggregated process details
-    for (name, (memory, cpu, pid, status)) in sorted_processes {
-        println! (
-            "{:<10} {:<20} {:<15.2} {:<15.2} {:<15}",
-            pid.unwrap_or(0),
-            name,
-            memory / (1024*1024),
-            cpu,
-            status.map_or("Unknown".to_string(), |s| format!("{:?}", s))
+// Prompt for moving through a history file, the same commands as live mode work on the current snapshot
+fn replay_prompt(mut replay: Replay, config: &Config) {
+    println!("Replaying {} snapshots. Type 'help' to view replay commands.", replay.snapshots.len());
+    loop {
+        print!(
+            "replay {} UTC (+{:.0}s) {}/{}> ",
+            snapshot::format_time_of_day(replay.current().timestamp_ms),
+            replay.offset_seconds(),
+            replay.position + 1,
+            replay.snapshots.len()
         );
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).expect("Failed to read input") == 0 {
+            break;
+        }
+        match *input.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            ["display"] => {
+                print_overview(replay.current(), config.display.memory_unit);
+                print_processes(replay.current(), None, &config.display);
+            }
+            ["display", status] => {
+                print_overview(replay.current(), config.display.memory_unit);
+                print_processes(replay.current(), Some(status), &config.display);
+            }
+            ["search", pid] => match pid.parse::<u32>() {
+                Ok(pid_num) => match replay.current().process(pid_num) {
+                    Some(process) => println!(
+                        "Process found: \nPID: {} \nName: {} \nCommand: {} \nMemory: {} \nCPU Usage: {:.2}% {} \nDisk read: {} ({}/s) \nDisk written: {} ({}/s) \nNice: {} (priority {}) \nStatus: {}",
+                        pid_num,
+                        process.name,
+                        process.cmd.as_deref().unwrap_or("unknown"),
+                        units::format_bytes(process.memory, config.display.memory_unit),
+                        config.display.cpu_mode.scale(process.cpu, replay.current().cpus),
+                        config.display.cpu_mode.label(),
+                        units::format_bytes(process.read_bytes, config.display.memory_unit),
+                        units::format_bytes(process.read_rate as u64, config.display.memory_unit),
+                        units::format_bytes(process.written_bytes, config.display.memory_unit),
+                        units::format_bytes(process.write_rate as u64, config.display.memory_unit),
+                        process.nice,
+                        process.priority,
+                        process.status
+                    ),
+                    None => println!("Process with PID {} not found.", pid_num),
+                },
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            ["next"] => replay.step(1),
+            ["prev"] => replay.step(-1),
+            ["next", n] => match n.parse::<isize>() {
+                Ok(n) => replay.step(n),
+                Err(_) => println!("Invalid number of snapshots."),
+            },
+            ["prev", n] => match n.parse::<isize>() {
+                Ok(n) => replay.step(-n),
+                Err(_) => println!("Invalid number of snapshots."),
+            },
+            ["seek", seconds] => match seconds.parse::<f64>() {
+                Ok(seconds) => replay.seek_seconds(seconds),
+                Err(_) => println!("Invalid time, expected seconds from the start of the recording."),
+            },
+            ["play"] => play_replay(&mut replay, &config.display),
+            ["leaks"] => print_leaks(&history_leaks(&replay.snapshots[..=replay.position], config), &config.display),
+            ["exit"] => break,
+            ["help"] => {
+                println!(
+                    "Replay commands:
+                    \n  -- 'display'            : View processes at the current point in time.
+                    \n  -- 'display <status>'   : View processes by status (e.g., 'display sleep')
+                    \n  -- 'search <proc_id>'   : Show a recorded process by its PID.
+                    \n  -- 'next [n]' / 'prev [n]' : Move n snapshots forward / back (default 1).
+                    \n  -- 'seek <seconds>'     : Jump to this many seconds after the start of the recording.
+                    \n  -- 'play'               : Play the recording like the live 'display', press any key to stop.
+                    \n  -- 'leaks'              : Processes whose memory only grew in the window before this point.
+                    \n  -- 'exit'               : Leave replay mode.
+                    \n"
+                );
+            }
+            _ => {
+                println!("Unknown command. Type 'help' to view replay commands.");
+            }
+        }
     }
-}    
+}
+
+// Same screen as `display`, but fed from the recording at its original pace
+fn play_replay(replay: &mut Replay, display: &DisplayConfig) {
+    replay.playing = true;
+    replay.step(0);
+    loop {
+        clearscreen::clear().unwrap();
+        terminal::disable_raw_mode().expect("Failed to re-enter raw mode");
+        println!(
+            "Replay {} UTC (+{:.0}s) {}/{}",
+            snapshot::format_time_of_day(replay.current().timestamp_ms),
+            replay.offset_seconds(),
+            replay.position + 1,
+            replay.snapshots.len()
+        );
+        print_overview(replay.current(), display.memory_unit);
+        print_processes(replay.current(), None, display);
+        terminal::enable_raw_mode().expect("Failed to re-enter raw mode");
 
+        // wait for the next snapshot or a key press
+        let wait = match replay.until_next() {
+            Some(wait) => wait,
+            None => break, // end of the recording
+        };
+        if event::poll(wait).expect("Failed to poll event") {
+            if let event::Event::Key(_) = event::read().expect("Failed to read event") {
+                break;
+            }
+        }
+        replay.advance();
+    }
+    replay.playing = false;
+    terminal::disable_raw_mode().expect("Failed to disable raw mode");
+    println!("Replay playback stopped.");
+}
 
 fn with_process(pid_str: &str, system: &mut System) -> bool {
     // Parse the input string into a numeric PID
@@ -666,17 +2668,25 @@ fn with_process(pid_str: &str, system: &mut System) -> bool {
     }
 }
 
-fn search_process(pid: u32, system: &System) {
+fn search_process(pid: u32, system: &System, display: &DisplayConfig) {
     // Attempt to retrieve the process with the given PID from the system
     if let Some(process) = system.process(sysinfo::Pid::from_u32(pid)) {
         // If the process is found, print its details including:
-        // PID, name, memory usage in MB, CPU usage percentage, and status
+        // PID, name, memory usage, CPU usage percentage, and status
         println!(
-            "Process found: \nPID: {} \nName: {} \nMemory: {} MB \nCPU Usage: {:.2}% \nStatus: {:?}",
+            "Process found: \nPID: {} \nName: {} \nCommand: {} \nExecutable: {} \nMemory: {} \nCPU Usage: {:.2}% {} \nDisk read: {} \nDisk written: {} \nNice: {} \nI/O priority: {} \nStatus: {:?}",
             pid,
             process.name().to_string_lossy(), // Converts process name to a displayable string
-            process.memory() / 1024 / 1024, // Convert memory usage from bytes to MB
-            process.cpu_usage(), // CPU usage percentage
+            process.cmd().iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "), // the full command line
+            process.exe().map_or("unknown".to_string(), |path| path.display().to_string()),
+            units::format_bytes(process.memory(), display.memory_unit), // in the configured memory unit
+            display.cpu_mode.scale(process.cpu_usage(), system.cpus().len()), // CPU usage percentage in the configured mode
+            display.cpu_mode.label(),
+            units::format_bytes(process.disk_usage().total_read_bytes, display.memory_unit), // since the process started
+            units::format_bytes(process.disk_usage().total_written_bytes, display.memory_unit),
+            // sysinfo has neither, read them ourselves
+            priority::read_priority(Path::new("/proc"), pid).map_or("unknown".to_string(), |(priority, nice)| format!("{} (priority {})", nice, priority)),
+            priority::io_priority(pid).map_or_else(|e| e, |io| io.describe()),
             process.status() // Current status of the process (Running, Sleeping)
         );
     } else {
@@ -685,58 +2695,406 @@ fn search_process(pid: u32, system: &System) {
     }
 }
 
-fn show_process_count(system: &System) {
-    // Initialize counters for running, sleeping, and stopped processes
-    let mut running = 0;
-    let mut sleeping = 0;
-    let mut stopped = 0;
-    // Iterate through all processes in the system
-    for (_, process) in system.processes() {
-        // Categorize the process based on its current status
-        match process.status() {
-            // If the status contains "Run", increment the running counter
-            status if format!("{:?}", status).contains("Run") => running += 1,
-            // If the status contains "Sleep", increment the sleeping counter
-            status if format!("{:?}", status).contains("Sleep") => sleeping += 1,
-            // If the status contains "Stop", increment the stopped counter
-            status if format!("{:?}", status).contains("Stop") => stopped += 1,
-            // Ignore all other statuses
-            _ => {}
-        }
-    }
-    // Calculate the total number of processes
-    let total = running + sleeping + stopped;
+// `search <text>`: every process whose command line (or name) contains the text, with the full command line
+fn find_processes(text: &str, snapshot: &Snapshot) {
+    let filter = ProcessFilter { command: Some(text.to_string()), ..Default::default() };
+    let mut found: Vec<&ProcessRecord> = snapshot.processes.iter().filter(|p| !p.is_thread && filter.matches(p)).collect();
+    if found.is_empty() {
+        println!("No process with '{}' in its command line.", text);
+        return;
+    }
+    found.sort_by_key(|p| p.pid);
+    println!("{:<10} {:<14} Command", "PID", "User");
+    for process in found {
+        println!(
+            "{:<10} {:<14} {}",
+            process.pid,
+            process.user_label(),
+            process.cmd.clone().unwrap_or_else(|| format!("[{}]", process.name))
+        );
+    }
+}
+
+fn show_process_count(snapshot: &Snapshot) {
+    let counts = snapshot.status_counts();
     println!( // Print the counts for total, running, sleeping, and stopped processes
         "Total processes: {}\nRunning: {}\nSleeping: {}\nStopped: {}",
-        total, running, sleeping, stopped
+        counts.total, counts.running, counts.sleeping, counts.stopped
+    );
+}
+
+// "45 open fds, limit 1024 (hard 524288)"
+fn fd_usage_text(count: usize, limit: &Result<(Option<u64>, Option<u64>), String>) -> String {
+    let show = |limit: Option<u64>| limit.map_or("unlimited".to_string(), |l| l.to_string());
+    match limit {
+        Ok((soft, hard)) => format!("{} open fds, limit {} (hard {})", count, show(*soft), show(*hard)),
+        Err(_) => format!("{} open fds, limit unknown", count),
+    }
+}
+
+// The fd target, with the connection spelled out for sockets
+fn describe_fd(file: &OpenFile, sockets: &HashMap<u64, Connection>) -> String {
+    match file.socket_inode().and_then(|inode| sockets.get(&inode)) {
+        Some(connection) => format!("{} ({})", file.target, connection.describe()),
+        None => file.target.clone(),
+    }
+}
+
+// `files <pid>`: lsof-like list of open fds
+fn print_files(pid: u32) {
+    let proc_root = Path::new("/proc");
+    let files = match inspect::open_files(proc_root, pid) {
+        Ok(files) => files,
+        Err(e) => {
+            println!("Failed to list open files of {}: {}", pid, e);
+            return;
+        }
+    };
+    let sockets = net::sockets_by_inode(proc_root).unwrap_or_default();
+    println!("{}", fd_usage_text(files.len(), &inspect::fd_limit(proc_root, pid)));
+    println!("{:<6} {:<8} Target", "FD", "Type");
+    for file in &files {
+        println!("{:<6} {:<8} {}", file.fd, file.kind.label(), describe_fd(file, &sockets));
+    }
+}
+
+// `threads <pid>`: sample the threads twice, then list them busiest first
+fn print_threads(pid: u32, display: &DisplayConfig, cpus: usize) {
+    let proc_root = Path::new("/proc");
+    let before = match inspect::threads(proc_root, pid) {
+        Ok(threads) => threads,
+        Err(e) => {
+            println!("Failed to list threads of {}: {}", pid, e);
+            return;
+        }
+    };
+    let started = Instant::now();
+    std::thread::sleep(Duration::from_millis(500));
+    let mut threads = match inspect::threads(proc_root, pid) {
+        Ok(threads) => threads,
+        Err(e) => {
+            println!("Failed to list threads of {}: {}", pid, e);
+            return;
+        }
+    };
+    inspect::compute_thread_cpu(&mut threads, &before, started.elapsed().as_secs_f64());
+    threads.sort_by(|a, b| b.cpu.partial_cmp(&a.cpu).unwrap_or(std::cmp::Ordering::Equal).then(a.tid.cmp(&b.tid)));
+
+    println!("{} threads", threads.len());
+    println!("{:<10} {:<20} {:<12} {:<26} {:<8}", "TID", "Name", "State", display.cpu_mode.header(), "Last CPU");
+    for thread in &threads {
+        println!(
+            "{:<10} {:<20} {:<12} {:<26.2} {:<8}",
+            thread.tid,
+            thread.name,
+            thread.state,
+            display.cpu_mode.scale(thread.cpu, cpus),
+            thread.last_cpu
+        );
+    }
+}
+
+// `maps <pid>`: smaps_rollup totals, then the mappings grouped by file
+fn print_maps(pid: u32, unit: MemoryUnit) {
+    let maps = match inspect::memory_maps(Path::new("/proc"), pid) {
+        Ok(maps) => maps,
+        Err(e) => {
+            println!("Failed to read memory maps of {}: {}", pid, e);
+            return;
+        }
+    };
+    let format = |bytes: u64| units::format_bytes(bytes, unit);
+    let total = &maps.total;
+    println!(
+        "RSS {}  PSS {}  Shared {} (clean {}, dirty {})  Private {} (clean {}, dirty {})  Swap {}\n",
+        format(total.rss),
+        format(total.pss),
+        format(total.shared()),
+        format(total.shared_clean),
+        format(total.shared_dirty),
+        format(total.private()),
+        format(total.private_clean),
+        format(total.private_dirty),
+        format(total.swap)
     );
+    println!(
+        "{:<12} {:<12} {:<12} {:<12} {:<12} {:<12} {:<6} Mapping",
+        "Size", "RSS", "PSS", "Shared", "Private", "Swap", "Count"
+    );
+    for group in &maps.groups {
+        println!(
+            "{:<12} {:<12} {:<12} {:<12} {:<12} {:<12} {:<6} {}",
+            format(group.usage.size),
+            format(group.usage.rss),
+            format(group.usage.pss),
+            format(group.usage.shared()),
+            format(group.usage.private()),
+            format(group.usage.swap),
+            group.mappings,
+            group.name
+        );
+    }
+}
+
+fn print_connections(connections: &[Connection]) {
+    if connections.is_empty() {
+        println!("No connections found.");
+        return;
+    }
+    println!("{:<6} {:<40} {:<40} {:<12} {:<10} {:<20}", "Proto", "Local", "Remote", "State", "PID", "Process");
+    for connection in connections {
+        let (pids, names) = if connection.owners.is_empty() {
+            ("-".to_string(), "-".to_string()) // not readable without root
+        } else {
+            connection.owner_text()
+        };
+        println!(
+            "{:<6} {:<40} {:<40} {:<12} {:<10} {:<20}",
+            connection.protocol.label(),
+            connection.local,
+            connection.remote,
+            connection.state,
+            pids,
+            names
+        );
+    }
+}
+
+// `ports`, `connections`, `connections unix` and `connections <pid>`
+fn show_connections(listening_only: bool, include_unix: bool, pid: Option<u32>) {
+    match net::connections(Path::new("/proc"), include_unix) {
+        Ok(connections) => {
+            let shown: Vec<Connection> = connections
+                .into_iter()
+                .filter(|c| !listening_only || c.is_listening())
+                .filter(|c| pid.is_none_or(|pid| c.is_owned_by(pid)))
+                .collect();
+            print_connections(&shown);
+        }
+        Err(e) => println!("Failed to read connections: {}", e),
+    }
+}
+
+// Processes listening on a port, for `search :8080` / `kill :8080`
+fn port_listeners(port_str: &str) -> Vec<(u32, String)> {
+    let port = match net::parse_port(port_str) {
+        Some(port) => port,
+        None => {
+            println!("Invalid port. Please provide a port like ':8080'.");
+            return Vec::new();
+        }
+    };
+    match net::connections(Path::new("/proc"), false) {
+        Ok(connections) => {
+            let owners = net::listeners(&connections, port);
+            if owners.is_empty() {
+                println!("Nothing we can see listens on port {} (other users' sockets need root).", port);
+            }
+            owners
+        }
+        Err(e) => {
+            println!("Failed to read connections: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+// "docker container 3f2a...", or the cgroup of a process that is not in one
+fn container_text(cgroup: Option<&str>) -> String {
+    match cgroup {
+        Some(path) => match container::from_cgroup(path) {
+            Some(found) => format!("{} container {}", found.runtime, found.id),
+            None => format!("Not in a container (cgroup {})", path),
+        },
+        None => "Cgroup unknown".to_string(),
+    }
+}
+
+// (kind, inode, "host" / "own") per namespace, compared with PID 1's
+fn namespace_rows(namespaces: &[(&'static str, u64)], host: &[(&'static str, u64)]) -> Vec<(&'static str, u64, &'static str)> {
+    namespaces
+        .iter()
+        .map(|(kind, inode)| {
+            let shared = match host.iter().find(|(host_kind, _)| host_kind == kind) {
+                Some((_, host_inode)) if host_inode == inode => "host",
+                Some(_) => "own",
+                None => "?", // PID 1's namespaces need root
+            };
+            (*kind, *inode, shared)
+        })
+        .collect()
+}
+
+// `container <pid>`: the process' container and which namespaces it does not share with the host
+fn print_container(pid: u32) {
+    let proc_root = Path::new("/proc");
+    println!("PID {}: {}", pid, container_text(cgroup::read_cgroup(proc_root, pid).as_deref()));
+    match container::namespaces(proc_root, pid) {
+        Ok(namespaces) => {
+            let host = container::namespaces(proc_root, 1).unwrap_or_default();
+            println!("{:<10} {:<14} Shared with", "Namespace", "Inode");
+            for (kind, inode, shared) in namespace_rows(&namespaces, &host) {
+                println!("{:<10} {:<14} {}", kind, inode, shared);
+            }
+        }
+        Err(e) => println!("Failed to read namespaces: {}", e),
+    }
+}
+
+// `affinity <pid>`: the process' CPUs, plus any thread pinned differently
+fn print_affinity(pid: u32, cpus: usize) {
+    let mask = match affinity::get(pid) {
+        Ok(mask) => mask,
+        Err(e) => {
+            println!("Failed to read CPU affinity: {}", e);
+            return;
+        }
+    };
+    if affinity::is_unpinned(&mask, cpus) {
+        println!("PID {}: any of the {} CPUs (not pinned)", pid, cpus);
+    } else {
+        println!("PID {}: CPUs {} ({} of {})", pid, affinity::format_cpu_list(&mask), mask.len(), cpus);
+    }
+    for (tid, thread_mask) in affinity::thread_masks(Path::new("/proc"), pid) {
+        if let Ok(thread_mask) = thread_mask {
+            if thread_mask != mask {
+                println!("  thread {}: CPUs {}", tid, affinity::format_cpu_list(&thread_mask));
+            }
+        }
+    }
+}
+
+// `affinity <pid> <cpu-list> [threads]`
+fn set_affinity(pid: u32, list: &str, all_threads: bool, cpus: usize) {
+    let list = match affinity::parse_cpu_list(list, cpus) {
+        Ok(list) => list,
+        Err(e) => {
+            println!("Invalid CPU list: {}. Use something like '0-3,8'.", e);
+            return;
+        }
+    };
+    match affinity::set(Path::new("/proc"), pid, &list, all_threads) {
+        Ok(changed) => {
+            println!("Pinned {} thread(s) of PID {} to CPUs {}.", changed, pid, affinity::format_cpu_list(&list));
+            print_affinity(pid, cpus);
+        }
+        Err(e) => println!("Failed to set CPU affinity: {}", e),
+    }
+}
+
+// Processes for `renice` / `ionice`: a pid, tree:<pid> or name:<name>, from a fresh refresh
+fn priority_targets(target: &str, system: &mut System) -> Vec<(u32, String)> {
+    let target = match Target::parse(target) {
+        Some(target) => target,
+        None => {
+            println!("Invalid target. Please provide a PID, 'tree:<pid>' or 'name:<name>'.");
+            return Vec::new();
+        }
+    };
+    RefreshPlan::everything().refresh(system);
+    let targets = target.resolve(&Snapshot::capture(system).processes);
+    if targets.is_empty() {
+        println!("No matching process is running.");
+    }
+    targets
+}
+
+fn renice_processes(target: &str, nice: &str, system: &mut System) {
+    let nice = match nice.parse::<i32>() {
+        Ok(nice) => nice,
+        Err(_) => {
+            println!("Invalid nice value. Please provide a number from {} to {}.", priority::NICE_MIN, priority::NICE_MAX);
+            return;
+        }
+    };
+    let targets = priority_targets(target, system);
+    let proc_root = Path::new("/proc");
+    for line in priority::apply(&targets, &format!("nice set to {}", nice), |pid| priority::renice(proc_root, pid, nice)) {
+        println!("{}", line);
+    }
 }
 
-fn kill_process(pid: u32) {
-    match signal::kill(Pid::from_raw(pid as i32), Signal::SIGKILL) {
-        Ok(_) => println!("Process with PID {} killed successfully.", pid),
-        Err(e) => println!("Failed to kill process with PID {}: {}", pid, e),
+fn ionice_processes(target: &str, class: &str, level: Option<&str>, system: &mut System) {
+    let class = match IoClass::parse(class) {
+        Some(class) => class,
+        None => {
+            println!("Invalid I/O class. Please use none, realtime, best-effort or idle.");
+            return;
+        }
+    };
+    // realtime and best-effort default to the middle level, like ionice(1)
+    let level = match level.map(|l| l.parse::<u8>()) {
+        None => 4,
+        Some(Ok(level)) if level <= 7 => level,
+        Some(_) => {
+            println!("Invalid level. Please provide a number from 0 (highest) to 7.");
+            return;
+        }
+    };
+    let io = IoPriority { class, level };
+    let targets = priority_targets(target, system);
+    let proc_root = Path::new("/proc");
+    for line in priority::apply(&targets, &format!("I/O priority set to {}", io.describe()), |pid| priority::ionice(proc_root, pid, class, level)) {
+        println!("{}", line);
     }
 }
 
-fn sleep_process(pid: u32) {
-    match signal::kill(Pid::from_raw(pid as i32), Signal::SIGSTOP) {
-        Ok(_) => println!("Process with PID {} paused (SIGSTOP).", pid),
-        Err(e) => println!("Failed to pause process with PID {}: {}", pid, e),
+// SIGKILL / SIGSTOP / SIGCONT. The message says what happened, the GUI shows it and the CLI prints it.
+fn kill_process(pid: u32) -> Result<String, String> {
+    send_signal(pid, Signal::SIGKILL, "kill")?;
+    Ok(format!("Process with PID {} killed successfully.", pid))
+}
+
+fn sleep_process(pid: u32) -> Result<String, String> {
+    send_signal(pid, Signal::SIGSTOP, "pause")?;
+    Ok(format!("Process with PID {} paused (SIGSTOP).", pid))
+}
+
+fn resume_process(pid: u32) -> Result<String, String> {
+    send_signal(pid, Signal::SIGCONT, "resume")?;
+    Ok(format!("Process with PID {} resumed (SIGCONT).", pid))
+}
+
+fn send_signal(pid: u32, sig: Signal, action: &str) -> Result<(), String> {
+    users::check_owner(Path::new("/proc"), pid, Permission::Signal, action)?;
+    signal::kill(Pid::from_raw(pid as i32), sig).map_err(|e| format!("Failed to {} process with PID {}: {}", action, pid, e))
+}
+
+// CLI: the outcome of an action either way
+fn print_outcome(outcome: Result<String, String>) {
+    match outcome {
+        Ok(message) | Err(message) => println!("{}", message),
     }
 }
 
-fn resume_process(pid: u32) {
-    match signal::kill(Pid::from_raw(pid as i32), Signal::SIGCONT) {
-        Ok(_) => println!("Process with PID {} resumed (SIGCONT).", pid),
-        Err(e) => println!("Failed to resume process with PID {}: {}", pid, e),
+// GUI: (is error, text) for the action message under the filters
+fn outcome_message(outcome: Result<String, String>) -> (bool, String) {
+    match outcome {
+        Ok(message) => (false, message),
+        Err(message) => (true, message),
     }
 }
 
 fn main() {
 
+    // Config file + TASKMANAGER_* env + flags like --cpu-threshold 75, the flags are removed from args
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let (config, file_config, config_path) = match config::load_with_overrides(&mut args) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Config error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // `watch` runs the alert rules headless (e.g. under systemd) instead of the interactive prompt
+    if let Some("watch") = args.first().map(String::as_str) {
+        std::process::exit(watch::run_watch(&args[1..], &config));
+    }
+
     println!("Welcome! Type 'help' to view all commands.");
     let mut system = System::new_all();
+    let mut frozen: Option<Snapshot> = None; // paused in 'display' or taken with 'freeze', for 'compare'
     loop {
         print!("> ");
         io::stdout().flush().unwrap();
@@ -756,7 +3114,10 @@ fn main() {
                     initial_window_size: Some(egui::vec2(800.0, 600.0)), // this determines starting resolution
                     ..Default::default()
                 },
-                Box::new(|_cc| Box::<ProcessDisplay>::default()), // second time the struct is used, this is related to memory and how gui is stored
+                {
+                    let (config, file_config, config_path) = (config.clone(), file_config.clone(), config_path.clone());
+                    Box::new(move |_cc| Box::new(ProcessDisplay::new(config, file_config, config_path, None))) // second time the struct is used, this is related to memory and how gui is stored
+                },
                 );
             }
             &["Tree", "View", "display"] => {            
@@ -768,44 +3129,200 @@ fn main() {
                         initial_window_size: Some(egui::vec2(800.0, 600.0)), // this determines starting resolution
                         ..Default::default()
                     },
-                    Box::new(|_cc| Box::<TreeView>::default()),
+                    {
+                        let tree = TreeView::new(None, &config);
+                        Box::new(move |_cc| Box::new(tree))
+                    },
                 )
                 .expect("Failed to start eframe app");
             }
+            &["GUI", "replay", path] => {
+                // same window as 'GUI display', fed from a history file
+                match history::load_history(std::path::Path::new(path)) {
+                    Ok(snapshots) => {
+                        let (config, file_config, config_path) = (config.clone(), file_config.clone(), config_path.clone());
+                        eframe::run_native(
+                            "GUI Process Replay",
+                            eframe::NativeOptions {
+                                drag_and_drop_support: true,
+                                maximized: true,
+                                initial_window_size: Some(egui::vec2(800.0, 600.0)),
+                                ..Default::default()
+                            },
+                            Box::new(move |_cc| Box::new(ProcessDisplay::new(config, file_config, config_path, Some(Replay::new(snapshots))))),
+                        )
+                        .expect("Failed to start eframe app");
+                    }
+                    Err(e) => println!("Cannot replay: {}", e),
+                }
+            }
+            &["Tree", "View", "replay", path] => {
+                match history::load_history(std::path::Path::new(path)) {
+                    Ok(snapshots) => {
+                        eframe::run_native(
+                            "Process Tree Replay",
+                            eframe::NativeOptions {
+                                drag_and_drop_support: true,
+                                maximized: true,
+                                initial_window_size: Some(egui::vec2(800.0, 600.0)),
+                                ..Default::default()
+                            },
+                            {
+                                let tree = TreeView::new(Some(Replay::new(snapshots)), &config);
+                                Box::new(move |_cc| Box::new(tree))
+                            },
+                        )
+                        .expect("Failed to start eframe app");
+                    }
+                    Err(e) => println!("Cannot replay: {}", e),
+                }
+            }
+            &["replay"] => {
+                // default to the file the recorder writes
+                match history::load_history(&config.history.path) {
+                    Ok(snapshots) => replay_prompt(Replay::new(snapshots), &config),
+                    Err(e) => println!("Cannot replay: {}", e),
+                }
+            }
+            &["replay", path] => {
+                match history::load_history(std::path::Path::new(path)) {
+                    Ok(snapshots) => replay_prompt(Replay::new(snapshots), &config),
+                    Err(e) => println!("Cannot replay: {}", e),
+                }
+            }
             &["display"] => {
-                display(&mut system);
+                display(&mut system, &config, &mut frozen);
             }
             &["display", status] => {
-                display_processes(&mut system, Some(status));
+                display_processes(&mut system, Some(status), &config.display);
+            }
+            &["search", port] if port.starts_with(':') => {
+                RefreshPlan::everything().refresh(&mut system);
+                for (pid, name) in port_listeners(port) {
+                    println!("Listening on {}: {} ({})", port, pid, name);
+                    search_process(pid, &system, &config.display);
+                }
+            }
+            // anything that is not a PID searches the command lines
+            &["search", text] if text.parse::<u32>().is_err() => {
+                RefreshPlan::everything().refresh(&mut system);
+                find_processes(text, &Snapshot::capture(&system));
             }
             &["search", pid] => {
                 if with_process(pid, &mut system) {
                     if let Ok(pid_num) = pid.parse::<u32>() {
-                        search_process(pid_num, &system);
+                        search_process(pid_num, &system, &config.display);
                     }
                 }
             }
             &["count"] => {
-                show_process_count(&system);
+                show_process_count(&Snapshot::capture(&system));
+            }
+            &["leaks"] => {
+                // look through the recorded history, if there is any
+                match history::load_history(&config.history.path) {
+                    Ok(snapshots) => print_leaks(&history_leaks(&snapshots, &config), &config.display),
+                    Err(e) => println!("No history to check ({}). Use 'leaks <seconds>' to sample live.", e),
+                }
+            }
+            &["freeze"] => {
+                RefreshPlan::everything().refresh(&mut system);
+                let snapshot = Snapshot::capture(&system);
+                println!(
+                    "Froze {} processes at {}. 'compare' shows what changed since.",
+                    snapshot.processes.iter().filter(|p| !p.is_thread).count(),
+                    snapshot::format_time_of_day(snapshot.timestamp_ms)
+                );
+                frozen = Some(snapshot);
+            }
+            &["compare"] => match &frozen {
+                Some(frozen) => {
+                    RefreshPlan::everything().refresh(&mut system);
+                    print_diff(frozen, &Snapshot::capture(&system), &config.display);
+                }
+                None => println!("Nothing frozen yet. Use 'freeze', or pause 'display' with p."),
+            },
+            &["overhead"] => measure_overhead(&config, 5),
+            &["overhead", samples] => match samples.parse::<u32>() {
+                Ok(samples) if samples > 0 => measure_overhead(&config, samples),
+                _ => println!("Invalid count. Please provide a number of refreshes."),
+            },
+            &["leaks", seconds] => match seconds.parse::<u64>() {
+                Ok(seconds) => sample_leaks(&mut system, &config, seconds),
+                Err(_) => println!("Invalid duration. Please provide a number of seconds."),
+            },
+            &["kill", port] if port.starts_with(':') => {
+                for (pid, name) in port_listeners(port) {
+                    println!("Killing {} ({}), listening on {}.", pid, name, port);
+                    print_outcome(kill_process(pid));
+                }
             }
             &["kill", pid] => {
                 if with_process(pid, &mut system) {
                     if let Ok(pid_num) = pid.parse::<u32>() {
-                        kill_process(pid_num);
+                        print_outcome(kill_process(pid_num));
                     }
                 }
             }
+            &["files", pid] => match pid.parse::<u32>() {
+                Ok(pid) => print_files(pid),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["threads", pid] => match pid.parse::<u32>() {
+                Ok(pid) => print_threads(pid, &config.display, system.cpus().len()),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["maps", pid] => match pid.parse::<u32>() {
+                Ok(pid) => print_maps(pid, config.display.memory_unit),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["ports"] => show_connections(true, false, None),
+            &["connections"] => show_connections(false, false, None),
+            &["connections", "unix"] => show_connections(false, true, None),
+            &["connections", pid] => match pid.parse::<u32>() {
+                Ok(pid) => show_connections(false, true, Some(pid)),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID or 'unix'."),
+            },
+            &["cgroups"] => {
+                // the process table grouped by cgroup, whatever the configured grouping is
+                RefreshPlan::everything().refresh(&mut system);
+                let display = DisplayConfig { group_by: GroupBy::Cgroup, ..config.display.clone() };
+                print_processes(&Snapshot::capture(&system), None, &display);
+            }
+            &["container", pid] => match pid.parse::<u32>() {
+                Ok(pid) => print_container(pid),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["cgroup", pid] => match pid.parse::<u32>() {
+                Ok(pid) => print_cgroup(pid, config.display.memory_unit),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["affinity", pid] => match pid.parse::<u32>() {
+                Ok(pid) => print_affinity(pid, system.cpus().len()),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["affinity", pid, list] => match pid.parse::<u32>() {
+                Ok(pid) => set_affinity(pid, list, false, system.cpus().len()),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["affinity", pid, list, "threads"] => match pid.parse::<u32>() {
+                Ok(pid) => set_affinity(pid, list, true, system.cpus().len()),
+                Err(_) => println!("Invalid PID. Please provide a valid numeric PID."),
+            },
+            &["renice", target, nice] => renice_processes(target, nice, &mut system),
+            &["ionice", target, class] => ionice_processes(target, class, None, &mut system),
+            &["ionice", target, class, level] => ionice_processes(target, class, Some(level), &mut system),
             &["sleep", pid] => {
                 if with_process(pid, &mut system) {
                     if let Ok(pid_num) = pid.parse::<u32>() {
-                        sleep_process(pid_num);
+                        print_outcome(sleep_process(pid_num));
                     }
                 }
             }
             &["resume", pid] => {
                 if with_process(pid, &mut system) {
                     if let Ok(pid_num) = pid.parse::<u32>() {
-                        resume_process(pid_num);
+                        print_outcome(resume_process(pid_num));
                     }
                 }
             }
@@ -818,14 +3335,45 @@ fn main() {
                     "Available commands:
                     \n  -- 'GUI display'        : View processes in GUI window
                     \n  -- 'Tree View display'  : View Tree View of process in GUI window
+                    \n  -- 'replay [file]'      : Step through recorded history (default: the configured history file).
+                    \n  -- 'GUI replay <file>'  : Scrub through recorded history in the GUI window.
+                    \n  -- 'Tree View replay <file>' : Tree View of recorded history.
                     \n  -- 'display'            : View processes info.
                     \n  -- 'display <status>'   : View processes by status (e.g., 'display sleep')
                     \n  -- 'search <proc_id>'   : Search for a process by its PID.
+                    \n  -- 'search :<port>'     : Show the processes listening on a port.
+                    \n  -- 'search <text>'      : Find processes whose command line contains the text (e.g., 'search manage.py').
                     \n  -- 'kill <proc_id>'     : Kill a process, where <proc_id> is the process ID.
+                    \n  -- 'kill :<port>'       : Kill whoever listens on a port (e.g., 'kill :8080').
+                    \n  -- 'ports'              : List listening TCP / UDP sockets and their processes.
+                    \n  -- 'connections'        : List all TCP / UDP sockets ('connections unix' adds unix sockets).
+                    \n  -- 'connections <pid>'  : List the sockets of one process.
+                    \n  -- 'files <proc_id>'    : List a process's open files, sockets and pipes with its fd limit.
+                    \n  -- 'threads <proc_id>'  : List a process's threads (TID, name, state, CPU, last CPU), busiest first.
+                    \n  -- 'maps <proc_id>'     : Show a process's memory (RSS, PSS, shared, private, swap) by mapping.
+                    \n  -- 'cgroups'            : View processes added up per cgroup, with each cgroup's limits.
+                    \n  -- 'container <proc_id>' : Show a process's container (Docker, Podman, ...) and namespaces.
+                    \n  -- 'cgroup <proc_id>'   : Show a process's cgroup, its limits (memory.max, cpu.max, pids.max) and usage.
+                    \n  -- 'affinity <proc_id>' : Show the CPUs a process may run on.
+                    \n  -- 'affinity <proc_id> <cpu-list> [threads]' : Pin a process (and with 'threads' all its threads) to CPUs like '0-3,8'.
+                    \n  -- 'renice <target> <n>' : Set the nice value (-20 to 19, lower needs root). <target> is a PID,
+                    \n                            'tree:<pid>' (the process and its children) or 'name:<name>'.
+                    \n  -- 'ionice <target> <class> [level]' : Set the I/O priority, class none|realtime|best-effort|idle, level 0-7.
                     \n  -- 'sleep <proc_id>'    : Put a process to sleep, where <proc_id> is the process ID.
                     \n  -- 'resume <proc_id>'   : Resume a sleeping process, where <proc_id> is the process ID.
                     \n  -- 'count'              : Display process counts by state.
+                    \n  -- 'leaks'              : Processes whose memory only grew, from the recorded history.
+                    \n  -- 'leaks <seconds>'    : Sample for this many seconds, then list suspected leaks.
+                    \n  -- 'freeze'             : Keep a snapshot of the processes now (also done by p in 'display').
+                    \n  -- 'compare'            : Show processes started, exited or with big CPU/memory changes since 'freeze'.
+                    \n  -- 'overhead [n]'       : Time n refreshes (default 5) the old way, with every field and with the display columns.
                     \n  -- 'exit'               : To exit the Task Manager.
+                    \n  Start with '--config <file>', '--cpu-threshold <n>', '--memory-threshold-mb <n>',
+                    \n  '--io-threshold-mb-per-sec <n>', '--gui-refresh-ms <n>', '--cli-refresh-ms <n>', '--low-impact', '--sort memory|cpu|io',
+                    \n  '--memory-unit auto|bytes|kib|mib|gib', '--cpu-mode irix|solaris', '--group-by name|cgroup|container|unit|user|exe',
+                    \n  '--container <id-prefix>|host', '--unit <name>', '--user <name|uid>',
+                    \n  '--columns pid,name,cpu,rss,user,...' or '--record <file>' to override the config file (also TASKMANAGER_* env variables).
+                    \n  Run with 'watch [--interval <ms>] [--rules <file>] [--log stderr|syslog|<file>]' to check alerts headless.
                     \n"
                 );
             }
@@ -835,3 +3383,43 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snapshot::ProcessRecord;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn record(pid: u32, name: &str, cpu: f32, memory: u64) -> ProcessRecord {
+        serde_json::from_value(serde_json::json!({"pid": pid, "name": name, "mem": memory, "cpu": cpu, "st": "Sleep"})).unwrap()
+    }
+
+    fn check_alerts() -> CheckAlerts {
+        CheckAlerts {
+            alert_message: None,
+            cpu_threshold: 80.0,
+            memory_threshold: 2 * GIB,
+            io_threshold: 0.0,
+            cpu_mode: units::CpuMode::Irix,
+        }
+    }
+
+    #[test]
+    fn ignored_pids_are_not_alerted_again() {
+        let snapshot = Snapshot {
+            cpus: 4,
+            processes: vec![record(10, "quiet", 1.0, 1024), record(20, "busy", 95.0, 1024), record(30, "hog", 5.0, 3 * GIB)],
+            ..Snapshot::default()
+        };
+        let alerts = check_alerts();
+
+        let (pid, name, ..) = alerts.find_alert(&snapshot, &HashSet::new()).unwrap();
+        assert_eq!((pid, name.as_str()), (20, "busy"));
+
+        // after Ignore on the first one the next process over a limit is reported, not the same one again
+        let (pid, ..) = alerts.find_alert(&snapshot, &HashSet::from([20])).unwrap();
+        assert_eq!(pid, 30);
+        assert!(alerts.find_alert(&snapshot, &HashSet::from([20, 30])).is_none());
+    }
+}
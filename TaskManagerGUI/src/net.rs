@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+// NETWORK CONNECTIONS
+//
+// Sockets come from /proc/net/{tcp,tcp6,udp,udp6,unix}. Those tables only know the socket inode, so the
+// owning process is found by reading every /proc/<pid>/fd/* link that points at "socket:[<inode>]".
+// Other users' fd directories can only be read as root, their sockets show up without an owner.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+    Unix,
+}
+
+impl Protocol {
+    pub fn label(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Tcp6 => "tcp6",
+            Protocol::Udp => "udp",
+            Protocol::Udp6 => "udp6",
+            Protocol::Unix => "unix",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Connection {
+    pub protocol: Protocol,
+    pub local: String,  // "127.0.0.1:8080", "[::]:22" or a unix socket path
+    pub remote: String, // "*" when not connected
+    pub port: Option<u16>, // local port, none for unix sockets
+    pub state: String,  // "LISTEN", "ESTABLISHED", ...
+    pub inode: u64,
+    pub owners: Vec<(u32, String)>, // (pid, process name), several when the socket was shared with fork; empty when unreadable
+}
+
+impl Connection {
+    // Waiting for connections: a listening TCP / unix socket or a bound UDP socket
+    pub fn is_listening(&self) -> bool {
+        match self.protocol {
+            Protocol::Udp | Protocol::Udp6 => self.state == "UNCONN" && self.port.is_some_and(|p| p != 0),
+            _ => self.state == "LISTEN",
+        }
+    }
+
+    // "tcp 0.0.0.0:8080 -> * LISTEN", for fd lists
+    pub fn describe(&self) -> String {
+        format!("{} {} -> {} {}", self.protocol.label(), self.local, self.remote, self.state)
+    }
+
+    pub fn is_owned_by(&self, pid: u32) -> bool {
+        self.owners.iter().any(|(owner, _)| *owner == pid)
+    }
+
+    // "812,813,814" and "nginx", each process name once
+    pub fn owner_text(&self) -> (String, String) {
+        let pids: Vec<String> = self.owners.iter().map(|(pid, _)| pid.to_string()).collect();
+        let mut names: Vec<&str> = Vec::new();
+        for (_, name) in &self.owners {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        (pids.join(","), names.join(","))
+    }
+}
+
+// Every socket on the machine, with its owner where we can see it
+pub fn connections(proc_root: &Path, include_unix: bool) -> Result<Vec<Connection>, String> {
+    let mut connections = socket_table(proc_root, include_unix)?;
+    let owners = socket_owners(proc_root);
+    for connection in connections.iter_mut() {
+        if let Some(found) = owners.get(&connection.inode) {
+            connection.owners = found.clone();
+        }
+    }
+    Ok(connections)
+}
+
+// Sockets by inode without looking up owners, for describing one process' fds
+pub fn sockets_by_inode(proc_root: &Path) -> Result<HashMap<u64, Connection>, String> {
+    Ok(socket_table(proc_root, true)?.into_iter().map(|c| (c.inode, c)).collect())
+}
+
+fn socket_table(proc_root: &Path, include_unix: bool) -> Result<Vec<Connection>, String> {
+    let mut connections = Vec::new();
+    for protocol in [Protocol::Tcp, Protocol::Tcp6, Protocol::Udp, Protocol::Udp6] {
+        let path = proc_root.join("net").join(protocol.label());
+        match std::fs::read_to_string(&path) {
+            Ok(text) => connections.extend(parse_inet(&text, protocol)),
+            // no IPv6 in this kernel
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+    if include_unix {
+        let path = proc_root.join("net").join("unix");
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        connections.extend(parse_unix(&text));
+    }
+    Ok(connections)
+}
+
+// Processes with a socket listening on this local port, each pid once
+pub fn listeners(connections: &[Connection], port: u16) -> Vec<(u32, String)> {
+    let mut owners: Vec<(u32, String)> = Vec::new();
+    for connection in connections.iter().filter(|c| c.is_listening() && c.port == Some(port)) {
+        for (pid, name) in &connection.owners {
+            if !owners.iter().any(|(p, _)| p == pid) {
+                owners.push((*pid, name.clone()));
+            }
+        }
+    }
+    owners
+}
+
+// ":8080" or "8080" as used by `kill :8080`
+pub fn parse_port(text: &str) -> Option<u16> {
+    text.strip_prefix(':').unwrap_or(text).parse().ok()
+}
+
+// /proc/net/tcp style tables:
+//   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+//    0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 12345 ...
+fn parse_inet(text: &str, protocol: Protocol) -> Vec<Connection> {
+    let mut connections = Vec::new();
+    for line in text.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            continue;
+        }
+        let (local, port) = match parse_address(fields[1]) {
+            Some(address) => address,
+            None => continue,
+        };
+        let remote = match parse_address(fields[2]) {
+            Some((_, 0)) => "*".to_string(),
+            Some((address, _)) => address,
+            None => continue,
+        };
+        let state = match protocol {
+            Protocol::Udp | Protocol::Udp6 => udp_state(fields[3]),
+            _ => tcp_state(fields[3]),
+        };
+        connections.push(Connection {
+            protocol,
+            local,
+            remote,
+            port: Some(port),
+            state: state.to_string(),
+            inode: fields[9].parse().unwrap_or(0),
+            owners: Vec::new(),
+        });
+    }
+    connections
+}
+
+// "0100007F:1F90" -> ("127.0.0.1:8080", 8080). The address is the raw network order value printed
+// as host order words, so every 32 bit group is turned back into the bytes it was in memory.
+fn parse_address(text: &str) -> Option<(String, u16)> {
+    let (address, port) = text.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for chunk in address.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let address = match bytes.len() {
+        4 => format!("{}:{}", Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]), port),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes);
+            format!("[{}]:{}", Ipv6Addr::from(octets), port)
+        }
+        _ => return None,
+    };
+    Some((address, port))
+}
+
+// include/net/tcp_states.h
+fn tcp_state(code: &str) -> &'static str {
+    match code {
+        "01" => "ESTABLISHED",
+        "02" => "SYN_SENT",
+        "03" => "SYN_RECV",
+        "04" => "FIN_WAIT1",
+        "05" => "FIN_WAIT2",
+        "06" => "TIME_WAIT",
+        "07" => "CLOSE",
+        "08" => "CLOSE_WAIT",
+        "09" => "LAST_ACK",
+        "0A" => "LISTEN",
+        "0B" => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+// UDP reuses the TCP codes, but only "connected" and "not connected" mean something
+fn udp_state(code: &str) -> &'static str {
+    match code {
+        "01" => "ESTABLISHED",
+        _ => "UNCONN",
+    }
+}
+
+// /proc/net/unix:
+//   Num       RefCount Protocol Flags    Type St Inode Path
+//   0000000000000000: 00000002 00000000 00010000 0001 01 23456 /run/dbus/system_bus_socket
+fn parse_unix(text: &str) -> Vec<Connection> {
+    const ACCEPTING: u32 = 0x10000; // __SO_ACCEPTCON, set on listening sockets
+    let mut connections = Vec::new();
+    for line in text.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 7 {
+            continue;
+        }
+        let flags = u32::from_str_radix(fields[3], 16).unwrap_or(0);
+        let state = if flags & ACCEPTING != 0 {
+            "LISTEN"
+        } else {
+            match fields[5] {
+                "01" => "UNCONN",
+                "02" => "CONNECTING",
+                "03" => "CONNECTED",
+                "04" => "DISCONNECTING",
+                _ => "UNKNOWN",
+            }
+        };
+        connections.push(Connection {
+            protocol: Protocol::Unix,
+            local: fields.get(7).map_or("*".to_string(), |path| path.to_string()),
+            remote: "*".to_string(),
+            port: None,
+            state: state.to_string(),
+            inode: fields[6].parse().unwrap_or(0),
+            owners: Vec::new(),
+        });
+    }
+    connections
+}
+
+// socket inode -> every (pid, process name) holding it, from the /proc/<pid>/fd links we are allowed to read
+fn socket_owners(proc_root: &Path) -> HashMap<u64, Vec<(u32, String)>> {
+    let mut owners: HashMap<u64, Vec<(u32, String)>> = HashMap::new();
+    let entries = match std::fs::read_dir(proc_root) {
+        Ok(entries) => entries,
+        Err(_) => return owners,
+    };
+    for entry in entries.flatten() {
+        let pid: u32 = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let fds = match std::fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue, // gone, or not ours
+        };
+        let mut name = None;
+        for fd in fds.flatten() {
+            let target = match std::fs::read_link(fd.path()) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            let inode = target
+                .to_str()
+                .and_then(|t| t.strip_prefix("socket:["))
+                .and_then(|t| t.strip_suffix(']'))
+                .and_then(|t| t.parse::<u64>().ok());
+            if let Some(inode) = inode {
+                let name = name.get_or_insert_with(|| {
+                    std::fs::read_to_string(entry.path().join("comm")).map_or(String::new(), |comm| comm.trim().to_string())
+                });
+                // pre-fork servers share one listening socket between all workers, keep them all
+                let holders = owners.entry(inode).or_default();
+                if !holders.iter().any(|(holder, _)| *holder == pid) {
+                    holders.push((pid, name.clone()));
+                }
+            }
+        }
+    }
+    for holders in owners.values_mut() {
+        holders.sort();
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // Sample lines from a little endian machine, the only kind the hex addresses below are right for
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 12345 1 0000000000000000 100 0 0 10 0
+   1: 0F02000A:0016 0202000A:C3A2 01 00000000:00000000 02:0008F2A1 00000000     0        0 23456 4 0000000000000000 20 4 30 10 -1
+   2: 0F02000A:A1B2 0202000A:01BB 06 00000000:00000000 03:00001770 00000000     0        0 0 3 0000000000000000
+";
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000000000000:0016 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 3456 1 0000000000000000 100 0 0 10 0
+   1: 00000000000000000000000001000000:1F90 00000000000000000000000001000000:D2F0 01 00000000:00000000 00:00000000 00000000  1000        0 3457 1 0000000000000000 20 4 30 10 -1
+   2: B80D0120000000000000000005000000:01BB 000080FE000000000000000001000000:E1A8 08 00000000:00000000 00:00000000 00000000  1000        0 3458 1 0000000000000000 20 4 30 10 -1
+";
+    const UDP: &str = "   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  318: 00000000:0044 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 34567 2 0000000000000000 0
+  519: 0F02000A:E5C1 08080808:0035 01 00000000:00000000 00:00000000 00000000  1000        0 34568 2 0000000000000000 0
+  802: 0100007F:0000 00000000:0000 07 00000000:00000000 00:00000000 00000000  1000        0 34569 2 0000000000000000 0
+";
+    const UNIX: &str = "Num       RefCount Protocol Flags    Type St Inode Path
+0000000000000000: 00000002 00000000 00010000 0001 01 23456 /run/dbus/system_bus_socket
+0000000000000000: 00000003 00000000 00000000 0001 03 23457
+0000000000000000: 00000002 00000000 00000000 0002 01 23458 @/tmp/.X11-unix/X0
+0000000000000000: 00000002 00000000 00010000 0001 02 23459 /run/user/1000/bus
+";
+
+    // local, remote, state and inode of each parsed line
+    fn summary(connections: &[Connection]) -> Vec<(&str, &str, &str, u64)> {
+        connections.iter().map(|c| (c.local.as_str(), c.remote.as_str(), c.state.as_str(), c.inode)).collect()
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn tcp_addresses_and_states() {
+        let connections = parse_inet(TCP, Protocol::Tcp);
+        assert_eq!(summary(&connections), [
+            ("127.0.0.1:8080", "*", "LISTEN", 12345),
+            ("10.0.2.15:22", "10.0.2.2:50082", "ESTABLISHED", 23456),
+            ("10.0.2.15:41394", "10.0.2.2:443", "TIME_WAIT", 0),
+        ]);
+        assert_eq!(connections[0].port, Some(8080));
+        assert!(connections[0].is_listening());
+        assert!(!connections[1].is_listening());
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn tcp6_words_are_flipped_one_by_one() {
+        assert_eq!(summary(&parse_inet(TCP6, Protocol::Tcp6)), [
+            ("[::]:22", "*", "LISTEN", 3456),
+            ("[::1]:8080", "[::1]:54000", "ESTABLISHED", 3457),
+            ("[2001:db8::5]:443", "[fe80::1]:57768", "CLOSE_WAIT", 3458),
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn udp_is_bound_or_connected() {
+        let connections = parse_inet(UDP, Protocol::Udp);
+        assert_eq!(summary(&connections), [
+            ("0.0.0.0:68", "*", "UNCONN", 34567),
+            ("10.0.2.15:58817", "8.8.8.8:53", "ESTABLISHED", 34568),
+            ("127.0.0.1:0", "*", "UNCONN", 34569),
+        ]);
+        // a bound socket listens, one without a port yet does not
+        assert_eq!(connections.iter().map(Connection::is_listening).collect::<Vec<_>>(), [true, false, false]);
+    }
+
+    #[test]
+    fn states_and_bad_lines() {
+        assert_eq!(tcp_state("0A"), "LISTEN");
+        assert_eq!(tcp_state("0B"), "CLOSING");
+        assert_eq!(tcp_state("0C"), "UNKNOWN");
+        assert_eq!(udp_state("07"), "UNCONN");
+        assert_eq!(parse_address("0100007F"), None);
+        assert_eq!(parse_address("0100007G:0050"), None);
+        // short lines and bad addresses are skipped, not returned half filled
+        let text = "header\n   0: 0100007F:1F90 00000000:0000 0A\n   1: nonsense:1F90 00000000:0000 0A 0 0 0 0 0 1\n";
+        assert!(parse_inet(text, Protocol::Tcp).is_empty());
+    }
+
+    #[test]
+    fn unix_listening_flag_wins_over_the_state() {
+        let connections = parse_unix(UNIX);
+        assert_eq!(summary(&connections), [
+            ("/run/dbus/system_bus_socket", "*", "LISTEN", 23456),
+            ("*", "*", "CONNECTED", 23457),
+            ("@/tmp/.X11-unix/X0", "*", "UNCONN", 23458),
+            ("/run/user/1000/bus", "*", "LISTEN", 23459),
+        ]);
+        assert!(connections.iter().all(|c| c.port.is_none()));
+    }
+
+    // A fake /proc with net tables and fd links, removed again when the test ends
+    struct FakeProc {
+        root: PathBuf,
+    }
+
+    impl FakeProc {
+        fn new(test: &str) -> FakeProc {
+            let root = std::env::temp_dir().join(format!("taskmanager-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(root.join("net")).unwrap();
+            FakeProc { root }
+        }
+
+        fn table(&self, name: &str, text: &str) -> &Self {
+            std::fs::write(self.root.join("net").join(name), text).unwrap();
+            self
+        }
+
+        fn process(&self, pid: u32, comm: &str, fds: &[&str]) -> &Self {
+            let dir = self.root.join(pid.to_string());
+            std::fs::create_dir_all(dir.join("fd")).unwrap();
+            std::fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+            for (fd, target) in fds.iter().enumerate() {
+                std::os::unix::fs::symlink(target, dir.join("fd").join(fd.to_string())).unwrap();
+            }
+            self
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn socket_owners_from_fd_links() {
+        let proc = FakeProc::new("net-owners");
+        proc.process(812, "nginx", &["/dev/null", "socket:[12345]", "pipe:[777]", "socket:[23456]"])
+            .process(813, "nginx", &["socket:[12345]"])
+            .process(90, "sshd", &["socket:[23456]", "socket:[23456]"])
+            .process(91, "idle", &["anon_inode:[eventfd]", "socket:[oops]"]);
+        // a pid without a readable fd directory and entries that are not pids at all
+        std::fs::create_dir_all(proc.root.join("92")).unwrap();
+        std::fs::create_dir_all(proc.root.join("self")).unwrap();
+
+        let owners = socket_owners(&proc.root);
+        assert_eq!(owners.len(), 2);
+        assert_eq!(owners[&12345], [(812, "nginx".to_string()), (813, "nginx".to_string())]);
+        // sorted by pid, and a socket open twice in one process counts once
+        assert_eq!(owners[&23456], [(90, "sshd".to_string()), (812, "nginx".to_string())]);
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn connections_get_their_owners() {
+        let proc = FakeProc::new("net-connections");
+        proc.table("tcp", TCP).table("udp", UDP).table("unix", UNIX)
+            .process(812, "nginx", &["socket:[12345]"])
+            .process(813, "nginx", &["socket:[12345]"])
+            .process(500, "dbus-daemon", &["socket:[23459]"]);
+
+        // no tcp6 / udp6 tables is no IPv6, not an error
+        let connections = connections(&proc.root, false).unwrap();
+        assert_eq!(connections.len(), 6);
+        assert_eq!(listeners(&connections, 8080), [(812, "nginx".to_string()), (813, "nginx".to_string())]);
+        assert!(listeners(&connections, 22).is_empty()); // listed, but nobody we can see holds it
+
+        let with_unix = super::connections(&proc.root, true).unwrap();
+        assert_eq!(with_unix.len(), 10);
+        let bus = with_unix.iter().find(|c| c.is_owned_by(500)).unwrap();
+        assert_eq!((bus.protocol, bus.local.as_str(), bus.is_listening()), (Protocol::Unix, "/run/user/1000/bus", true));
+        std::fs::remove_file(proc.root.join("net").join("unix")).unwrap();
+        assert!(super::connections(&proc.root, true).unwrap_err().contains("cannot read"));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::Value;
+use crate::units::{self, MemoryUnit};
+
+// DESKTOP NOTIFICATIONS (org.freedesktop.Notifications over D-Bus)
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const APP_NAME: &str = "Task Manager";
+
+// how bad an alert is, mapped to the "urgency" hint of the notification spec
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Warning,  // one threshold exceeded -> normal urgency
+    Critical, // CPU and memory thresholds both exceeded -> critical urgency
+}
+
+impl Severity {
+    fn urgency(&self) -> u8 {
+        match self {
+            Severity::Warning => 1,
+            Severity::Critical => 2,
+        }
+    }
+}
+
+// what the user clicked on a notification
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NotificationAction {
+    Kill(u32),   // kill the process with this PID
+    Ignore(u32), // stop alerting about this PID
+}
+
+// what the signal thread saw on the bus
+enum Event {
+    Action(NotificationAction),
+    Closed(u32), // the notification for this PID was dismissed or expired
+}
+
+pub struct DesktopNotifier {
+    proxy: Proxy<'static>,
+    // notification id -> PID it was sent for, shared with the signal thread
+    sent: Arc<Mutex<HashMap<u32, u32>>>,
+    // PIDs that already have a notification on screen or were ignored
+    notified: HashSet<u32>,
+    ignored: HashSet<u32>,
+    events: Receiver<Event>,
+}
+
+impl DesktopNotifier {
+    // Connect to the user's session bus, from DBUS_SESSION_BUS_ADDRESS
+    pub fn connect() -> zbus::Result<Self> {
+        Self::connect_to(Connection::session()?)
+    }
+
+    // Any bus with something owning org.freedesktop.Notifications on it, the tests start their own
+    pub fn connect_to(connection: Connection) -> zbus::Result<Self> {
+        let proxy = Proxy::new(&connection, NOTIFICATIONS_NAME, NOTIFICATIONS_PATH, NOTIFICATIONS_NAME)?;
+        let sent = Arc::new(Mutex::new(HashMap::new()));
+        let (sender, events) = mpsc::channel();
+
+        // Listen in the background. The daemon sends ActionInvoked(id, action_key) for a button click and
+        // NotificationClosed(id, reason) when the notification goes away, after a click too. Both come
+        // through one iterator so a click is always handled before the close that follows it.
+        let mut signals = proxy.receive_all_signals()?;
+        let signal_sent = Arc::clone(&sent);
+        std::thread::spawn(move || {
+            for message in &mut signals {
+                let header = message.header();
+                let event = match header.member().map(|member| member.as_str()) {
+                    Some("ActionInvoked") => {
+                        let Ok((id, key)) = message.body().deserialize::<(u32, String)>() else {
+                            continue;
+                        };
+                        let pid = signal_sent.lock().unwrap().remove(&id);
+                        match (pid, key.as_str()) {
+                            (Some(pid), "kill") => Event::Action(NotificationAction::Kill(pid)),
+                            (Some(pid), "ignore") => Event::Action(NotificationAction::Ignore(pid)),
+                            _ => continue, // not one of ours
+                        }
+                    }
+                    Some("NotificationClosed") => {
+                        let Ok((id, _reason)) = message.body().deserialize::<(u32, u32)>() else {
+                            continue;
+                        };
+                        // already gone when a button was clicked
+                        match signal_sent.lock().unwrap().remove(&id) {
+                            Some(pid) => Event::Closed(pid),
+                            None => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+                if sender.send(event).is_err() {
+                    break; // notifier was dropped
+                }
+            }
+        });
+
+        Ok(DesktopNotifier {
+            proxy,
+            sent,
+            notified: HashSet::new(),
+            ignored: HashSet::new(),
+            events,
+        })
+    }
+
+    // Send an alert for a process, only once per PID until the user reacts to it
+    pub fn notify_alert(&mut self, pid: u32, name: &str, cpu: f32, memory: u64, io_rate: f64, severity: Severity) -> zbus::Result<()> {
+        if self.ignored.contains(&pid) || self.notified.contains(&pid) {
+            return Ok(());
+        }
+
+        let summary = format!("High resource usage: {}", name);
+        let body = format!(
+            "PID: {}\nCPU Usage: {:.2}%\nMemory Usage: {}\nDisk I/O: {}/s",
+            pid,
+            cpu,
+            units::format_bytes(memory, MemoryUnit::Auto),
+            units::format_bytes(io_rate as u64, MemoryUnit::Auto)
+        );
+        // actions are pairs of (key, label) as the spec requires
+        let actions = vec!["kill", "Kill", "ignore", "Ignore"];
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("urgency", Value::U8(severity.urgency()));
+
+        let id: u32 = self.proxy.call(
+            "Notify",
+            &(APP_NAME, 0u32, "dialog-warning", summary.as_str(), body.as_str(), actions, hints, -1i32),
+        )?;
+        self.sent.lock().unwrap().insert(id, pid);
+        self.notified.insert(pid);
+        Ok(())
+    }
+
+    // PIDs the user clicked Ignore for, they are not alerted about again
+    pub fn ignored(&self) -> &HashSet<u32> {
+        &self.ignored
+    }
+
+    // Collect every button the user clicked since the last call. A PID whose notification was dismissed or
+    // expired can be alerted about again.
+    pub fn poll_actions(&mut self) -> Vec<NotificationAction> {
+        let mut actions = Vec::new();
+        for event in self.events.try_iter() {
+            match event {
+                Event::Action(NotificationAction::Kill(pid)) => {
+                    self.notified.remove(&pid);
+                    actions.push(NotificationAction::Kill(pid));
+                }
+                Event::Action(NotificationAction::Ignore(pid)) => {
+                    self.notified.remove(&pid);
+                    self.ignored.insert(pid);
+                    actions.push(NotificationAction::Ignore(pid));
+                }
+                Event::Closed(pid) => {
+                    self.notified.remove(&pid);
+                }
+            }
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::time::{Duration, Instant};
+    use zbus::blocking::connection::Builder;
+    use zbus::zvariant::OwnedValue;
+
+    // A private session bus, killed when the test ends
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        fn start() -> Option<Bus> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?).read_line(&mut address).ok()?;
+            Some(Bus { daemon, address: address.trim().to_string() })
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    // Stand-in notification daemon, remembers what it was asked to show
+    struct StandIn {
+        shown: Arc<Mutex<Vec<(String, u8)>>>, // summary and urgency
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl StandIn {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &mut self,
+            _app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            _body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let urgency = hints.get("urgency").and_then(|value| u8::try_from(value).ok()).unwrap_or(0);
+            let mut shown = self.shown.lock().unwrap();
+            shown.push((summary, urgency));
+            shown.len() as u32 // ids start at 1
+        }
+    }
+
+    fn emit<B: serde::Serialize + zbus::zvariant::DynamicType>(daemon: &Connection, signal: &str, body: &B) {
+        daemon
+            .emit_signal(None::<&str>, NOTIFICATIONS_PATH, NOTIFICATIONS_NAME, signal, body)
+            .unwrap();
+    }
+
+    // The signal thread is asynchronous, poll until `done` or give up after a few seconds
+    fn wait_for(notifier: &mut DesktopNotifier, done: impl Fn(&DesktopNotifier, &[NotificationAction]) -> bool) -> Vec<NotificationAction> {
+        let started = Instant::now();
+        let mut actions = Vec::new();
+        while started.elapsed() < Duration::from_secs(5) {
+            actions.extend(notifier.poll_actions());
+            if done(notifier, &actions) {
+                return actions;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out, got {:?}", actions);
+    }
+
+    #[test]
+    fn alerts_actions_and_closing_on_a_local_bus() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let daemon = Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(NOTIFICATIONS_NAME)
+            .unwrap()
+            .serve_at(NOTIFICATIONS_PATH, StandIn { shown: Arc::clone(&shown) })
+            .unwrap()
+            .build()
+            .unwrap();
+        let connection = Builder::address(bus.address.as_str()).unwrap().build().unwrap();
+        let mut notifier = DesktopNotifier::connect_to(connection).unwrap();
+
+        // one notification per PID while it is on screen
+        notifier.notify_alert(100, "stress", 95.0, 1 << 30, 0.0, Severity::Critical).unwrap();
+        notifier.notify_alert(100, "stress", 96.0, 1 << 30, 0.0, Severity::Critical).unwrap();
+        assert_eq!(*shown.lock().unwrap(), vec![("High resource usage: stress".to_string(), 2)]);
+
+        // expired (reason 1): the PID can be alerted about again
+        emit(&daemon, "NotificationClosed", &(1u32, 1u32));
+        wait_for(&mut notifier, |n, _| !n.notified.contains(&100));
+        notifier.notify_alert(100, "stress", 97.0, 1 << 30, 0.0, Severity::Warning).unwrap();
+        assert_eq!(shown.lock().unwrap().len(), 2);
+        assert_eq!(shown.lock().unwrap()[1].1, 1);
+
+        // Kill, followed by the close the daemon sends after every click
+        emit(&daemon, "ActionInvoked", &(2u32, "kill"));
+        emit(&daemon, "NotificationClosed", &(2u32, 2u32));
+        let actions = wait_for(&mut notifier, |n, actions| !actions.is_empty() && !n.notified.contains(&100));
+        assert_eq!(actions, vec![NotificationAction::Kill(100)]);
+
+        // Ignore: no more notifications for that PID, even after it was closed
+        notifier.notify_alert(200, "leaky", 5.0, 8 << 30, 0.0, Severity::Warning).unwrap();
+        emit(&daemon, "ActionInvoked", &(3u32, "ignore"));
+        emit(&daemon, "NotificationClosed", &(3u32, 2u32));
+        let actions = wait_for(&mut notifier, |_, actions| !actions.is_empty());
+        assert_eq!(actions, vec![NotificationAction::Ignore(200)]);
+        notifier.notify_alert(200, "leaky", 5.0, 9 << 30, 0.0, Severity::Warning).unwrap();
+        assert_eq!(shown.lock().unwrap().len(), 3);
+
+        // signals for notifications someone else sent are left alone
+        emit(&daemon, "ActionInvoked", &(42u32, "kill"));
+        emit(&daemon, "NotificationClosed", &(42u32, 3u32));
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(notifier.poll_actions().is_empty());
+    }
+}
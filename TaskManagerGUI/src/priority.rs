@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::snapshot::ProcessRecord;
+use crate::users::Permission;
+
+// PROCESS PRIORITY (renice / ionice)
+//
+// Nice values go through setpriority(2), I/O priorities through the ioprio_set(2) syscall (glibc has no wrapper).
+// On Linux both only change one thread, so every thread in /proc/<pid>/task is changed, like `renice` on a whole
+// process would be expected to do. Without root a process can only be made nicer: a lower nice value and the
+// realtime I/O class need root.
+
+pub const NICE_MIN: i32 = -20;
+pub const NICE_MAX: i32 = 19;
+
+// (priority, nice) from fields 18 and 19 of /proc/<pid>/stat, priority is 20 + nice for normal processes
+pub fn read_priority(proc_root: &Path, pid: u32) -> Option<(i32, i32)> {
+    let stat = std::fs::read_to_string(proc_root.join(pid.to_string()).join("stat")).ok()?;
+    // the name can contain spaces, fields[0] after the last ')' is field 3
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    Some((fields.get(15)?.parse().ok()?, fields.get(16)?.parse().ok()?))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IoClass {
+    None, // not set, the kernel derives best-effort from the nice value
+    RealTime,
+    BestEffort,
+    Idle, // only gets disk time nobody else wants
+}
+
+pub const IO_CLASSES: [IoClass; 4] = [IoClass::None, IoClass::RealTime, IoClass::BestEffort, IoClass::Idle];
+
+impl IoClass {
+    pub fn parse(text: &str) -> Option<IoClass> {
+        match text.to_lowercase().as_str() {
+            "none" | "0" => Some(IoClass::None),
+            "realtime" | "rt" | "1" => Some(IoClass::RealTime),
+            "best-effort" | "be" | "2" => Some(IoClass::BestEffort),
+            "idle" | "3" => Some(IoClass::Idle),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            IoClass::None => "none",
+            IoClass::RealTime => "realtime",
+            IoClass::BestEffort => "best-effort",
+            IoClass::Idle => "idle",
+        }
+    }
+
+    // Only realtime and best-effort have levels, 0 (highest) to 7
+    pub fn has_level(self) -> bool {
+        matches!(self, IoClass::RealTime | IoClass::BestEffort)
+    }
+}
+
+// linux/ioprio.h: class in the top 3 bits, level in the low 13
+const IOPRIO_CLASS_SHIFT: i32 = 13;
+const IOPRIO_WHO_PROCESS: i32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct IoPriority {
+    pub class: IoClass,
+    pub level: u8,
+}
+
+impl IoPriority {
+    // "best-effort/4", "idle"
+    pub fn describe(&self) -> String {
+        if self.class.has_level() {
+            format!("{}/{}", self.class.label(), self.level)
+        } else {
+            self.class.label().to_string()
+        }
+    }
+}
+
+pub fn io_priority(pid: u32) -> Result<IoPriority, String> {
+    let value = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, pid as libc::c_int) };
+    if value < 0 {
+        return Err(explain(pid, std::io::Error::last_os_error(), "read the I/O priority of"));
+    }
+    let value = value as i32;
+    let class = match value >> IOPRIO_CLASS_SHIFT {
+        1 => IoClass::RealTime,
+        2 => IoClass::BestEffort,
+        3 => IoClass::Idle,
+        _ => IoClass::None,
+    };
+    Ok(IoPriority { class, level: (value & ((1 << IOPRIO_CLASS_SHIFT) - 1)) as u8 })
+}
+
+// Set the nice value of every thread of a process
+pub fn renice(proc_root: &Path, pid: u32, nice: i32) -> Result<(), String> {
+    if !(NICE_MIN..=NICE_MAX).contains(&nice) {
+        return Err(format!("nice value {} is out of range ({} to {})", nice, NICE_MIN, NICE_MAX));
+    }
+    crate::users::check_owner(proc_root, pid, Permission::Schedule, "renice")?;
+    for tid in thread_ids(proc_root, pid) {
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } != 0 {
+            let error = std::io::Error::last_os_error();
+            // the kernel says EACCES when an unprivileged user asks for a lower nice value
+            if error.raw_os_error() == Some(libc::EACCES) {
+                let current = read_priority(proc_root, pid).map_or("its current value".to_string(), |(_, nice)| nice.to_string());
+                return Err(format!(
+                    "permission denied: lowering the nice value of PID {} from {} to {} needs root (CAP_SYS_NICE)",
+                    pid, current, nice
+                ));
+            }
+            return Err(explain(pid, error, "renice"));
+        }
+    }
+    Ok(())
+}
+
+// Set the I/O class and level of every thread of a process
+pub fn ionice(proc_root: &Path, pid: u32, class: IoClass, level: u8) -> Result<(), String> {
+    if level > 7 {
+        return Err(format!("I/O priority level {} is out of range (0 to 7)", level));
+    }
+    crate::users::check_owner(proc_root, pid, Permission::IoPriority, "change the I/O priority of")?;
+    let class_value = match class {
+        IoClass::None => 0,
+        IoClass::RealTime => 1,
+        IoClass::BestEffort => 2,
+        IoClass::Idle => 3,
+    };
+    let level = if class.has_level() { level as i32 } else { 0 };
+    let value = (class_value << IOPRIO_CLASS_SHIFT) | level;
+    for tid in thread_ids(proc_root, pid) {
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid as libc::c_int, value) } != 0 {
+            let error = std::io::Error::last_os_error();
+            if class == IoClass::RealTime && error.raw_os_error() == Some(libc::EPERM) {
+                return Err(format!("permission denied: the realtime I/O class for PID {} needs root", pid));
+            }
+            return Err(explain(pid, error, "change the I/O priority of"));
+        }
+    }
+    Ok(())
+}
+
+// Threads of a process, or just the pid when the task directory can't be read (the syscall will say why)
+pub fn thread_ids(proc_root: &Path, pid: u32) -> Vec<u32> {
+    let mut tids: Vec<u32> = match std::fs::read_dir(proc_root.join(pid.to_string()).join("task")) {
+        Ok(entries) => entries.flatten().filter_map(|e| e.file_name().to_str().and_then(|name| name.parse().ok())).collect(),
+        Err(_) => Vec::new(),
+    };
+    if tids.is_empty() {
+        tids.push(pid);
+    }
+    tids.sort();
+    tids
+}
+
+// Turn the errno of a failed call into something the user can act on
+pub fn explain(pid: u32, error: std::io::Error, action: &str) -> String {
+    match error.raw_os_error() {
+        Some(libc::EPERM) | Some(libc::EACCES) => format!("permission denied: cannot {} PID {}, it belongs to another user", action, pid),
+        Some(libc::ESRCH) => format!("no process with PID {}", pid),
+        _ => format!("cannot {} PID {}: {}", action, pid, error),
+    }
+}
+
+// Which processes a renice / ionice applies to
+#[derive(Clone, PartialEq, Debug)]
+pub enum Target {
+    Pid(u32),
+    Tree(u32),    // the process and all its descendants
+    Name(String), // every process with this exact name
+}
+
+impl Target {
+    // "1234", "tree:1234" or "name:java"
+    pub fn parse(text: &str) -> Option<Target> {
+        if let Some(pid) = text.strip_prefix("tree:") {
+            return pid.parse().ok().map(Target::Tree);
+        }
+        if let Some(name) = text.strip_prefix("name:") {
+            return Some(Target::Name(name.to_string())).filter(|_| !name.is_empty());
+        }
+        text.parse().ok().map(Target::Pid)
+    }
+
+    // (pid, name) of every matching process, threads left out since renice / ionice cover them already
+    pub fn resolve(&self, processes: &[ProcessRecord]) -> Vec<(u32, String)> {
+        let processes: Vec<&ProcessRecord> = processes.iter().filter(|p| !p.is_thread).collect();
+        match self {
+            Target::Pid(pid) => processes.iter().filter(|p| p.pid == *pid).map(|p| (p.pid, p.name.clone())).collect(),
+            Target::Name(name) => processes.iter().filter(|p| &p.name == name).map(|p| (p.pid, p.name.clone())).collect(),
+            Target::Tree(root) => {
+                let mut children: HashMap<u32, Vec<&ProcessRecord>> = HashMap::new();
+                for process in &processes {
+                    if let Some(parent) = process.parent {
+                        children.entry(parent).or_default().push(process);
+                    }
+                }
+                // breadth first from the root, parents before their children
+                let mut found: Vec<(u32, String)> = processes.iter().filter(|p| p.pid == *root).map(|p| (p.pid, p.name.clone())).collect();
+                let mut next = 0;
+                while next < found.len() {
+                    if let Some(kids) = children.get(&found[next].0) {
+                        found.extend(kids.iter().map(|p| (p.pid, p.name.clone())));
+                    }
+                    next += 1;
+                }
+                found
+            }
+        }
+    }
+}
+
+// Run a renice / ionice on every target, one line per process saying what happened
+pub fn apply(targets: &[(u32, String)], done: &str, action: impl Fn(u32) -> Result<(), String>) -> Vec<String> {
+    targets
+        .iter()
+        .map(|(pid, name)| match action(*pid) {
+            Ok(()) => format!("PID {} ({}): {}", pid, name, done),
+            Err(e) => format!("PID {} ({}): {}", pid, name, e),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // A fake /proc under the temp directory, removed again when the test ends
+    struct FakeProc {
+        root: PathBuf,
+    }
+
+    impl FakeProc {
+        fn new(test: &str) -> FakeProc {
+            let root = std::env::temp_dir().join(format!("taskmanager-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            FakeProc { root }
+        }
+
+        fn stat(&self, pid: u32, text: &str) -> &Self {
+            std::fs::create_dir_all(self.root.join(pid.to_string())).unwrap();
+            std::fs::write(self.root.join(pid.to_string()).join("stat"), text).unwrap();
+            self
+        }
+
+        fn tasks(&self, pid: u32, tids: &[&str]) -> &Self {
+            for tid in tids {
+                std::fs::create_dir_all(self.root.join(pid.to_string()).join("task").join(tid)).unwrap();
+            }
+            self
+        }
+    }
+
+    impl Drop for FakeProc {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn record(pid: u32, parent: Option<u32>, name: &str) -> ProcessRecord {
+        serde_json::from_value(serde_json::json!({"pid": pid, "ppid": parent, "name": name, "mem": 1024, "cpu": 0.0, "st": "Sleep"})).unwrap()
+    }
+
+    #[test]
+    fn priority_and_nice_after_the_last_parenthesis() {
+        let proc = FakeProc::new("priority-stat");
+        // priority (field 18) and nice (field 19) of a niced process, and of a realtime one
+        proc.stat(100, "100 (a) b (c) S 1 100 100 0 -1 4194560 120 0 0 0 5 3 0 0 30 10 1 0 500 1000 200\n")
+            .stat(200, "200 (rtkit) S 1 200 200 0 -1 4194560 0 0 0 0 0 0 0 0 -51 0 1 0 500 1000 200\n")
+            .stat(300, "300 (short) S 1 300");
+        assert_eq!(read_priority(&proc.root, 100), Some((30, 10)));
+        assert_eq!(read_priority(&proc.root, 200), Some((-51, 0)));
+        assert_eq!(read_priority(&proc.root, 300), None);
+        assert_eq!(read_priority(&proc.root, 400), None);
+    }
+
+    #[test]
+    fn io_class_names_and_numbers() {
+        for (text, class) in [
+            ("none", IoClass::None),
+            ("0", IoClass::None),
+            ("RealTime", IoClass::RealTime),
+            ("rt", IoClass::RealTime),
+            ("1", IoClass::RealTime),
+            ("best-effort", IoClass::BestEffort),
+            ("BE", IoClass::BestEffort),
+            ("2", IoClass::BestEffort),
+            ("idle", IoClass::Idle),
+            ("3", IoClass::Idle),
+        ] {
+            assert_eq!(IoClass::parse(text), Some(class), "{}", text);
+        }
+        assert_eq!(IoClass::parse("besteffort"), None);
+        assert_eq!(IoClass::parse("4"), None);
+        // every label parses back to its class
+        assert!(IO_CLASSES.iter().all(|class| IoClass::parse(class.label()) == Some(*class)));
+        assert_eq!(IoPriority { class: IoClass::BestEffort, level: 4 }.describe(), "best-effort/4");
+        assert_eq!(IoPriority { class: IoClass::Idle, level: 4 }.describe(), "idle");
+    }
+
+    #[test]
+    fn thread_ids_from_the_task_directory() {
+        let proc = FakeProc::new("priority-tasks");
+        proc.tasks(100, &["100", "1003", "101", "not-a-tid"]);
+        assert_eq!(thread_ids(&proc.root, 100), [100, 101, 1003]);
+        // unreadable or gone: just the pid, the syscall reports the real error
+        assert_eq!(thread_ids(&proc.root, 200), [200]);
+    }
+
+    #[test]
+    fn targets_parse() {
+        assert_eq!(Target::parse("1234"), Some(Target::Pid(1234)));
+        assert_eq!(Target::parse("tree:1"), Some(Target::Tree(1)));
+        assert_eq!(Target::parse("name:java"), Some(Target::Name("java".to_string())));
+        assert_eq!(Target::parse("name:"), None);
+        assert_eq!(Target::parse("tree:java"), None);
+        assert_eq!(Target::parse("java"), None);
+    }
+
+    #[test]
+    fn tree_and_name_targets() {
+        let mut thread = record(11, Some(10), "nginx");
+        thread.is_thread = true;
+        let processes = vec![
+            record(1, None, "systemd"),
+            record(12, Some(10), "nginx"),
+            record(10, Some(1), "nginx"),
+            thread,
+            record(20, Some(12), "helper"),
+            record(30, Some(1), "nginx-exporter"),
+            record(31, Some(30), "nginx"),
+        ];
+        let pids = |target: Target| target.resolve(&processes).into_iter().map(|(pid, _)| pid).collect::<Vec<u32>>();
+
+        // parents before their children, the thread is covered by its process
+        assert_eq!(pids(Target::Tree(10)), [10, 12, 20]);
+        assert_eq!(pids(Target::Tree(1)), [1, 10, 30, 12, 31, 20]);
+        assert_eq!(pids(Target::Tree(20)), [20]);
+        assert!(pids(Target::Tree(99)).is_empty());
+        // the exact name only
+        assert_eq!(pids(Target::Name("nginx".to_string())), [12, 10, 31]);
+        assert_eq!(pids(Target::Pid(11)), Vec::<u32>::new());
+        assert_eq!(Target::Pid(20).resolve(&processes), [(20, "helper".to_string())]);
+    }
+
+    #[test]
+    fn apply_reports_every_target() {
+        let targets = [(10, "nginx".to_string()), (12, "nginx".to_string())];
+        let lines = apply(&targets, "nice 5", |pid| if pid == 10 { Ok(()) } else { Err(format!("no process with PID {}", pid)) });
+        assert_eq!(lines, ["PID 10 (nginx): nice 5", "PID 12 (nginx): no process with PID 12"]);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use sysinfo::{CpuRefreshKind, MemoryRefreshKind, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use crate::columns::Column;
+use crate::snapshot::{GroupBy, ProcessFilter};
+
+// REFRESH PLANNER
+//
+// System::refresh_all() reads everything sysinfo knows about every process, including environment, working
+// directory and root which nothing here shows. A plan asks sysinfo only for what the current view needs, and
+// also tells Snapshot::capture which of our own per-process /proc reads (priority, owner, affinity, cgroup)
+// to do. CPU and memory are always refreshed. Command lines only change with exec, so they are read once per
+// process and read again on every refresh only for `detail_pids`: the rows on screen and the selected one.
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RefreshPlan {
+    pub disk: bool,     // /proc/<pid>/io, for disk columns, sorting by I/O and the I/O alert
+    pub command: bool,  // command line and executable
+    pub priority: bool, // nice and priority from /proc/<pid>/stat
+    pub owner: bool,    // UIDs from /proc/<pid>/status
+    pub affinity: bool,
+    pub cgroup: bool, // also needed for the container and unit
+    pub detail_pids: HashSet<u32>,
+}
+
+impl RefreshPlan {
+    // Every field of a snapshot: CLI commands, history recording and replays of it need them all
+    pub fn everything() -> Self {
+        RefreshPlan {
+            disk: true,
+            command: true,
+            priority: true,
+            owner: true,
+            affinity: true,
+            cgroup: true,
+            detail_pids: HashSet::new(),
+        }
+    }
+
+    // What the process table needs for these columns, grouping, filter and sort order
+    pub fn for_view(columns: &[Column], group_by: GroupBy, filter: &ProcessFilter, sort_by_io: bool, io_alerts: bool) -> Self {
+        let shown = |wanted: &[Column]| columns.iter().any(|column| wanted.contains(column));
+        RefreshPlan {
+            disk: sort_by_io
+                || io_alerts
+                || shown(&[Column::Io, Column::Read, Column::Write, Column::ReadTotal, Column::WriteTotal]),
+            command: filter.command.is_some() || group_by == GroupBy::Exe || shown(&[Column::Command, Column::Exe]),
+            priority: shown(&[Column::Nice, Column::Priority]),
+            owner: filter.user.is_some() || group_by == GroupBy::User || shown(&[Column::User, Column::Uid]),
+            affinity: shown(&[Column::Affinity]),
+            cgroup: filter.container.is_some()
+                || filter.unit.is_some()
+                || matches!(group_by, GroupBy::Cgroup | GroupBy::Container | GroupBy::Unit)
+                || shown(&[Column::Container, Column::Unit, Column::Cgroup]),
+            detail_pids: HashSet::new(),
+        }
+    }
+
+    // Replaces System::refresh_all()
+    pub fn refresh(&self, system: &mut System) {
+        system.refresh_memory_specifics(MemoryRefreshKind::everything());
+        system.refresh_cpu_specifics(CpuRefreshKind::new().with_cpu_usage());
+        let mut kind = ProcessRefreshKind::new().with_cpu().with_memory();
+        if self.disk {
+            kind = kind.with_disk_usage();
+        }
+        if self.command {
+            kind = kind.with_cmd(UpdateKind::OnlyIfNotSet).with_exe(UpdateKind::OnlyIfNotSet);
+        }
+        // true drops processes that exited, refresh_all() keeps them around
+        system.refresh_processes_specifics(ProcessesToUpdate::All, true, kind);
+    }
+
+    // "disk, command, owner" for the overhead report
+    pub fn describe(&self) -> String {
+        let parts: Vec<&str> = [
+            (self.disk, "disk"),
+            (self.command, "command"),
+            (self.priority, "priority"),
+            (self.owner, "owner"),
+            (self.affinity, "affinity"),
+            (self.cgroup, "cgroup"),
+        ]
+        .iter()
+        .filter(|(wanted, _)| *wanted)
+        .map(|(_, name)| *name)
+        .collect();
+        if parts.is_empty() {
+            "CPU and memory only".to_string()
+        } else {
+            format!("CPU, memory, {}", parts.join(", "))
+        }
+    }
+}
+
+// What one refresh + capture cost the sampler
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SampleCost {
+    pub elapsed: Duration,  // wall time of the refresh and capture
+    pub cpu_time: Duration, // CPU time of the sampling thread for it
+    pub cpu_percent: f32,   // sampling thread CPU since the previous sample, in % of one core
+    pub processes: usize,
+}
+
+impl SampleCost {
+    // "12.3 ms per refresh (9.8 ms CPU), 2.5% of one core, 312 processes"
+    pub fn describe(&self) -> String {
+        format!(
+            "{:.1} ms per refresh ({:.1} ms CPU), {:.1}% of one core, {} processes",
+            self.elapsed.as_secs_f64() * 1000.0,
+            self.cpu_time.as_secs_f64() * 1000.0,
+            self.cpu_percent,
+            self.processes
+        )
+    }
+}
+
+// CPU time the calling thread has used so far, the first field of /proc/thread-self/schedstat in nanoseconds
+pub fn thread_cpu_time() -> Duration {
+    std::fs::read_to_string("/proc/thread-self/schedstat")
+        .ok()
+        .and_then(|text| text.split_whitespace().next()?.parse().ok())
+        .map(Duration::from_nanos)
+        .unwrap_or_default()
+}
+
+// The task manager's own CPU and memory, for the status bar. CPU is averaged over at least a second,
+// shorter windows jump around too much to read.
+pub struct SelfUsage {
+    last: Option<(Instant, Duration)>, // when and at which CPU time the percentage was last computed
+    pub cpu_percent: f32, // all our threads, in % of one core
+    pub rss: u64,         // in bytes
+}
+
+impl SelfUsage {
+    pub fn new() -> Self {
+        SelfUsage { last: None, cpu_percent: 0.0, rss: 0 }
+    }
+
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        if self.last.is_some_and(|(at, _)| now - at < Duration::from_secs(1)) {
+            return;
+        }
+        let cpu_time = process_cpu_time();
+        if let Some((at, before)) = self.last {
+            self.cpu_percent = (cpu_time.saturating_sub(before).as_secs_f64() / (now - at).as_secs_f64() * 100.0) as f32;
+        }
+        self.last = Some((now, cpu_time));
+        self.rss = read_own_rss().unwrap_or(0);
+    }
+}
+
+// User + system time of all our threads
+fn process_cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Duration::ZERO;
+    }
+    let micros = |time: libc::timeval| time.tv_sec as u64 * 1_000_000 + time.tv_usec as u64;
+    Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+}
+
+// "VmRSS:     12345 kB" in /proc/self/status (getrusage only knows the peak)
+fn read_own_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().next()?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns::ALL_COLUMNS;
+
+    // the plan for a view with only these columns, grouped by name and not filtered
+    fn plan(columns: &[Column]) -> RefreshPlan {
+        RefreshPlan::for_view(columns, GroupBy::Name, &ProcessFilter::default(), false, false)
+    }
+
+    #[test]
+    fn columns_decide_the_reads() {
+        assert_eq!(plan(&[Column::Pid, Column::Name, Column::Memory, Column::Cpu]).describe(), "CPU and memory only");
+        for column in [Column::Io, Column::Read, Column::Write, Column::ReadTotal, Column::WriteTotal] {
+            assert_eq!(plan(&[Column::Name, column]).describe(), "CPU, memory, disk", "{:?}", column);
+        }
+        assert_eq!(plan(&[Column::Command]).describe(), "CPU, memory, command");
+        assert_eq!(plan(&[Column::Exe]).describe(), "CPU, memory, command");
+        assert_eq!(plan(&[Column::Nice]).describe(), "CPU, memory, priority");
+        assert_eq!(plan(&[Column::Priority]).describe(), "CPU, memory, priority");
+        assert_eq!(plan(&[Column::User]).describe(), "CPU, memory, owner");
+        assert_eq!(plan(&[Column::Uid]).describe(), "CPU, memory, owner");
+        assert_eq!(plan(&[Column::Affinity]).describe(), "CPU, memory, affinity");
+        // the container and unit come from the cgroup path
+        for column in [Column::Container, Column::Unit, Column::Cgroup] {
+            assert_eq!(plan(&[column]).describe(), "CPU, memory, cgroup", "{:?}", column);
+        }
+        assert_eq!(plan(&ALL_COLUMNS), RefreshPlan::everything());
+    }
+
+    #[test]
+    fn cheap_columns_need_nothing_per_process() {
+        // what low impact mode keeps, command lines are read once per process
+        let cheap: Vec<Column> = ALL_COLUMNS.iter().copied().filter(|column| column.is_cheap()).collect();
+        assert_eq!(plan(&cheap).describe(), "CPU, memory, command");
+    }
+
+    #[test]
+    fn grouping_needs_its_key() {
+        let grouped = |group_by: GroupBy| RefreshPlan::for_view(&[Column::Name], group_by, &ProcessFilter::default(), false, false);
+        assert_eq!(grouped(GroupBy::Name).describe(), "CPU and memory only");
+        assert_eq!(grouped(GroupBy::Exe).describe(), "CPU, memory, command");
+        assert_eq!(grouped(GroupBy::User).describe(), "CPU, memory, owner");
+        for group_by in [GroupBy::Cgroup, GroupBy::Container, GroupBy::Unit] {
+            assert_eq!(grouped(group_by).describe(), "CPU, memory, cgroup", "{:?}", group_by);
+        }
+    }
+
+    #[test]
+    fn filters_need_what_they_match_on() {
+        let filtered = |filter: ProcessFilter| RefreshPlan::for_view(&[Column::Name], GroupBy::Name, &filter, false, false);
+        let some = || Some("x".to_string());
+        assert_eq!(filtered(ProcessFilter { command: some(), ..ProcessFilter::default() }).describe(), "CPU, memory, command");
+        assert_eq!(filtered(ProcessFilter { user: some(), ..ProcessFilter::default() }).describe(), "CPU, memory, owner");
+        assert_eq!(filtered(ProcessFilter { container: some(), ..ProcessFilter::default() }).describe(), "CPU, memory, cgroup");
+        assert_eq!(filtered(ProcessFilter { unit: some(), ..ProcessFilter::default() }).describe(), "CPU, memory, cgroup");
+        // the status comes with every refresh
+        assert_eq!(filtered(ProcessFilter { status: some(), ..ProcessFilter::default() }).describe(), "CPU and memory only");
+    }
+
+    #[test]
+    fn sorting_and_alerting_by_io_need_disk() {
+        let filter = ProcessFilter::default();
+        assert!(RefreshPlan::for_view(&[Column::Name], GroupBy::Name, &filter, true, false).disk);
+        assert!(RefreshPlan::for_view(&[Column::Name], GroupBy::Name, &filter, false, true).disk);
+        let both = RefreshPlan::for_view(&[Column::Name, Column::User], GroupBy::Cgroup, &filter, true, true);
+        assert_eq!(both.describe(), "CPU, memory, disk, owner, cgroup");
+        assert!(both.detail_pids.is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::System;
+use crate::refresh::{self, RefreshPlan, SampleCost};
+use crate::snapshot::Snapshot;
+
+// BACKGROUND SAMPLER
+//
+// The GUI windows never refresh sysinfo in their update(): one thread owns the System, refreshes it every
+// interval and sends an immutable Snapshot over a channel. After every send it calls `wake` (the GUI passes
+// ctx.request_repaint), so a window only repaints when there is new data or input from the user.
+// A paused sampler (the tree view in low impact mode, window in the background) does not refresh at all until
+// resumed. A new interval wakes the thread up, so going from a slow background interval back to the normal one
+// refreshes right away.
+
+pub struct Sampler {
+    receiver: Receiver<(Snapshot, SampleCost)>,
+    interval_ms: Arc<AtomicU64>, // read by the thread before every sleep, so a new interval applies from the next tick
+    plan: Arc<Mutex<RefreshPlan>>, // what the window shows right now, read before every refresh
+    paused: Arc<AtomicBool>,
+    thread: std::thread::Thread, // unparked to resume
+    cost: Option<SampleCost>, // of the newest snapshot
+}
+
+impl Sampler {
+    pub fn start(interval: Duration, wake: impl Fn() + Send + 'static) -> Sampler {
+        let (sender, receiver) = mpsc::channel();
+        let interval_ms = Arc::new(AtomicU64::new(interval.as_millis() as u64));
+        // everything until the window says what it shows
+        let plan = Arc::new(Mutex::new(RefreshPlan::everything()));
+        let paused = Arc::new(AtomicBool::new(false));
+        let (thread_interval, thread_plan, thread_paused) = (Arc::clone(&interval_ms), Arc::clone(&plan), Arc::clone(&paused));
+        let handle = std::thread::Builder::new()
+            .name("sampler".to_string())
+            .spawn(move || {
+                let mut system = System::new();
+                let mut previous = (Instant::now(), refresh::thread_cpu_time());
+                loop {
+                    if thread_paused.load(Ordering::Relaxed) {
+                        // set_paused(false) wakes us up, the timeout is only in case that wake up got lost
+                        std::thread::park_timeout(Duration::from_secs(1));
+                        continue;
+                    }
+                    let started = (Instant::now(), refresh::thread_cpu_time());
+                    let plan = thread_plan.lock().unwrap().clone();
+                    plan.refresh(&mut system);
+                    let snapshot = Snapshot::capture_planned(&system, &plan);
+                    let done = (Instant::now(), refresh::thread_cpu_time());
+                    let cost = SampleCost {
+                        elapsed: done.0 - started.0,
+                        cpu_time: done.1.saturating_sub(started.1),
+                        // the time asleep counts too, so this is the sampler's share of one core
+                        cpu_percent: (done.1.saturating_sub(previous.1).as_secs_f64()
+                            / (done.0 - previous.0).as_secs_f64().max(0.001)
+                            * 100.0) as f32,
+                        processes: snapshot.processes.len(),
+                    };
+                    previous = done;
+                    // the window was closed, nobody reads the snapshots anymore
+                    if sender.send((snapshot, cost)).is_err() {
+                        break;
+                    }
+                    wake();
+                    let interval = Duration::from_millis(thread_interval.load(Ordering::Relaxed));
+                    // set_interval() and set_paused() unpark us early
+                    std::thread::park_timeout(interval.saturating_sub(started.0.elapsed()));
+                }
+            })
+            .expect("Failed to start the sampler thread");
+        Sampler { receiver, interval_ms, plan, paused, thread: handle.thread().clone(), cost: None }
+    }
+
+    pub fn set_interval(&self, interval: Duration) {
+        let ms = interval.as_millis() as u64;
+        if self.interval_ms.swap(ms, Ordering::Relaxed) != ms {
+            self.thread.unpark();
+        }
+    }
+
+    // Used from the next refresh on
+    pub fn set_plan(&self, plan: RefreshPlan) {
+        *self.plan.lock().unwrap() = plan;
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::Relaxed) && !paused {
+            self.thread.unpark();
+        }
+    }
+
+    // The newest snapshot since the last call; older ones are dropped when the GUI could not keep up
+    pub fn latest(&mut self) -> Option<Snapshot> {
+        let (snapshot, cost) = self.receiver.try_iter().last()?;
+        self.cost = Some(cost);
+        Some(snapshot)
+    }
+
+    pub fn cost(&self) -> Option<SampleCost> {
+        self.cost
+    }
+}
+
+impl Drop for Sampler {
+    // a paused thread would never notice the window is gone, let it run into the closed channel
+    fn drop(&mut self) {
+        self.set_paused(false);
+    }
+}
@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Process, System};
+use crate::container::ContainerRef;
+use crate::refresh::RefreshPlan;
+use crate::systemd::Unit;
+
+// PROCESS SNAPSHOTS
+//
+// A plain copy of what the views need from sysinfo at one point in time, so the same
+// display code can show live data or data read back from a history file.
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProcessRecord {
+    pub pid: u32,
+    #[serde(rename = "ppid", default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u32>,
+    pub name: String,
+    #[serde(rename = "mem")]
+    pub memory: u64, // in bytes
+    pub cpu: f32,    // as reported by sysinfo (100% = one core)
+    #[serde(rename = "st")]
+    pub status: String, // e.g. "Run", "Sleep"
+    #[serde(default)]
+    pub threads: u32, // entries in /proc/<pid>/task, 0 for threads themselves
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_thread: bool, // sysinfo also lists every thread as its own "process"
+    #[serde(rename = "rd", default)]
+    pub read_bytes: u64, // total read from disk since the process started (/proc/<pid>/io)
+    #[serde(rename = "wr", default)]
+    pub written_bytes: u64,
+    #[serde(rename = "ni", default)]
+    pub nice: i32, // -20 (greedy) to 19 (nice), from /proc/<pid>/stat
+    #[serde(rename = "pri", default)]
+    pub priority: i32, // kernel priority, 20 + nice for normal processes, negative for realtime
+    #[serde(rename = "aff", default, skip_serializing_if = "Option::is_none")]
+    pub affinity: Option<String>, // CPU list like "0-3,8" when pinned, None when it may run on every CPU
+    #[serde(rename = "cg", default, skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<String>, // cgroup v2 path, e.g. "/system.slice/nginx.service"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>, // real UID, who started the process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub euid: Option<u32>, // effective UID, whose permissions it runs with (differs for setuid programs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>, // name of the real UID, kept so a replay on another machine shows the same names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>, // full command line, arguments separated by spaces, None for kernel threads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>, // path of the executable, None when /proc/<pid>/exe can't be read (other users, kernel threads)
+    #[serde(skip)]
+    pub read_rate: f64, // bytes per second since the previous snapshot, see `compute_io_rates`
+    #[serde(skip)]
+    pub write_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Snapshot {
+    #[serde(rename = "t")]
+    pub timestamp_ms: u64, // milliseconds since the unix epoch
+    pub total_memory: u64, // in bytes
+    pub cpus: usize,
+    #[serde(rename = "procs")]
+    pub processes: Vec<ProcessRecord>,
+    #[serde(default)]
+    pub overview: SystemOverview,
+}
+
+// System wide numbers for the top-style summary above the process list
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SystemOverview {
+    pub load_average: [f64; 3], // 1, 5 and 15 minutes
+    pub uptime_secs: u64,
+    pub used_memory: u64, // in bytes, like total_memory
+    pub available_memory: u64,
+    pub cached_memory: u64, // page cache, from /proc/meminfo
+    pub total_swap: u64,
+    pub used_swap: u64,
+    pub core_usage: Vec<f32>, // percent per logical CPU
+}
+
+impl SystemOverview {
+    fn capture(system: &System) -> Self {
+        let load = System::load_average();
+        SystemOverview {
+            load_average: [load.one, load.five, load.fifteen],
+            uptime_secs: System::uptime(),
+            used_memory: system.used_memory(),
+            available_memory: system.available_memory(),
+            cached_memory: read_cached_memory().unwrap_or(0),
+            total_swap: system.total_swap(),
+            used_swap: system.used_swap(),
+            core_usage: system.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
+        }
+    }
+}
+
+// /proc/<pid>/cmdline has the arguments separated by NUL bytes, empty for kernel threads
+fn read_cmdline(proc_root: &Path, pid: u32) -> Option<String> {
+    let bytes = std::fs::read(proc_root.join(pid.to_string()).join("cmdline")).ok()?;
+    let args: Vec<String> = bytes
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect();
+    Some(args.join(" ")).filter(|cmd| !cmd.is_empty())
+}
+
+fn read_exe(proc_root: &Path, pid: u32) -> Option<String> {
+    std::fs::read_link(proc_root.join(pid.to_string()).join("exe")).ok().map(|path| path.display().to_string())
+}
+
+// sysinfo has no page cache number, so read the "Cached:" line (in kB) ourselves
+fn read_cached_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("Cached:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+// Process counts by state, as shown by the `count` command and the overview header
+pub struct StatusCounts {
+    pub total: usize,
+    pub running: usize,
+    pub sleeping: usize,
+    pub stopped: usize,
+}
+
+// What the process table adds together
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Name,
+    Cgroup,
+    Container,
+    Unit, // systemd unit
+    User, // owner (real UID)
+    Exe,  // executable path, tells apart programs that share a name
+}
+
+pub const GROUP_BY_MODES: [GroupBy; 6] = [GroupBy::Name, GroupBy::Cgroup, GroupBy::Container, GroupBy::Unit, GroupBy::User, GroupBy::Exe];
+
+impl GroupBy {
+    pub fn parse(text: &str) -> Option<GroupBy> {
+        match text.to_lowercase().as_str() {
+            "name" => Some(GroupBy::Name),
+            "cgroup" => Some(GroupBy::Cgroup),
+            "container" => Some(GroupBy::Container),
+            "unit" => Some(GroupBy::Unit),
+            "user" => Some(GroupBy::User),
+            "exe" => Some(GroupBy::Exe),
+            _ => None,
+        }
+    }
+
+    // Also the header of the first table column
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupBy::Name => "Name",
+            GroupBy::Cgroup => "Cgroup",
+            GroupBy::Container => "Container",
+            GroupBy::Unit => "Unit",
+            GroupBy::User => "User",
+            GroupBy::Exe => "Executable",
+        }
+    }
+
+    fn key(self, process: &ProcessRecord) -> String {
+        match self {
+            GroupBy::Name => process.name.clone(),
+            GroupBy::Cgroup => process.cgroup.clone().unwrap_or_else(|| "(unknown)".to_string()),
+            GroupBy::Container => process.container().map_or("(host)".to_string(), |c| format!("{} {}", c.runtime, c.short_id())),
+            GroupBy::Unit => process.unit().map_or("(no unit)".to_string(), |unit| unit.label()),
+            GroupBy::User => process.user_label(),
+            // like ps shows kernel threads
+            GroupBy::Exe => process.exe.clone().unwrap_or_else(|| format!("[{}]", process.name)),
+        }
+    }
+}
+
+// Which processes the table shows
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct ProcessFilter {
+    pub status: Option<String>,    // part of the status, e.g. "sleep"
+    pub container: Option<String>, // container ID prefix, or "host" for processes outside containers
+    pub unit: Option<String>,      // systemd unit name, "nginx" meaning nginx.service
+    pub user: Option<String>,      // user name or UID (real UID)
+    pub command: Option<String>,   // part of the command line (or of the name), any case
+}
+
+impl ProcessFilter {
+    pub fn matches(&self, process: &ProcessRecord) -> bool {
+        if let Some(status) = &self.status {
+            if !process.status.to_lowercase().contains(&status.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(prefix) = &self.container {
+            let matched = match process.container() {
+                Some(container) => container.id.starts_with(&prefix.to_lowercase()),
+                None => prefix == "host",
+            };
+            if !matched {
+                return false;
+            }
+        }
+        if let Some(filter) = &self.unit {
+            if !process.unit().is_some_and(|unit| unit.matches(filter)) {
+                return false;
+            }
+        }
+        if let Some(text) = &self.command {
+            let text = text.to_lowercase();
+            let in_command = process.cmd.as_ref().is_some_and(|cmd| cmd.to_lowercase().contains(&text));
+            if !in_command && !process.name.to_lowercase().contains(&text) {
+                return false;
+            }
+        }
+        if let Some(user) = &self.user {
+            let matched = process.user.as_deref() == Some(user.as_str()) || process.uid.is_some_and(|uid| uid.to_string() == *user);
+            if !matched {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl ProcessRecord {
+    // The container this process runs in, from its cgroup path
+    pub fn container(&self) -> Option<ContainerRef> {
+        self.cgroup.as_deref().and_then(crate::container::from_cgroup)
+    }
+
+    // The systemd unit (service or scope) this process belongs to, from its cgroup path
+    pub fn unit(&self) -> Option<Unit> {
+        self.cgroup.as_deref().and_then(crate::systemd::unit_from_cgroup)
+    }
+
+    // "alice", the bare UID without a passwd entry, "(unknown)" when /proc/<pid>/status could not be read
+    pub fn user_label(&self) -> String {
+        match (&self.user, self.uid) {
+            (Some(name), _) => name.clone(),
+            (None, Some(uid)) => uid.to_string(),
+            (None, None) => "(unknown)".to_string(),
+        }
+    }
+
+    // "1000/1000", real and effective UID
+    pub fn uid_label(&self) -> String {
+        match (self.uid, self.euid) {
+            (Some(uid), Some(euid)) => format!("{}/{}", uid, euid),
+            _ => "-".to_string(),
+        }
+    }
+}
+
+// One row of the process table: all processes with the same name (or cgroup, ...) added together
+pub struct Aggregated {
+    pub name: String, // the group: process name, cgroup path, ...
+    pub memory: u64,
+    pub cpu: f32,
+    pub pid: Option<u32>,       // first pid seen with this name
+    pub parent: Option<u32>,    // parent of that first process
+    pub status: Option<String>, // status of that first process
+    pub nice: Option<i32>,      // nice and priority of that first process
+    pub priority: Option<i32>,
+    pub affinity: Option<String>, // pinned CPUs of that first process
+    pub cgroup: Option<String>,
+    pub user: String,           // owner of that first process
+    pub uids: String,           // its real/effective UID
+    pub cmd: Option<String>,    // command line and executable of that first process
+    pub exe: Option<String>,
+    pub processes: usize,       // how many processes were added together
+    pub threads: u32,           // threads of all those processes
+    pub read_bytes: u64,        // read and written since the processes started
+    pub written_bytes: u64,
+    pub read_rate: f64,         // bytes per second
+    pub write_rate: f64,
+}
+
+impl Aggregated {
+    pub fn io_rate(&self) -> f64 {
+        self.read_rate + self.write_rate
+    }
+
+    // First column of the table, per-user rows also say how many processes they add up
+    pub fn label(&self, group_by: GroupBy) -> String {
+        match group_by {
+            GroupBy::User => format!("{} ({} {})", self.name, self.processes, if self.processes == 1 { "process" } else { "processes" }),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+impl Snapshot {
+    // Copy the processes out of an already refreshed System
+    pub fn capture(system: &System) -> Self {
+        Snapshot::capture_planned(system, &RefreshPlan::everything())
+    }
+
+    // Same, but only the fields the plan asked for (see refresh.rs); the others keep their defaults
+    pub fn capture_planned(system: &System, plan: &RefreshPlan) -> Self {
+        let cpus = system.cpus().len();
+        let proc_root = Path::new("/proc");
+        let user_names = if plan.owner { crate::users::read_passwd(Path::new("/etc/passwd")) } else { HashMap::new() };
+        let processes = system
+            .processes()
+            .values()
+            .map(|process| {
+                let pid = process.pid().as_u32();
+                let disk = process.disk_usage();
+                // sysinfo does not expose the nice value
+                let (priority, nice) = if plan.priority { crate::priority::read_priority(proc_root, pid) } else { None }.unwrap_or((20, 0));
+                let uids = if plan.owner { crate::users::read_uids(proc_root, pid) } else { None };
+                let mask = if plan.affinity { crate::affinity::get(pid).ok() } else { None };
+                // sysinfo read the command line once, rows on screen get it fresh in case they exec'd since
+                let (cmd, exe) = if plan.command && plan.detail_pids.contains(&pid) {
+                    (read_cmdline(proc_root, pid), read_exe(proc_root, pid))
+                } else {
+                    (
+                        Some(process.cmd().iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "))
+                            .filter(|cmd| !cmd.is_empty()),
+                        process.exe().map(|path| path.display().to_string()),
+                    )
+                };
+                ProcessRecord {
+                    pid,
+                    parent: process.parent().map(|p| p.as_u32()),
+                    name: process.name().to_string_lossy().to_string(),
+                    memory: process.memory(),
+                    cpu: process.cpu_usage(),
+                    status: format!("{:?}", process.status()),
+                    threads: process.tasks().map_or(0, |tasks| tasks.len() as u32 + 1), // tasks() leaves out the main thread
+                    is_thread: process.thread_kind().is_some(),
+                    // zero for other users' processes, /proc/<pid>/io needs the same permissions as ptrace
+                    read_bytes: disk.total_read_bytes,
+                    written_bytes: disk.total_written_bytes,
+                    nice,
+                    priority,
+                    affinity: mask
+                        .filter(|mask| !crate::affinity::is_unpinned(mask, cpus))
+                        .map(|mask| crate::affinity::format_cpu_list(&mask)),
+                    cgroup: if plan.cgroup { crate::cgroup::read_cgroup(proc_root, pid) } else { None },
+                    uid: uids.map(|uids| uids.real),
+                    euid: uids.map(|uids| uids.effective),
+                    user: uids.and_then(|uids| user_names.get(&uids.real).cloned()),
+                    cmd,
+                    exe,
+                    read_rate: 0.0,
+                    write_rate: 0.0,
+                }
+            })
+            .collect();
+        Snapshot {
+            timestamp_ms: now_ms(),
+            total_memory: system.total_memory(),
+            cpus,
+            processes,
+            overview: SystemOverview::capture(system),
+        }
+    }
+
+    pub fn status_counts(&self) -> StatusCounts {
+        let mut running = 0;
+        let mut sleeping = 0;
+        let mut stopped = 0;
+        for process in &self.processes {
+            // Categorize the process based on its current status
+            if process.status.contains("Run") {
+                running += 1;
+            } else if process.status.contains("Sleep") {
+                sleeping += 1;
+            } else if process.status.contains("Stop") {
+                stopped += 1;
+            }
+        }
+        StatusCounts {
+            total: running + sleeping + stopped,
+            running,
+            sleeping,
+            stopped,
+        }
+    }
+
+    // Disk rates from the difference to an earlier snapshot; processes that are new, restarted or
+    // from a later snapshot (seeking backwards in a replay) get zero
+    pub fn compute_io_rates(&mut self, previous: &Snapshot) {
+        let seconds = self.timestamp_ms.saturating_sub(previous.timestamp_ms) as f64 / 1000.0;
+        let before: HashMap<u32, &ProcessRecord> = previous.processes.iter().map(|p| (p.pid, p)).collect();
+        for process in self.processes.iter_mut() {
+            let (read_rate, write_rate) = match before.get(&process.pid) {
+                Some(old) if seconds > 0.0 && old.name == process.name => (
+                    process.read_bytes.saturating_sub(old.read_bytes) as f64 / seconds,
+                    process.written_bytes.saturating_sub(old.written_bytes) as f64 / seconds,
+                ),
+                _ => (0.0, 0.0),
+            };
+            process.read_rate = read_rate;
+            process.write_rate = write_rate;
+        }
+    }
+
+    pub fn process(&self, pid: u32) -> Option<&ProcessRecord> {
+        self.processes.iter().find(|p| p.pid == pid)
+    }
+
+    // Collect the processes the filter lets through and aggregate them by name (or cgroup, container, ...)
+    pub fn aggregate(&self, filter: &ProcessFilter, group_by: GroupBy) -> Vec<Aggregated> {
+        let mut aggregated_processes: HashMap<String, Aggregated> = HashMap::new();
+        for process in &self.processes {
+            // a thread reports its process' memory, CPU and I/O and is not a process of its own
+            if process.memory > 0 && !process.is_thread {
+                if !filter.matches(process) {
+                    continue;
+                }
+                let key = group_by.key(process);
+                let entry = aggregated_processes
+                    .entry(key.clone())
+                    .or_insert_with(|| Aggregated {
+                        name: key,
+                        memory: 0,
+                        cpu: 0.0,
+                        pid: None,
+                        parent: None,
+                        status: None,
+                        nice: None,
+                        priority: None,
+                        affinity: None,
+                        cgroup: None,
+                        user: String::new(),
+                        uids: String::new(),
+                        cmd: None,
+                        exe: None,
+                        processes: 0,
+                        threads: 0,
+                        read_bytes: 0,
+                        written_bytes: 0,
+                        read_rate: 0.0,
+                        write_rate: 0.0,
+                    });
+                entry.memory += process.memory; // Sum memory usage
+                entry.cpu += process.cpu; // Sum CPU usage
+                entry.processes += 1;
+                entry.threads += process.threads;
+                entry.read_bytes += process.read_bytes;
+                entry.written_bytes += process.written_bytes;
+                entry.read_rate += process.read_rate;
+                entry.write_rate += process.write_rate;
+
+                if entry.pid.is_none() {
+                    entry.pid = Some(process.pid);
+                    entry.parent = process.parent;
+                }
+
+                if entry.status.is_none() {
+                    entry.status = Some(process.status.clone());
+                    entry.nice = Some(process.nice);
+                    entry.priority = Some(process.priority);
+                    entry.affinity = process.affinity.clone();
+                    entry.cgroup = process.cgroup.clone();
+                    entry.user = process.user_label();
+                    entry.uids = process.uid_label();
+                    entry.cmd = process.cmd.clone();
+                    entry.exe = process.exe.clone();
+                }
+            }
+        }
+        aggregated_processes.into_values().collect()
+    }
+}
+
+// Bytes read and written per second by a live process since the System was last refreshed.
+// Threads are skipped, their I/O is part of their process.
+pub fn disk_rate(process: &Process, seconds: f64) -> f64 {
+    if seconds <= 0.0 || process.thread_kind().is_some() {
+        return 0.0;
+    }
+    let disk = process.disk_usage();
+    (disk.read_bytes + disk.written_bytes) as f64 / seconds
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+// "3 days, 04:12" style uptime
+pub fn format_uptime(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let clock = format!("{:02}:{:02}", (seconds / 3600) % 24, (seconds / 60) % 60);
+    match days {
+        0 => clock,
+        1 => format!("1 day, {}", clock),
+        _ => format!("{} days, {}", days, clock),
+    }
+}
+
+// Cut the middle out of a long command line so it fits in `max` characters. The program stays readable at the
+// front (up to half the room) and the end, where the script, jar or file being worked on usually is, is kept.
+pub fn truncate_middle(text: &str, max: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max || max < 8 {
+        return text.to_string();
+    }
+    let room = max - 1; // for the '…'
+    let program = chars.iter().position(|c| *c == ' ').unwrap_or(chars.len()) + 1;
+    let head = program.clamp(room / 3, room / 2);
+    let tail = room - head;
+    format!("{}…{}", chars[..head].iter().collect::<String>(), chars[chars.len() - tail..].iter().collect::<String>())
+}
+
+// "HH:MM:SS" (UTC) for a snapshot timestamp
+pub fn format_time_of_day(timestamp_ms: u64) -> String {
+    let seconds = (timestamp_ms / 1000) % 86400;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds / 60) % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_keeps_program_and_end() {
+        let cmd = "python3 /opt/app/manage.py runserver 0.0.0.0:8000";
+        assert_eq!(truncate_middle(cmd, 30), "python3 /…nserver 0.0.0.0:8000");
+        assert_eq!(truncate_middle(cmd, 30).chars().count(), 30);
+    }
+
+    #[test]
+    fn truncate_without_spaces() {
+        // the whole string is the program, it gets half the room
+        assert_eq!(truncate_middle("/usr/lib/jvm/java-17-openjdk/bin/java", 20), "/usr/lib/…k/bin/java");
+        assert_eq!(truncate_middle("abcdefghij", 8), "abc…ghij");
+    }
+
+    #[test]
+    fn truncate_exact_length_and_shorter() {
+        assert_eq!(truncate_middle("eleven-char", 11), "eleven-char");
+        assert_eq!(truncate_middle("eleven-char", 12), "eleven-char");
+        assert_eq!(truncate_middle("eleven-char", 10), "elev…-char");
+        assert_eq!(truncate_middle("", 0), "");
+    }
+
+    #[test]
+    fn truncate_needs_room_for_something() {
+        // below 8 characters nothing readable is left, the text is kept whole
+        assert_eq!(truncate_middle("a very long command line here", 7), "a very long command line here");
+        assert_eq!(truncate_middle("a very long command line here", 0), "a very long command line here");
+        assert_eq!(truncate_middle("a very long command line here", 8).chars().count(), 8);
+    }
+
+    #[test]
+    fn truncate_counts_characters_not_bytes() {
+        let accented = "ünïcödé ïs wëïrd änd lööng ßtring";
+        assert_eq!(truncate_middle(accented, 16), "ünïcödé…g ßtring");
+        let japanese = "日本語のコマンド 引数を含む長い行です";
+        assert_eq!(truncate_middle(japanese, 12), "日本語のコ…む長い行です");
+        assert_eq!(truncate_middle(japanese, 12).chars().count(), 12);
+    }
+
+    #[test]
+    fn threads_are_not_added_to_their_process() {
+        const MIB: u64 = 1024 * 1024;
+        let record = |value: serde_json::Value| -> ProcessRecord { serde_json::from_value(value).unwrap() };
+        // sysinfo lists the threads next to the process, each with the memory of the whole process
+        let snapshot = Snapshot {
+            processes: vec![
+                record(serde_json::json!({"pid": 101, "name": "server", "mem": 100 * MIB, "cpu": 30.0, "st": "Run",
+                    "is_thread": true, "ni": 5, "rd": 500})),
+                record(serde_json::json!({"pid": 100, "ppid": 1, "name": "server", "mem": 100 * MIB, "cpu": 50.0, "st": "Sleep",
+                    "threads": 3, "rd": 1000, "cmd": "server --port 80"})),
+                record(serde_json::json!({"pid": 102, "name": "server", "mem": 100 * MIB, "cpu": 20.0, "st": "Run", "is_thread": true})),
+                // a thread named differently than its process does not get a row of its own
+                record(serde_json::json!({"pid": 103, "name": "worker", "mem": 100 * MIB, "cpu": 5.0, "st": "Run", "is_thread": true})),
+            ],
+            ..Snapshot::default()
+        };
+
+        let rows = snapshot.aggregate(&ProcessFilter::default(), GroupBy::Name);
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!((row.memory, row.cpu), (100 * MIB, 50.0));
+        assert_eq!((row.processes, row.threads, row.read_bytes), (1, 3, 1000));
+        // the row is described by the process, not by whichever thread came first
+        assert_eq!((row.pid, row.parent), (Some(100), Some(1)));
+        assert_eq!((row.status.as_deref(), row.nice), (Some("Sleep"), Some(0)));
+        assert_eq!(row.cmd.as_deref(), Some("server --port 80"));
+    }
+}
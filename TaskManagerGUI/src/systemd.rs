@@ -0,0 +1,158 @@
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+
+// SYSTEMD UNITS
+//
+// systemd gives every service and scope its own cgroup, so the unit owning a process is the innermost
+// "*.service" / "*.scope" component of its cgroup path:
+//   /system.slice/nginx.service                                          -> nginx.service
+//   /user.slice/user-1000.slice/user@1000.service/app.slice/foo.scope     -> foo.scope (user manager of UID 1000)
+// Groups a service creates below itself (e.g. "/system.slice/foo.service/payload") still belong to it.
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Unit {
+    pub name: String,
+    pub user: Option<u32>, // UID of the user manager running it, None for system units
+}
+
+impl Unit {
+    // "nginx.service", "foo.scope (user 1000)"
+    pub fn label(&self) -> String {
+        match self.user {
+            Some(uid) => format!("{} (user {})", self.name, uid),
+            None => self.name.clone(),
+        }
+    }
+
+    // Scopes wrap processes started by something else (a login, a container runtime), only services restart
+    pub fn can_restart(&self) -> bool {
+        self.name.ends_with(".service") && !self.name.starts_with("user@")
+    }
+
+    // `nginx` is short for `nginx.service`, like in systemctl
+    pub fn matches(&self, filter: &str) -> bool {
+        self.name == filter || self.name == format!("{}.service", filter)
+    }
+}
+
+pub fn unit_from_cgroup(path: &str) -> Option<Unit> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    let index = components.iter().rposition(|c| c.ends_with(".service") || c.ends_with(".scope"))?;
+    // units below user@<uid>.service belong to that user's manager
+    let user = components[..index]
+        .iter()
+        .find_map(|c| c.strip_prefix("user@").and_then(|c| c.strip_suffix(".service")).and_then(|uid| uid.parse().ok()));
+    Some(Unit { name: components[index].to_string(), user })
+}
+
+// `systemctl restart`, for a user unit only when it is our own user's
+pub fn restart(unit: &Unit) -> Result<(), String> {
+    if !unit.can_restart() {
+        return Err(format!("{} is not a service, it cannot be restarted", unit.name));
+    }
+    let mut command = Command::new("systemctl");
+    if let Some(uid) = unit.user {
+        if uid != nix::unistd::getuid().as_raw() {
+            return Err(format!("{} belongs to the user manager of UID {}, restart it as that user", unit.name, uid));
+        }
+        command.arg("--user");
+    }
+    let output = command
+        .arg("restart")
+        .arg(&unit.name)
+        .output()
+        .map_err(|e| format!("cannot run systemctl: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!("systemctl restart {} failed: {}", unit.name, stderr));
+    }
+    Ok(())
+}
+
+// restart() on its own thread: systemctl waits until the unit is up again, up to its start timeout.
+// The result arrives on the returned channel, after which `wake` is called (the GUI passes request_repaint).
+pub fn restart_in_background(unit: Unit, wake: impl FnOnce() + Send + 'static) -> Receiver<Result<(), String>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(restart(&unit));
+        wake();
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(name: &str, user: Option<u32>) -> Option<Unit> {
+        Some(Unit { name: name.to_string(), user })
+    }
+
+    #[test]
+    fn system_services_and_their_subgroups() {
+        assert_eq!(unit_from_cgroup("/system.slice/nginx.service"), unit("nginx.service", None));
+        assert_eq!(unit_from_cgroup("/system.slice/foo.service/payload"), unit("foo.service", None));
+        assert_eq!(unit_from_cgroup("/system.slice/system-getty.slice/getty@tty1.service"), unit("getty@tty1.service", None));
+    }
+
+    #[test]
+    fn scopes() {
+        // a login session is a system scope, only the user manager's units belong to a user
+        assert_eq!(
+            unit_from_cgroup("/user.slice/user-1000.slice/session-2.scope"),
+            unit("session-2.scope", None)
+        );
+        assert_eq!(unit_from_cgroup("/init.scope"), unit("init.scope", None));
+        assert_eq!(unit_from_cgroup("/machine.slice/libpod-3f2a9c1b.scope/container"), unit("libpod-3f2a9c1b.scope", None));
+    }
+
+    #[test]
+    fn units_of_a_user_manager() {
+        assert_eq!(
+            unit_from_cgroup("/user.slice/user-1000.slice/user@1000.service/app.slice/foo.scope"),
+            unit("foo.scope", Some(1000))
+        );
+        assert_eq!(
+            unit_from_cgroup("/user.slice/user-1000.slice/user@1000.service/app.slice/pipewire.service"),
+            unit("pipewire.service", Some(1000))
+        );
+        // the manager itself is a system service
+        assert_eq!(
+            unit_from_cgroup("/user.slice/user-1000.slice/user@1000.service"),
+            unit("user@1000.service", None)
+        );
+        assert_eq!(unit("foo.scope", Some(1000)).unwrap().label(), "foo.scope (user 1000)");
+    }
+
+    #[test]
+    fn no_unit() {
+        assert_eq!(unit_from_cgroup("/"), None);
+        assert_eq!(unit_from_cgroup(""), None);
+        assert_eq!(unit_from_cgroup("/user.slice/user-1000.slice"), None);
+        assert_eq!(unit_from_cgroup("/docker/3f2a9c1b"), None);
+    }
+
+    #[test]
+    fn only_services_restart() {
+        assert!(unit("nginx.service", None).unwrap().can_restart());
+        assert!(unit("pipewire.service", Some(1000)).unwrap().can_restart());
+        assert!(!unit("session-2.scope", None).unwrap().can_restart());
+        assert!(!unit("user@1000.service", None).unwrap().can_restart());
+
+        // refused before systemctl runs
+        let scope = unit("session-2.scope", None).unwrap();
+        assert!(restart(&scope).unwrap_err().contains("is not a service"));
+        let someone_else = unit("pipewire.service", Some(nix::unistd::getuid().as_raw() + 1)).unwrap();
+        assert!(restart(&someone_else).unwrap_err().contains("restart it as that user"));
+    }
+
+    #[test]
+    fn short_service_names() {
+        let nginx = unit("nginx.service", None).unwrap();
+        assert!(nginx.matches("nginx"));
+        assert!(nginx.matches("nginx.service"));
+        assert!(!nginx.matches("nginx.scope"));
+        assert!(!nginx.matches("ngin"));
+        assert!(!unit("session-2.scope", None).unwrap().matches("session-2"));
+    }
+}
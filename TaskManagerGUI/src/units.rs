@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+// MEMORY UNITS AND CPU MODE
+//
+// Everything in a Snapshot is in bytes and raw sysinfo CPU percentages. Views only convert when
+// printing, through here, so the numbers and the unit next to them always agree.
+
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * KIB;
+const GIB: u64 = 1024 * MIB;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryUnit {
+    Auto, // pick B / KiB / MiB / GiB per value
+    Bytes,
+    KiB,
+    MiB,
+    GiB,
+}
+
+pub const MEMORY_UNITS: [MemoryUnit; 5] = [MemoryUnit::Auto, MemoryUnit::Bytes, MemoryUnit::KiB, MemoryUnit::MiB, MemoryUnit::GiB];
+
+impl MemoryUnit {
+    pub fn parse(text: &str) -> Option<MemoryUnit> {
+        match text.to_lowercase().as_str() {
+            "auto" => Some(MemoryUnit::Auto),
+            "bytes" | "b" => Some(MemoryUnit::Bytes),
+            "kib" => Some(MemoryUnit::KiB),
+            "mib" => Some(MemoryUnit::MiB),
+            "gib" => Some(MemoryUnit::GiB),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MemoryUnit::Auto => "Auto",
+            MemoryUnit::Bytes => "Bytes",
+            MemoryUnit::KiB => "KiB",
+            MemoryUnit::MiB => "MiB",
+            MemoryUnit::GiB => "GiB",
+        }
+    }
+
+    // Auto resolves to the biggest unit the value reaches
+    fn for_value(self, bytes: u64) -> MemoryUnit {
+        match self {
+            MemoryUnit::Auto if bytes >= GIB => MemoryUnit::GiB,
+            MemoryUnit::Auto if bytes >= MIB => MemoryUnit::MiB,
+            MemoryUnit::Auto if bytes >= KIB => MemoryUnit::KiB,
+            MemoryUnit::Auto => MemoryUnit::Bytes,
+            unit => unit,
+        }
+    }
+}
+
+// The value expressed in `unit` (Auto picks one per value), e.g. 1536 bytes -> 1.5 KiB
+pub fn convert(bytes: u64, unit: MemoryUnit) -> f64 {
+    match unit.for_value(bytes) {
+        MemoryUnit::GiB => bytes as f64 / GIB as f64,
+        MemoryUnit::MiB => bytes as f64 / MIB as f64,
+        MemoryUnit::KiB => bytes as f64 / KIB as f64,
+        _ => bytes as f64,
+    }
+}
+
+// "1.5 GiB", "312.0 MiB", "4096 B"
+pub fn format_bytes(bytes: u64, unit: MemoryUnit) -> String {
+    let value = convert(bytes, unit);
+    match unit.for_value(bytes) {
+        MemoryUnit::Bytes | MemoryUnit::Auto => format!("{} B", bytes),
+        MemoryUnit::GiB => format!("{:.2} GiB", value),
+        resolved => format!("{:.1} {}", value, resolved.label()),
+    }
+}
+
+// Growth rates like leak slopes, "2.5 MiB/min"
+pub fn format_rate(bytes_per_sec: f64, unit: MemoryUnit) -> String {
+    format!("{}/min", format_bytes((bytes_per_sec.max(0.0) * 60.0) as u64, unit))
+}
+
+// Share of `total` in percent, always between 0 and 100 (0 when the total is unknown)
+pub fn percent_of(part: u64, total: u64) -> f32 {
+    if total == 0 {
+        return 0.0;
+    }
+    (part as f64 / total as f64 * 100.0).clamp(0.0, 100.0) as f32
+}
+
+// How a process' CPU percentage is expressed, like top's Irix mode toggle
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CpuMode {
+    Irix,    // 100% = one core, a busy process can go up to cores * 100%
+    Solaris, // 100% = the whole machine
+}
+
+impl CpuMode {
+    pub fn parse(text: &str) -> Option<CpuMode> {
+        match text.to_lowercase().as_str() {
+            "irix" | "per-core" => Some(CpuMode::Irix),
+            "solaris" | "machine" => Some(CpuMode::Solaris),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CpuMode::Irix => "per core",
+            CpuMode::Solaris => "of machine",
+        }
+    }
+
+    // Column header naming the active mode, e.g. "CPU Usage (% per core)"
+    pub fn header(self) -> String {
+        format!("CPU Usage (% {})", self.label())
+    }
+
+    // sysinfo reports Irix style, Solaris divides by the number of logical CPUs
+    pub fn scale(self, cpu: f32, cpus: usize) -> f32 {
+        match self {
+            CpuMode::Irix => cpu,
+            CpuMode::Solaris => cpu / cpus.max(1) as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_units() {
+        assert_eq!(format_bytes(1536, MemoryUnit::Bytes), "1536 B");
+        assert_eq!(format_bytes(1536, MemoryUnit::KiB), "1.5 KiB");
+        assert_eq!(format_bytes(3 * MIB / 2, MemoryUnit::MiB), "1.5 MiB");
+        assert_eq!(format_bytes(3 * GIB / 2, MemoryUnit::GiB), "1.50 GiB");
+        // a fixed unit is kept for small values too
+        assert_eq!(format_bytes(512, MemoryUnit::MiB), "0.0 MiB");
+        assert_eq!(convert(MIB, MemoryUnit::KiB), 1024.0);
+    }
+
+    #[test]
+    fn auto_thresholds() {
+        assert_eq!(format_bytes(1023, MemoryUnit::Auto), "1023 B");
+        assert_eq!(format_bytes(KIB, MemoryUnit::Auto), "1.0 KiB");
+        assert_eq!(format_bytes(MIB - 1, MemoryUnit::Auto), "1024.0 KiB");
+        assert_eq!(format_bytes(MIB, MemoryUnit::Auto), "1.0 MiB");
+        assert_eq!(format_bytes(GIB - 1, MemoryUnit::Auto), "1024.0 MiB");
+        assert_eq!(format_bytes(GIB, MemoryUnit::Auto), "1.00 GiB");
+    }
+
+    #[test]
+    fn zero_and_max() {
+        for unit in MEMORY_UNITS {
+            assert_eq!(convert(0, unit), 0.0);
+        }
+        assert_eq!(format_bytes(0, MemoryUnit::Auto), "0 B");
+        assert_eq!(format_bytes(0, MemoryUnit::GiB), "0.00 GiB");
+        assert_eq!(format_bytes(u64::MAX, MemoryUnit::Bytes), "18446744073709551615 B");
+        assert_eq!(format_bytes(u64::MAX, MemoryUnit::KiB), "18014398509481984.0 KiB");
+        assert_eq!(format_bytes(u64::MAX, MemoryUnit::Auto), "17179869184.00 GiB");
+    }
+
+    #[test]
+    fn rates() {
+        assert_eq!(format_rate(0.0, MemoryUnit::Auto), "0 B/min");
+        assert_eq!(format_rate(-100.0, MemoryUnit::Auto), "0 B/min"); // shrinking is not a leak
+        assert_eq!(format_rate(MIB as f64 / 60.0, MemoryUnit::Auto), "1.0 MiB/min");
+        assert_eq!(format_rate(KIB as f64, MemoryUnit::KiB), "60.0 KiB/min");
+        // saturates instead of wrapping
+        assert_eq!(format_rate(f64::MAX, MemoryUnit::Bytes), "18446744073709551615 B/min");
+    }
+
+    #[test]
+    fn percentages() {
+        assert_eq!(percent_of(1, 4), 25.0);
+        assert_eq!(percent_of(5, 0), 0.0);
+        assert_eq!(percent_of(0, 100), 0.0);
+        assert_eq!(percent_of(200, 100), 100.0);
+        assert_eq!(percent_of(u64::MAX, u64::MAX), 100.0);
+    }
+
+    #[test]
+    fn config_strings_round_trip() {
+        for unit in MEMORY_UNITS {
+            // what the config file holds, and what --memory-unit accepts
+            let saved = toml::Value::try_from(unit).unwrap();
+            assert_eq!(MemoryUnit::parse(saved.as_str().unwrap()), Some(unit));
+            assert_eq!(saved.clone().try_into::<MemoryUnit>().unwrap(), unit);
+            assert_eq!(MemoryUnit::parse(unit.label()), Some(unit));
+        }
+        assert_eq!(MemoryUnit::parse("B"), Some(MemoryUnit::Bytes));
+        assert_eq!(MemoryUnit::parse("MB"), None);
+        assert_eq!(MemoryUnit::parse(""), None);
+
+        for mode in [CpuMode::Irix, CpuMode::Solaris] {
+            let saved = toml::Value::try_from(mode).unwrap();
+            assert_eq!(CpuMode::parse(saved.as_str().unwrap()), Some(mode));
+        }
+        assert_eq!(CpuMode::parse("per-core"), Some(CpuMode::Irix));
+        assert_eq!(CpuMode::Solaris.scale(400.0, 8), 50.0);
+        assert_eq!(CpuMode::Solaris.scale(400.0, 0), 400.0);
+    }
+}
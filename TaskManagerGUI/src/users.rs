@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+// PROCESS OWNERS
+//
+// Every process has a real UID (who started it) and an effective UID (whose permissions it has, they differ
+// for setuid programs like passwd or sudo), both on the "Uid:" line of /proc/<pid>/status. Names come from
+// /etc/passwd; users from LDAP and the like are shown by UID only.
+
+// (real, effective, saved) UIDs of a process
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Uids {
+    pub real: u32,
+    pub effective: u32,
+    pub saved: u32,
+}
+
+// "Uid:	1000	1000	1000	1000" is real, effective, saved and filesystem UID
+pub fn read_uids(proc_root: &Path, pid: u32) -> Option<Uids> {
+    let status = std::fs::read_to_string(proc_root.join(pid.to_string()).join("status")).ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("Uid:"))?;
+    let ids: Vec<u32> = line.split_whitespace().filter_map(|id| id.parse().ok()).collect();
+    Some(Uids { real: *ids.first()?, effective: *ids.get(1)?, saved: *ids.get(2)? })
+}
+
+// UID -> name from a passwd file ("name:x:uid:gid:..."), read once per snapshot
+pub fn read_passwd(path: &Path) -> HashMap<u32, String> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            Some((fields.get(2)?.parse().ok()?, fields.first()?.to_string()))
+        })
+        .collect()
+}
+
+// "alice", or the bare UID when it has no name
+pub fn user_name(uid: u32) -> String {
+    read_passwd(Path::new("/etc/passwd")).remove(&uid).unwrap_or_else(|| uid.to_string())
+}
+
+// Which kernel rule an action falls under, they do not all compare the same UIDs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Permission {
+    Signal,     // kill(2): our real or effective UID is the target's real or saved UID, or CAP_KILL
+    Schedule,   // setpriority(2), sched_setaffinity(2): our effective UID is the target's real or effective UID, or CAP_SYS_NICE
+    IoPriority, // ioprio_set(2): our real or effective UID is the target's real or effective UID, or CAP_SYS_NICE
+}
+
+const CAP_KILL: u32 = 5;
+const CAP_SYS_NICE: u32 = 23;
+
+impl Permission {
+    fn capability(self) -> u32 {
+        match self {
+            Permission::Signal => CAP_KILL,
+            Permission::Schedule | Permission::IoPriority => CAP_SYS_NICE,
+        }
+    }
+
+    // Whether a caller with these UIDs and effective capabilities passes the kernel's check
+    pub fn allows(self, caller: Uids, capabilities: u64, target: Uids) -> bool {
+        if capabilities & (1 << self.capability()) != 0 {
+            return true;
+        }
+        match self {
+            Permission::Signal => [caller.real, caller.effective].iter().any(|uid| *uid == target.real || *uid == target.saved),
+            Permission::Schedule => caller.effective == target.real || caller.effective == target.effective,
+            Permission::IoPriority => [caller.real, caller.effective].iter().any(|uid| *uid == target.real || *uid == target.effective),
+        }
+    }
+}
+
+// "CapEff:	000001ffffffffff" in /proc/<pid>/status, the capabilities the process can use right now
+pub fn read_capabilities(proc_root: &Path, pid: &str) -> Option<u64> {
+    let status = std::fs::read_to_string(proc_root.join(pid).join("status")).ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(line.trim(), 16).ok()
+}
+
+// Refuse to touch another user's process before the kernel does, so the user sees who owns it
+pub fn check_owner(proc_root: &Path, pid: u32, permission: Permission, action: &str) -> Result<(), String> {
+    let (real, effective) = (nix::unistd::getuid().as_raw(), nix::unistd::geteuid().as_raw());
+    // without a readable CapEff line, root is assumed to have them all
+    let capabilities = read_capabilities(proc_root, "self").unwrap_or(if effective == 0 { u64::MAX } else { 0 });
+    // a vanished process is left for the syscall to report
+    let owner = match read_uids(proc_root, pid) {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+    if permission.allows(Uids { real, effective, saved: effective }, capabilities, owner) {
+        return Ok(());
+    }
+    Err(format!(
+        "permission denied: cannot {} PID {}, it belongs to {} (UID {}) and you are {} (UID {})",
+        action,
+        pid,
+        user_name(owner.real),
+        owner.real,
+        user_name(real),
+        real
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uids(real: u32, effective: u32, saved: u32) -> Uids {
+        Uids { real, effective, saved }
+    }
+
+    #[test]
+    fn signals_compare_against_real_and_saved() {
+        let alice = uids(1000, 1000, 1000);
+        assert!(Permission::Signal.allows(alice, 0, uids(1000, 1000, 1000)));
+        // a setuid program alice started: real UID is hers
+        assert!(Permission::Signal.allows(alice, 0, uids(1000, 0, 0)));
+        // a daemon that switched its effective UID to alice still belongs to root
+        assert!(!Permission::Signal.allows(alice, 0, uids(0, 1000, 0)));
+        assert!(!Permission::Signal.allows(alice, 0, uids(1001, 1001, 1001)));
+    }
+
+    #[test]
+    fn scheduling_compares_our_effective_uid() {
+        let alice = uids(1000, 1000, 1000);
+        assert!(Permission::Schedule.allows(alice, 0, uids(0, 1000, 0)));
+        assert!(Permission::Schedule.allows(alice, 0, uids(1000, 0, 0)));
+        assert!(!Permission::Schedule.allows(alice, 0, uids(1001, 1001, 1000)));
+        // running setuid as bob: only the effective UID counts
+        assert!(!Permission::Schedule.allows(uids(1000, 1001, 1001), 0, uids(1000, 1000, 1000)));
+        assert!(Permission::IoPriority.allows(uids(1000, 1001, 1001), 0, uids(1000, 1000, 1000)));
+    }
+
+    #[test]
+    fn capabilities_override_ownership() {
+        let alice = uids(1000, 1000, 1000);
+        let other = uids(0, 0, 0);
+        assert!(Permission::Signal.allows(alice, 1 << CAP_KILL, other));
+        assert!(!Permission::Schedule.allows(alice, 1 << CAP_KILL, other));
+        assert!(Permission::Schedule.allows(alice, 1 << CAP_SYS_NICE, other));
+        assert!(Permission::IoPriority.allows(alice, 1 << CAP_SYS_NICE, other));
+        assert!(!Permission::Signal.allows(alice, 1 << CAP_SYS_NICE, other));
+    }
+
+    #[test]
+    fn reads_effective_capabilities() {
+        let root = std::env::temp_dir().join(format!("taskmanager-caps-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("self")).unwrap();
+        std::fs::write(root.join("self").join("status"), "Name:\tcat\nCapInh:\t0000000000000000\nCapEff:\t0000000000800020\n").unwrap();
+        assert_eq!(read_capabilities(&root, "self"), Some((1 << CAP_KILL) | (1 << CAP_SYS_NICE)));
+        assert_eq!(read_capabilities(&root, "missing"), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use regex::Regex;
+use serde_json::Value;
+use sysinfo::System;
+use crate::config::Config;
+use crate::history::Recorder;
+use crate::notifications::Severity;
+use crate::refresh::RefreshPlan;
+use crate::snapshot::{self, Snapshot};
+use crate::units;
+
+// HEADLESS ALERT DAEMON (`watch`)
+
+// Set from the signal handlers, checked by the main loop
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_signal(signal: i32) {
+    if signal == Signal::SIGHUP as i32 {
+        RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+    } else {
+        EXIT_REQUESTED.store(true, Ordering::SeqCst);
+    }
+}
+
+// One CheckAlerts-style rule: alert when a process (optionally matching `name`) goes over a limit
+struct AlertRule {
+    name: Option<Regex>,
+    cpu_threshold: Option<f32>,
+    memory_threshold: Option<u64>, // in bytes
+    io_threshold: Option<f64>,     // disk read + write in bytes per second
+}
+
+impl AlertRule {
+    // Same limits the GUI uses (from the config file), for every process
+    fn default_rules(config: &Config) -> Vec<AlertRule> {
+        vec![AlertRule {
+            name: None,
+            cpu_threshold: Some(config.alerts.cpu_threshold),
+            memory_threshold: Some(config.memory_threshold_bytes()),
+            io_threshold: Some(config.io_threshold_bytes_per_sec()).filter(|limit| *limit > 0.0),
+        }]
+    }
+
+    // Returns the severity if this process breaks the rule
+    fn check(&self, name: &str, cpu_usage: f32, memory_usage: u64, io_rate: f64) -> Option<Severity> {
+        if let Some(pattern) = &self.name {
+            if !pattern.is_match(name) {
+                return None;
+            }
+        }
+        let cpu_over = self.cpu_threshold.is_some_and(|limit| cpu_usage > limit);
+        let memory_over = self.memory_threshold.is_some_and(|limit| memory_usage > limit);
+        let io_over = self.io_threshold.is_some_and(|limit| io_rate > limit);
+        // critical when more than one limit is broken
+        match [cpu_over, memory_over, io_over].iter().filter(|over| **over).count() {
+            0 => None,
+            1 => Some(Severity::Warning),
+            _ => Some(Severity::Critical),
+        }
+    }
+}
+
+// Rules file is JSON, "cpu" is in the configured CPU mode (display.cpu_mode), e.g.
+// [
+//   { "name": "java", "cpu": 80.0 },
+//   { "memory_mb": 4096 },
+//   { "name": "^cc1", "io_mb_per_sec": 50 }
+// ]
+fn load_rules(path: &str) -> Result<Vec<AlertRule>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read rules file {}: {}", path, e))?;
+    let json: Value = serde_json::from_str(&text).map_err(|e| format!("invalid JSON in {}: {}", path, e))?;
+    let entries = json.as_array().ok_or(format!("{}: expected a list of rules", path))?;
+
+    let mut rules = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let name = match entry.get("name").and_then(Value::as_str) {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| format!("{}: rule {}: bad name pattern: {}", path, i + 1, e))?),
+            None => None,
+        };
+        let cpu_threshold = entry.get("cpu").and_then(Value::as_f64).map(|v| v as f32);
+        let memory_threshold = entry.get("memory_mb").and_then(Value::as_u64).map(|mb| mb * 1024 * 1024);
+        let io_threshold = entry.get("io_mb_per_sec").and_then(Value::as_f64).map(|mb| mb * 1024.0 * 1024.0);
+        if cpu_threshold.is_none() && memory_threshold.is_none() && io_threshold.is_none() {
+            return Err(format!("{}: rule {}: needs a \"cpu\", \"memory_mb\" or \"io_mb_per_sec\" limit", path, i + 1));
+        }
+        rules.push(AlertRule { name, cpu_threshold, memory_threshold, io_threshold });
+    }
+    Ok(rules)
+}
+
+// Where alert lines go
+enum AlertLog {
+    Stderr,
+    Syslog(UnixDatagram),
+    File(File),
+}
+
+impl AlertLog {
+    fn open(target: &str) -> Result<AlertLog, String> {
+        match target {
+            "stderr" => Ok(AlertLog::Stderr),
+            "syslog" => {
+                let socket = UnixDatagram::unbound().map_err(|e| format!("cannot create syslog socket: {}", e))?;
+                socket.connect("/dev/log").map_err(|e| format!("cannot connect to /dev/log: {}", e))?;
+                Ok(AlertLog::Syslog(socket))
+            }
+            path => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map(AlertLog::File)
+                .map_err(|e| format!("cannot open log file {}: {}", path, e)),
+        }
+    }
+
+    fn log(&mut self, severity: Option<Severity>, message: &str) {
+        // syslog levels: 2 = crit, 4 = warning, 6 = info
+        let (level, label) = match severity {
+            Some(Severity::Critical) => (2, "CRITICAL"),
+            Some(Severity::Warning) => (4, "WARNING"),
+            None => (6, "INFO"),
+        };
+        match self {
+            AlertLog::Stderr => eprintln!("[{}] {}", label, message),
+            AlertLog::Syslog(socket) => {
+                // facility "daemon" (3), see RFC 3164
+                let line = format!("<{}>taskmanager[{}]: {}", 3 * 8 + level, std::process::id(), message);
+                let _ = socket.send(line.as_bytes());
+            }
+            AlertLog::File(file) => {
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                let _ = writeln!(file, "{} [{}] {}", timestamp, label, message);
+            }
+        }
+    }
+}
+
+fn print_watch_usage() {
+    eprintln!(
+        "Usage: watch [--interval <ms>] [--rules <file.json>] [--log stderr|syslog|<file>]
+  --interval <ms>   : How often processes are checked (default: watch_ms from the config file).
+  --rules <file>    : JSON list of rules, reloaded on SIGHUP (default: the config file's alert thresholds).
+  --log <target>    : Where alerts are written (default stderr)."
+    );
+}
+
+// Entry point for `watch`, returns the process exit code
+pub fn run_watch(args: &[String], config: &Config) -> i32 {
+    let mut interval = Duration::from_millis(config.refresh.watch_ms);
+    let mut rules_path: Option<String> = None;
+    let mut log_target = "stderr".to_string();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--interval", Some(ms)) => match ms.parse::<u64>() {
+                Ok(ms) if ms > 0 => interval = Duration::from_millis(ms),
+                _ => {
+                    eprintln!("Invalid interval '{}', expected milliseconds.", ms);
+                    return 2;
+                }
+            },
+            ("--rules", Some(path)) => rules_path = Some(path.clone()),
+            ("--log", Some(target)) => log_target = target.clone(),
+            _ => {
+                print_watch_usage();
+                return 2;
+            }
+        }
+    }
+
+    let mut log = match AlertLog::open(&log_target) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let mut rules = match &rules_path {
+        Some(path) => match load_rules(path) {
+            Ok(rules) => rules,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        None => AlertRule::default_rules(config),
+    };
+
+    // SIGHUP reloads the rules, SIGTERM / SIGINT stop the loop
+    let action = SigAction::new(SigHandler::Handler(handle_signal), SaFlags::SA_RESTART, SigSet::empty());
+    for sig in [Signal::SIGHUP, Signal::SIGTERM, Signal::SIGINT] {
+        if let Err(e) = unsafe { signal::sigaction(sig, &action) } {
+            eprintln!("Failed to install handler for {:?}: {}", sig, e);
+            return 1;
+        }
+    }
+
+    log.log(None, &format!("watching processes every {} ms with {} rule(s)", interval.as_millis(), rules.len()));
+
+    let mut system = System::new_all();
+    // the daemon is also the natural place to keep history when [history] is enabled
+    let mut recorder = Recorder::from_config(&config.history);
+    // (rule index, PID) pairs already reported, so an alert is logged once until it clears
+    let mut active: HashSet<(usize, u32)> = HashSet::new();
+    let mut last_refresh = Instant::now();
+
+    while !EXIT_REQUESTED.load(Ordering::SeqCst) {
+        if RELOAD_REQUESTED.swap(false, Ordering::SeqCst) {
+            match &rules_path {
+                Some(path) => match load_rules(path) {
+                    Ok(new_rules) => {
+                        rules = new_rules;
+                        active.clear();
+                        log.log(None, &format!("reloaded {} rule(s) from {}", rules.len(), path));
+                    }
+                    Err(e) => log.log(Some(Severity::Warning), &format!("keeping old rules, reload failed: {}", e)),
+                },
+                None => log.log(None, "SIGHUP received but no rules file was given"),
+            }
+        }
+
+        RefreshPlan::everything().refresh(&mut system);
+        let seconds = last_refresh.elapsed().as_secs_f64(); // disk usage is counted since the last refresh
+        last_refresh = Instant::now();
+        if let Some(rec) = recorder.as_mut() {
+            if let Err(e) = rec.maybe_record(&Snapshot::capture(&system)) {
+                log.log(Some(Severity::Warning), &format!("history recording stopped: {}", e));
+                recorder = None;
+            }
+        }
+
+        let mut still_active = HashSet::new();
+        for (index, rule) in rules.iter().enumerate() {
+            for process in system.processes().values() {
+                let name = process.name().to_string_lossy();
+                // rules use the same CPU mode as the other views
+                let cpu_usage = config.display.cpu_mode.scale(process.cpu_usage(), system.cpus().len());
+                let memory_usage = process.memory();
+                let io_rate = snapshot::disk_rate(process, seconds);
+                if let Some(severity) = rule.check(&name, cpu_usage, memory_usage, io_rate) {
+                    let key = (index, process.pid().as_u32());
+                    if !active.contains(&key) {
+                        log.log(
+                            Some(severity),
+                            &format!(
+                                "rule {}: PID {} ({}) CPU {:.2}% {} memory {} disk I/O {}/s",
+                                index + 1,
+                                key.1,
+                                name,
+                                cpu_usage,
+                                config.display.cpu_mode.label(),
+                                units::format_bytes(memory_usage, config.display.memory_unit),
+                                units::format_bytes(io_rate as u64, config.display.memory_unit)
+                            ),
+                        );
+                    }
+                    still_active.insert(key);
+                }
+            }
+        }
+        active = still_active;
+
+        // Sleep in small steps so signals are handled quickly
+        let mut slept = Duration::ZERO;
+        while slept < interval && !EXIT_REQUESTED.load(Ordering::SeqCst) && !RELOAD_REQUESTED.load(Ordering::SeqCst) {
+            let step = (interval - slept).min(Duration::from_millis(100));
+            std::thread::sleep(step);
+            slept += step;
+        }
+    }
+
+    log.log(None, "stopping");
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const MIB: u64 = 1024 * 1024;
+
+    // A rules file under the temp directory, removed again when the test ends
+    struct RulesFile {
+        path: PathBuf,
+    }
+
+    impl RulesFile {
+        fn new(test: &str, json: &str) -> RulesFile {
+            let path = std::env::temp_dir().join(format!("taskmanager-{}-{}.json", test, std::process::id()));
+            std::fs::write(&path, json).unwrap();
+            RulesFile { path }
+        }
+
+        fn load(&self) -> Result<Vec<AlertRule>, String> {
+            load_rules(self.path.to_str().unwrap())
+        }
+    }
+
+    impl Drop for RulesFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn rules_are_read_with_their_units() {
+        let file = RulesFile::new("rules-units", r#"[
+            { "name": "java", "cpu": 80.0 },
+            { "memory_mb": 4096 },
+            { "name": "^cc1", "io_mb_per_sec": 50 }
+        ]"#);
+        let rules = file.load().unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].cpu_threshold, Some(80.0));
+        assert_eq!(rules[0].memory_threshold, None);
+        assert_eq!(rules[1].memory_threshold, Some(4096 * MIB));
+        assert!(rules[1].name.is_none());
+        assert_eq!(rules[2].io_threshold, Some(50.0 * MIB as f64));
+    }
+
+    #[test]
+    fn bad_rules_files_are_reported() {
+        let missing = std::env::temp_dir().join(format!("taskmanager-rules-missing-{}.json", std::process::id()));
+        assert!(load_rules(missing.to_str().unwrap()).err().unwrap().contains("cannot read rules file"));
+
+        let error = |test: &str, json: &str| RulesFile::new(test, json).load().err().unwrap();
+        assert!(error("rules-json", "[{ \"cpu\": 80").contains("invalid JSON"));
+        assert!(error("rules-list", "{ \"cpu\": 80 }").contains("expected a list of rules"));
+        assert!(error("rules-pattern", r#"[{ "name": "(java", "cpu": 80 }]"#).contains("rule 1: bad name pattern"));
+        // only a name, or limits with the wrong type, is no limit at all
+        assert!(error("rules-limit", r#"[{ "cpu": 80 }, { "name": "java" }]"#).contains("rule 2: needs a"));
+        assert!(error("rules-type", r#"[{ "memory_mb": "4096" }]"#).contains("rule 1: needs a"));
+    }
+
+    #[test]
+    fn rule_only_applies_to_matching_names() {
+        let file = RulesFile::new("rules-names", r#"[{ "name": "^cc1", "cpu": 50 }]"#);
+        let rule = &file.load().unwrap()[0];
+        assert_eq!(rule.check("cc1plus", 90.0, 0, 0.0), Some(Severity::Warning));
+        assert_eq!(rule.check("cc1", 90.0, 0, 0.0), Some(Severity::Warning));
+        // a regex, not an exact name: "^cc1" does not match in the middle
+        assert_eq!(rule.check("gcc1", 90.0, 0, 0.0), None);
+        assert_eq!(rule.check("java", 90.0, 0, 0.0), None);
+    }
+
+    #[test]
+    fn limits_and_severity() {
+        let rule = AlertRule {
+            name: None,
+            cpu_threshold: Some(80.0),
+            memory_threshold: Some(1024 * MIB),
+            io_threshold: Some(10.0 * MIB as f64),
+        };
+        assert_eq!(rule.check("app", 10.0, MIB, 0.0), None);
+        // the limit itself is still fine, only going over it alerts
+        assert_eq!(rule.check("app", 80.0, 1024 * MIB, 10.0 * MIB as f64), None);
+        assert_eq!(rule.check("app", 80.5, MIB, 0.0), Some(Severity::Warning));
+        assert_eq!(rule.check("app", 10.0, 2048 * MIB, 0.0), Some(Severity::Warning));
+        assert_eq!(rule.check("app", 10.0, MIB, 20.0 * MIB as f64), Some(Severity::Warning));
+        assert_eq!(rule.check("app", 90.0, 2048 * MIB, 0.0), Some(Severity::Critical));
+        assert_eq!(rule.check("app", 90.0, 2048 * MIB, 20.0 * MIB as f64), Some(Severity::Critical));
+
+        // a rule without a memory limit never alerts on memory
+        let cpu_only = AlertRule { memory_threshold: None, io_threshold: None, ..rule };
+        assert_eq!(cpu_only.check("app", 10.0, 100 * 1024 * MIB, 0.0), None);
+        assert_eq!(cpu_only.check("app", 90.0, 100 * 1024 * MIB, 0.0), Some(Severity::Warning));
+    }
+}